@@ -0,0 +1,143 @@
+use byteorder::{ByteOrder, LittleEndian};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+};
+
+// Android boot image headers are in little endian
+const BOOT_MAGIC: &[u8] = b"ANDROID!";
+const VENDOR_BOOT_MAGIC: &[u8] = b"VNDRBOOT";
+
+// the page size of header version 3 and above is fixed
+const BOOT_IMAGE_V3_PAGE_SIZE: u32 = 4096;
+
+// the largest header is vendor_boot_img_hdr_v4
+const BOOT_HEADER_MAX_SIZE: usize = 2128;
+
+pub enum Kind {
+    Boot,
+    VendorBoot,
+}
+
+pub struct BootImage {
+    pub kind: Kind,
+    pub header_version: u32,
+    pub page_size: u32,
+    pub os_version: u32,
+    pub name: String,
+    pub cmdline: String,
+    // the kernel for boot image, or the vendor ramdisk for vendor_boot image
+    pub payload_offset: u64,
+    pub payload_size: u64,
+}
+
+fn cstr(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+impl fmt::Display for BootImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            Kind::Boot => "boot",
+            Kind::VendorBoot => "vendor_boot",
+        };
+        writeln!(
+            f,
+            "Android {kind} image v{}, page size {}",
+            self.header_version, self.page_size
+        )?;
+
+        // os_version packs version A.B.C and patch level Y-M:
+        // (A << 14 | B << 7 | C) << 11 | (Y - 2000) << 4 | M
+        if self.os_version != 0 {
+            let version = self.os_version >> 11;
+            writeln!(
+                f,
+                "OS version:   {}.{}.{}",
+                (version >> 14) & 0x7f,
+                (version >> 7) & 0x7f,
+                version & 0x7f
+            )?;
+            writeln!(
+                f,
+                "Patch level:  {}-{:02}",
+                ((self.os_version >> 4) & 0x7f) + 2000,
+                self.os_version & 0xf
+            )?;
+        }
+        if !self.name.is_empty() {
+            writeln!(f, "Name:         {}", self.name)?;
+        }
+        write!(f, "Command line: {}", self.cmdline)
+    }
+}
+
+pub fn parse(file: &mut File) -> Result<BootImage, io::Error> {
+    let mut header = vec![0u8; BOOT_HEADER_MAX_SIZE];
+    file.seek(SeekFrom::Start(0))?;
+    let len = Read::by_ref(file)
+        .take(BOOT_HEADER_MAX_SIZE as u64)
+        .read(&mut header)?;
+    if len < BOOT_MAGIC.len() {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    let image = if &header[..8] == BOOT_MAGIC {
+        let header_version = LittleEndian::read_u32(&header[40..44]);
+        match header_version {
+            // boot_img_hdr_v0, v1 and v2 share the same layout at the beginning
+            0..=2 => BootImage {
+                kind: Kind::Boot,
+                header_version,
+                page_size: LittleEndian::read_u32(&header[36..40]),
+                os_version: LittleEndian::read_u32(&header[44..48]),
+                name: cstr(&header[48..64]),
+                cmdline: cstr(&header[64..576]) + &cstr(&header[608..1632]),
+                payload_offset: LittleEndian::read_u32(&header[36..40]) as u64,
+                payload_size: LittleEndian::read_u32(&header[8..12]) as u64,
+            },
+            3 | 4 => BootImage {
+                kind: Kind::Boot,
+                header_version,
+                page_size: BOOT_IMAGE_V3_PAGE_SIZE,
+                os_version: LittleEndian::read_u32(&header[16..20]),
+                name: String::new(),
+                cmdline: cstr(&header[44..1580]),
+                payload_offset: BOOT_IMAGE_V3_PAGE_SIZE as u64,
+                payload_size: LittleEndian::read_u32(&header[8..12]) as u64,
+            },
+            _ => return Err(io::Error::from(ErrorKind::Unsupported)),
+        }
+    } else if &header[..8] == VENDOR_BOOT_MAGIC {
+        // vendor_boot image doesn't contain kernel, but the vendor ramdisk
+        // right after the header pages may contain kernel modules
+        let page_size = LittleEndian::read_u32(&header[12..16]);
+        let header_size = LittleEndian::read_u32(&header[2096..2100]);
+        if page_size == 0 {
+            return Err(io::Error::from(ErrorKind::InvalidData));
+        }
+        BootImage {
+            kind: Kind::VendorBoot,
+            header_version: LittleEndian::read_u32(&header[8..12]),
+            page_size,
+            os_version: 0,
+            name: cstr(&header[2080..2096]),
+            cmdline: cstr(&header[28..2076]),
+            payload_offset: header_size.div_ceil(page_size) as u64 * page_size as u64,
+            payload_size: LittleEndian::read_u32(&header[24..28]) as u64,
+        }
+    } else {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    };
+
+    if image.page_size == 0
+        || image.payload_size == 0
+        || image.payload_offset + image.payload_size > file.metadata()?.len()
+    {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    Ok(image)
+}
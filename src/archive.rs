@@ -0,0 +1,216 @@
+use crate::{cpio, tar};
+use flate2::read::DeflateDecoder;
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+};
+
+// zip file ends with the end of central directory record, which may be
+// followed by a comment up to 65535 bytes
+const ZIP_LOCAL_MAGIC: &[u8] = b"PK\x03\x04";
+const ZIP_CENTRAL_MAGIC: &[u8] = b"PK\x01\x02";
+const ZIP_END_MAGIC: &[u8] = b"PK\x05\x06";
+const ZIP_END_SIZE: u64 = 22;
+const ZIP_CENTRAL_SIZE: usize = 46;
+const ZIP_LOCAL_SIZE: u64 = 30;
+const ZIP_STORED: u16 = 0;
+const ZIP_DEFLATED: u16 = 8;
+
+pub struct Member {
+    pub path: String,
+    // the offset of the member data in archive, or its local header in zip
+    offset: u64,
+    size: u64,
+    // the compression method of zip
+    method: Option<u16>,
+}
+
+// the paths of kernels with their data, or the errors to extract them
+pub type Kernels = Vec<(String, Result<File, io::Error>)>;
+
+pub struct Archive {
+    pub format: &'static str,
+    pub members: Vec<Member>,
+}
+
+// the file names of kernel images and the module of in-kernel config
+pub fn is_kernel_name(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    matches!(
+        name,
+        "bzImage" | "zImage" | "uImage" | "Image" | "fitImage" | "kernel" | "boot.img"
+    ) || (name.starts_with("vmlinu") && !name.ends_with(".h"))
+        || name.starts_with("Image.")
+        || name.starts_with("configs.ko")
+        || (name.starts_with("kernel") && name.ends_with(".img"))
+}
+
+fn u16_le(data: &[u8]) -> u16 {
+    u16::from_le_bytes([data[0], data[1]])
+}
+
+fn u32_le(data: &[u8]) -> u32 {
+    u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn tar_members(file: &mut File) -> Result<Vec<Member>, io::Error> {
+    Ok(tar::entries(file)?
+        .into_iter()
+        .filter(|entry| matches!(entry.kind, tar::Kind::File))
+        .map(|entry| Member {
+            path: entry.path,
+            offset: entry.offset,
+            size: entry.size,
+            method: None,
+        })
+        .collect())
+}
+
+fn cpio_members(file: &mut File) -> Result<Vec<Member>, io::Error> {
+    Ok(cpio::parse(file, 0)?
+        .entries
+        .into_iter()
+        .filter(|entry| entry.mode & cpio::S_IFMT == cpio::S_IFREG)
+        .map(|entry| Member {
+            path: entry.path,
+            offset: entry.offset,
+            size: entry.size,
+            method: None,
+        })
+        .collect())
+}
+
+fn zip_members(file: &mut File) -> Result<Vec<Member>, io::Error> {
+    let invalid = || io::Error::from(ErrorKind::InvalidData);
+    let len = file.metadata()?.len();
+    let tail_size = len.min(ZIP_END_SIZE + u16::MAX as u64);
+    let mut tail = vec![0u8; tail_size as usize];
+    file.seek(SeekFrom::Start(len - tail_size))?;
+    file.read_exact(&mut tail)?;
+    let end = tail
+        .windows(ZIP_END_MAGIC.len())
+        .rposition(|window| window == ZIP_END_MAGIC)
+        .ok_or_else(invalid)?;
+    let end = tail
+        .get(end..end + ZIP_END_SIZE as usize)
+        .ok_or_else(invalid)?;
+    let count = u16_le(&end[10..12]) as usize;
+    let directory_size = u32_le(&end[12..16]) as usize;
+    let directory_offset = u32_le(&end[16..20]) as u64;
+    if directory_offset + directory_size as u64 > len {
+        return Err(io::Error::from(ErrorKind::UnexpectedEof));
+    }
+
+    let mut directory = vec![0u8; directory_size];
+    file.seek(SeekFrom::Start(directory_offset))?;
+    file.read_exact(&mut directory)?;
+
+    let mut members = Vec::new();
+    let mut entry = &directory[..];
+    for _ in 0..count {
+        if entry.len() < ZIP_CENTRAL_SIZE || !entry.starts_with(ZIP_CENTRAL_MAGIC) {
+            return Err(invalid());
+        }
+        let name_len = u16_le(&entry[28..30]) as usize;
+        let extra_len = u16_le(&entry[30..32]) as usize;
+        let comment_len = u16_le(&entry[32..34]) as usize;
+        let name = entry
+            .get(ZIP_CENTRAL_SIZE..ZIP_CENTRAL_SIZE + name_len)
+            .ok_or_else(invalid)?;
+        let path = String::from_utf8_lossy(name).into_owned();
+        // the directories end with '/'
+        if !path.ends_with('/') {
+            members.push(Member {
+                path,
+                offset: u32_le(&entry[42..46]) as u64,
+                size: u32_le(&entry[20..24]) as u64,
+                method: Some(u16_le(&entry[10..12])),
+            });
+        }
+        entry = entry
+            .get(ZIP_CENTRAL_SIZE + name_len + extra_len + comment_len..)
+            .ok_or_else(invalid)?;
+    }
+    Ok(members)
+}
+
+pub fn parse(file: &mut File) -> Result<Archive, io::Error> {
+    let mut header = [0u8; 512];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header[..6])?;
+    // tar archives shorter than a block are never valid
+    let _ = file.read_exact(&mut header[6..]);
+
+    let (format, members) = if tar::is_tar(&header) {
+        ("tar archive", tar_members(file)?)
+    } else if cpio::is_cpio(&header) {
+        ("cpio archive", cpio_members(file)?)
+    } else if header.starts_with(ZIP_LOCAL_MAGIC) {
+        ("zip archive", zip_members(file)?)
+    } else {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    };
+    Ok(Archive { format, members })
+}
+
+impl Archive {
+    // the data of a member, which is decompressed if deflated in zip
+    pub fn extract(&self, file: &mut File, member: &Member) -> Result<File, io::Error> {
+        let Some(method) = member.method else {
+            return crate::extract_payload(file, member.offset, member.size);
+        };
+
+        let mut header = [0u8; ZIP_LOCAL_SIZE as usize];
+        file.seek(SeekFrom::Start(member.offset))?;
+        file.read_exact(&mut header)?;
+        if !header.starts_with(ZIP_LOCAL_MAGIC) {
+            return Err(io::Error::from(ErrorKind::InvalidData));
+        }
+        let offset = member.offset
+            + ZIP_LOCAL_SIZE
+            + u16_le(&header[26..28]) as u64
+            + u16_le(&header[28..30]) as u64;
+        match method {
+            ZIP_STORED => crate::extract_payload(file, offset, member.size),
+            ZIP_DEFLATED => {
+                file.seek(SeekFrom::Start(offset))?;
+                let mut dst = tempfile::tempfile()?;
+                io::copy(
+                    &mut DeflateDecoder::new(Read::by_ref(file).take(member.size)),
+                    &mut dst,
+                )?;
+                Ok(dst)
+            }
+            _ => Err(io::Error::from(ErrorKind::Unsupported)),
+        }
+    }
+
+    // the members which look like kernels, with their paths
+    pub fn kernels(&self, file: &mut File, is_kernel: fn(&str) -> bool) -> Kernels {
+        self.members
+            .iter()
+            .filter(|member| is_kernel(&member.path))
+            .map(|member| (member.path.clone(), self.extract(file, member)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_kernel_name() {
+        assert!(is_kernel_name("arch/x86/boot/bzImage"));
+        assert!(is_kernel_name("out/vmlinux"));
+        assert!(is_kernel_name("boot/vmlinuz-6.1.0"));
+        assert!(is_kernel_name("Image.gz"));
+        assert!(is_kernel_name("boot/kernel8.img"));
+        assert!(is_kernel_name(
+            "lib/modules/6.1.0/kernel/kernel/configs.ko.xz"
+        ));
+        assert!(!is_kernel_name("include/vmlinux.h"));
+        assert!(!is_kernel_name("System.map"));
+        assert!(!is_kernel_name("modules/ext4.ko"));
+    }
+}
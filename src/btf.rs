@@ -0,0 +1,208 @@
+use argh::FromArgs;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+};
+
+// the magic 0xeB9F and version 1 of BTF in either byte order
+const BTF_MAGIC_LE: &str = r"\x9f\xeb\x01";
+const BTF_MAGIC_BE: &str = r"\xeb\x9f\x01";
+const BTF_HEADER_SIZE: usize = 24;
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "extract-btf",
+    description = "extract the BTF type information of a kernel image"
+)]
+pub struct ExtractBtfArgs {
+    #[argh(positional, description = "kernel image")]
+    image: String,
+
+    #[argh(
+        option,
+        short = 'o',
+        description = "output file of BTF, default to standard output"
+    )]
+    output: Option<String>,
+}
+
+// struct btf_header in include/uapi/linux/btf.h
+pub struct Header {
+    pub big_endian: bool,
+    pub hdr_len: u32,
+    pub type_off: u32,
+    pub type_len: u32,
+    pub str_off: u32,
+    pub str_len: u32,
+}
+
+impl Header {
+    pub fn parse(header: &[u8]) -> Result<Header, io::Error> {
+        let invalid = || io::Error::from(ErrorKind::InvalidData);
+        if header.len() < BTF_HEADER_SIZE {
+            return Err(invalid());
+        }
+        let big_endian = match &header[..2] {
+            b"\x9f\xeb" => false,
+            b"\xeb\x9f" => true,
+            _ => return Err(invalid()),
+        };
+        let u32_at = |offset: usize| {
+            if big_endian {
+                BigEndian::read_u32(&header[offset..])
+            } else {
+                LittleEndian::read_u32(&header[offset..])
+            }
+        };
+
+        let btf = Header {
+            big_endian,
+            hdr_len: u32_at(4),
+            type_off: u32_at(8),
+            type_len: u32_at(12),
+            str_off: u32_at(16),
+            str_len: u32_at(20),
+        };
+        // version 1 without flags, and the types are followed by the strings
+        // as checked by btf_parse_hdr() of the kernel
+        if header[2] != 1
+            || header[3] != 0
+            || (btf.hdr_len as usize) < BTF_HEADER_SIZE
+            || btf.type_off % 4 != 0
+            || btf.type_off as u64 + btf.type_len as u64 > btf.str_off as u64
+            || btf.str_len == 0
+        {
+            return Err(invalid());
+        }
+        Ok(btf)
+    }
+
+    // the size of the whole BTF blob including the header
+    pub fn size(&self) -> u64 {
+        self.hdr_len as u64 + self.str_off as u64 + self.str_len as u64
+    }
+}
+
+// read and validate the BTF blob at the offset
+fn read_btf(file: &mut File, offset: u64, max_size: u64) -> Result<(Header, Vec<u8>), io::Error> {
+    let mut header = [0u8; BTF_HEADER_SIZE];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut header)?;
+    let btf = Header::parse(&header)?;
+    let size = btf.size();
+    if size > max_size {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    let mut data = header.to_vec();
+    data.resize(size as usize, 0);
+    file.read_exact(&mut data[BTF_HEADER_SIZE..])?;
+
+    // the string section begins with an empty string and ends with NUL
+    let strings = &data[btf.hdr_len as usize + btf.str_off as usize..];
+    if strings.first() != Some(&0) || strings.last() != Some(&0) {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+    Ok((btf, data))
+}
+
+fn find_btf_elf(file: &mut File) -> Result<(u64, Header, Vec<u8>), io::Error> {
+    let elf = crate::elf::parse(file)?;
+    let section = elf
+        .sections
+        .iter()
+        .find(|section| section.name == ".BTF")
+        .ok_or_else(|| io::Error::from(ErrorKind::NotFound))?;
+    let (btf, data) = read_btf(file, section.offset, section.size)?;
+    Ok((section.offset, btf, data))
+}
+
+fn find_btf_raw(file: &mut File) -> Result<(u64, Header, Vec<u8>), io::Error> {
+    let len = file.metadata()?.len();
+    let mut found: Option<(u64, Header, Vec<u8>)> = None;
+    for pattern in [BTF_MAGIC_LE, BTF_MAGIC_BE] {
+        let mut start = 0;
+        while let Ok(offset) = crate::search_regex_from(file, pattern, start) {
+            start = offset + 1;
+            let Ok((btf, data)) = read_btf(file, offset, len - offset) else {
+                continue;
+            };
+            // the BTF of vmlinux is the largest one
+            let larger = match &found {
+                Some((_, _, found)) => data.len() > found.len(),
+                None => true,
+            };
+            if larger {
+                start = offset + data.len() as u64;
+                found = Some((offset, btf, data));
+            }
+        }
+    }
+    found.ok_or_else(|| io::Error::from(ErrorKind::NotFound))
+}
+
+pub fn find_btf(file: &mut File) -> Result<(u64, Header, Vec<u8>), io::Error> {
+    find_btf_elf(file).or_else(|_| find_btf_raw(file))
+}
+
+pub fn run(args: ExtractBtfArgs) {
+    let mut file = match File::open(&args.image) {
+        Ok(image) => image,
+        Err(err) => {
+            eprintln!("Failed to open file {}: {err}", &args.image);
+            return;
+        }
+    };
+
+    let found = crate::extract_vmlinux(&mut file).and_then(|mut vmlinux| find_btf(&mut vmlinux));
+    let (offset, btf, data) = match found {
+        Ok(found) => found,
+        Err(_) => {
+            eprintln!(
+                "{}: Cannot find BTF. Please confirm kernel compiled with CONFIG_DEBUG_INFO_BTF.",
+                env!("CARGO_BIN_NAME")
+            );
+            return;
+        }
+    };
+    eprintln!(
+        "Found {}-endian BTF at offset 0x{offset:x}: {} bytes of types, {} bytes of strings",
+        if btf.big_endian { "big" } else { "little" },
+        btf.type_len,
+        btf.str_len
+    );
+
+    let result = match &args.output {
+        Some(output) => fs::write(output, &data),
+        None => io::stdout().write_all(&data),
+    };
+    if let Err(err) = result {
+        eprintln!("Failed to write BTF: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_header() {
+        let mut header = b"\x9f\xeb\x01\x00\x18\x00\x00\x00\x00\x00\x00\x00\x10\x00\x00\x00\
+                           \x10\x00\x00\x00\x05\x00\x00\x00"
+            .to_vec();
+        let btf = Header::parse(&header).unwrap();
+        assert!(!btf.big_endian);
+        assert_eq!((btf.type_len, btf.str_off, btf.str_len), (16, 16, 5));
+        assert_eq!(btf.size(), 24 + 16 + 5);
+
+        // the strings overlap with the types
+        header[16] = 8;
+        assert!(Header::parse(&header).is_err());
+        header[16] = 16;
+        // version 2
+        header[2] = 2;
+        assert!(Header::parse(&header).is_err());
+    }
+}
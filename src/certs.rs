@@ -0,0 +1,329 @@
+use crate::der::{self, Tlv};
+use argh::FromArgs;
+use regex::bytes::RegexBuilder;
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind, Read, Seek},
+    path::Path,
+};
+
+// a SEQUENCE of certificate followed by a SEQUENCE of TBSCertificate,
+// both of which are longer than 255 bytes
+const CERT_PATTERN: &str = r"\x30\x82..\x30\x82";
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "extract-certs",
+    description = "extract the X.509 certificates built into a kernel image"
+)]
+pub struct ExtractCertsArgs {
+    #[argh(positional, description = "kernel image")]
+    image: String,
+
+    #[argh(
+        option,
+        short = 'd',
+        default = "String::from(\".\")",
+        description = "output directory of the .pem files, default to current directory"
+    )]
+    directory: String,
+
+    #[argh(switch, short = 'l', description = "only list the certificates")]
+    list: bool,
+}
+
+pub struct Certificate {
+    pub der: Vec<u8>,
+    pub serial: String,
+    pub issuer: String,
+    pub subject: String,
+    pub not_before: String,
+    pub not_after: String,
+    // to match the signer of a PKCS#7 message by issuer and serial number,
+    // or by subject key identifier
+    pub raw_issuer: Vec<u8>,
+    pub raw_serial: Vec<u8>,
+    pub subject_key_id: Option<Vec<u8>>,
+    pub public_key: Vec<u8>,
+}
+
+// the short names of attributes in distinguished names
+fn attribute_name(oid: &[u8]) -> String {
+    match oid {
+        b"\x55\x04\x03" => String::from("CN"),
+        b"\x55\x04\x06" => String::from("C"),
+        b"\x55\x04\x07" => String::from("L"),
+        b"\x55\x04\x08" => String::from("ST"),
+        b"\x55\x04\x0a" => String::from("O"),
+        b"\x55\x04\x0b" => String::from("OU"),
+        b"\x2a\x86\x48\x86\xf7\x0d\x01\x09\x01" => String::from("emailAddress"),
+        _ => der::oid_string(oid),
+    }
+}
+
+// Name, e.g. "CN=Build time autogenerated kernel key, O=Arch Linux"
+pub fn name(tlv: Tlv) -> Result<String, io::Error> {
+    let invalid = || io::Error::from(ErrorKind::InvalidData);
+    let mut attributes = Vec::new();
+    for rdn in tlv.expect(der::TAG_SEQUENCE)?.children()? {
+        for attribute in rdn.expect(der::TAG_SET)?.children()? {
+            let [oid, value] =
+                <[Tlv; 2]>::try_from(attribute.expect(der::TAG_SEQUENCE)?.children()?)
+                    .map_err(|_| invalid())?;
+            let oid = oid.expect(der::TAG_OID)?;
+            let value = der::string(&value).ok_or_else(invalid)?;
+            attributes.push(format!("{}={value}", attribute_name(oid.value)));
+        }
+    }
+    Ok(attributes.join(", "))
+}
+
+// the serial number in hexadecimal, e.g. "12:ab:34"
+fn serial(tlv: Tlv) -> Result<String, io::Error> {
+    let value = tlv.expect(der::TAG_INTEGER)?.value;
+    // the leading zero is only a sign
+    let value = match value {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        _ => value,
+    };
+    Ok(value
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(":"))
+}
+
+// the extension of subject key identifier, i.e. 2.5.29.14
+fn subject_key_id(extensions: &Tlv) -> Option<Vec<u8>> {
+    let extensions = Tlv::parse(extensions.value).ok()?.0;
+    for extension in extensions.children().ok()? {
+        let fields = extension.children().ok()?;
+        if fields.first()?.value == b"\x55\x1d\x0e" {
+            let value = fields.last()?.value;
+            let (key_id, _) = Tlv::parse(value).ok()?;
+            return Some(key_id.expect(der::TAG_OCTET_STRING).ok()?.value.to_vec());
+        }
+    }
+    None
+}
+
+impl Certificate {
+    // the certificate at the beginning of data, as defined by RFC 5280
+    pub fn parse(data: &[u8]) -> Result<Certificate, io::Error> {
+        let invalid = || io::Error::from(ErrorKind::InvalidData);
+        let (cert, _) = Tlv::parse(data)?;
+        let der = cert.raw.to_vec();
+        let [tbs, algorithm, signature] =
+            <[Tlv; 3]>::try_from(cert.expect(der::TAG_SEQUENCE)?.children()?)
+                .map_err(|_| invalid())?;
+        algorithm.expect(der::TAG_SEQUENCE)?;
+        signature.expect(der::TAG_BIT_STRING)?;
+
+        let mut fields = tbs.expect(der::TAG_SEQUENCE)?.children()?.into_iter();
+        let mut field = fields.next().ok_or_else(invalid)?;
+        // the explicit version, which is absent in v1
+        if field.tag == 0xa0 {
+            field = fields.next().ok_or_else(invalid)?;
+        }
+        let raw_serial = field.value.to_vec();
+        let serial = serial(field)?;
+        fields
+            .next()
+            .ok_or_else(invalid)?
+            .expect(der::TAG_SEQUENCE)?;
+        let issuer = fields.next().ok_or_else(invalid)?;
+        let raw_issuer = issuer.raw.to_vec();
+        let issuer = name(issuer)?;
+        let validity = fields
+            .next()
+            .ok_or_else(invalid)?
+            .expect(der::TAG_SEQUENCE)?
+            .children()?;
+        let subject = name(fields.next().ok_or_else(invalid)?)?;
+        let public_key = fields
+            .next()
+            .ok_or_else(invalid)?
+            .expect(der::TAG_SEQUENCE)?
+            .raw
+            .to_vec();
+        // the explicit extensions after the optional unique identifiers
+        let subject_key_id = fields
+            .find(|field| field.tag == 0xa3)
+            .and_then(|extensions| subject_key_id(&extensions));
+
+        let (not_before, not_after) = match validity.as_slice() {
+            [not_before, not_after] => (der::time(not_before), der::time(not_after)),
+            _ => return Err(invalid()),
+        };
+        Ok(Certificate {
+            der,
+            serial,
+            issuer,
+            subject,
+            not_before: not_before.ok_or_else(invalid)?,
+            not_after: not_after.ok_or_else(invalid)?,
+            raw_issuer,
+            raw_serial,
+            subject_key_id,
+            public_key,
+        })
+    }
+}
+
+// the concatenated certificates between system_certificate_list and its end
+fn find_cert_list(vmlinux: &mut File) -> Result<(u64, u64), io::Error> {
+    let elf = crate::elf::parse(vmlinux)?;
+    let symbols = elf.symbols(vmlinux)?;
+    if elf
+        .symbol(&symbols, "system_certificate_list_end")
+        .is_some()
+    {
+        return elf
+            .symbol_range(
+                &symbols,
+                "system_certificate_list",
+                "system_certificate_list_end",
+            )
+            .ok_or_else(|| io::Error::from(ErrorKind::NotFound));
+    }
+
+    // system_certificate_list_size is a variable since v5.13
+    let start = elf
+        .symbol(&symbols, "system_certificate_list")
+        .and_then(|symbol| elf.symbol_offset(symbol))
+        .ok_or_else(|| io::Error::from(ErrorKind::NotFound))?;
+    let (offset, size) = elf
+        .symbol(&symbols, "system_certificate_list_size")
+        .and_then(|symbol| elf.symbol_offset(symbol))
+        .ok_or_else(|| io::Error::from(ErrorKind::NotFound))?;
+    let size_of_size = if elf.class64 { 8 } else { 4 };
+    if size < size_of_size as u64 {
+        return Err(io::Error::from(ErrorKind::NotFound));
+    }
+    let mut value = vec![0u8; size_of_size];
+    vmlinux.seek(io::SeekFrom::Start(offset))?;
+    vmlinux.read_exact(&mut value)?;
+    let size = if elf.class64 {
+        elf.u64(&value)
+    } else {
+        elf.u32(&value) as u64
+    };
+    // the list can't go beyond the section containing it
+    Ok((start.0, size.min(start.1)))
+}
+
+// the ranges to search for the certificates without the list symbols, i.e.
+// the init data sections of a vmlinux, or the whole image if it's raw and
+// its sections are unknown
+fn search_ranges(vmlinux: &mut File, len: usize) -> Vec<(usize, usize)> {
+    let ranges: Vec<(usize, usize)> = crate::elf::parse(vmlinux)
+        .map(|elf| {
+            elf.sections
+                .iter()
+                .filter(|section| [".init.data", ".init.rodata"].contains(&section.name.as_str()))
+                .map(|section| {
+                    let end = section.offset.saturating_add(section.size);
+                    (section.offset as usize, end.min(len as u64) as usize)
+                })
+                .filter(|(start, end)| start < end)
+                .collect()
+        })
+        .unwrap_or_default();
+    if ranges.is_empty() {
+        vec![(0, len)]
+    } else {
+        ranges
+    }
+}
+
+pub fn find_certs(vmlinux: &mut File) -> Result<Vec<(u64, Certificate)>, io::Error> {
+    let mut data = Vec::new();
+    vmlinux.rewind()?;
+    vmlinux.read_to_end(&mut data)?;
+
+    let mut certs: Vec<(u64, Certificate)> = Vec::new();
+    if let Ok((offset, size)) = find_cert_list(vmlinux) {
+        let end = offset.saturating_add(size).min(data.len() as u64) as usize;
+        let mut offset = offset as usize;
+        // the space reserved for CONFIG_SYSTEM_EXTRA_CERTIFICATE is zeroed
+        while offset < end {
+            if data[offset] == 0 {
+                offset += 1;
+                continue;
+            }
+            let Ok(cert) = Certificate::parse(&data[offset..end]) else {
+                break;
+            };
+            let len = cert.der.len();
+            certs.push((offset as u64, cert));
+            offset += len;
+        }
+        return Ok(certs);
+    }
+
+    let re = RegexBuilder::new(CERT_PATTERN)
+        .unicode(false)
+        .dot_matches_new_line(true)
+        .build()
+        .map_err(|_| io::Error::from(ErrorKind::InvalidInput))?;
+    for (mut start, end) in search_ranges(vmlinux, data.len()) {
+        let data = &data[..end];
+        while let Some(m) = re.find_at(data, start) {
+            start = m.start() + 1;
+            let Ok(cert) = Certificate::parse(&data[m.start()..]) else {
+                continue;
+            };
+            start = m.start() + cert.der.len();
+            if certs.iter().all(|(_, found)| found.der != cert.der) {
+                certs.push((m.start() as u64, cert));
+            }
+        }
+    }
+    Ok(certs)
+}
+
+pub fn run(args: ExtractCertsArgs) {
+    let mut file = match File::open(&args.image) {
+        Ok(image) => image,
+        Err(err) => {
+            eprintln!("Failed to open file {}: {err}", &args.image);
+            return;
+        }
+    };
+
+    let certs = crate::extract_vmlinux(&mut file)
+        .and_then(|mut vmlinux| find_certs(&mut vmlinux))
+        .unwrap_or_default();
+    if certs.is_empty() {
+        eprintln!(
+            "{}: Cannot find X.509 certificates. Please confirm kernel compiled with CONFIG_SYSTEM_TRUSTED_KEYRING.",
+            env!("CARGO_BIN_NAME")
+        );
+        return;
+    }
+
+    if !args.list {
+        if let Err(err) = fs::create_dir_all(&args.directory) {
+            eprintln!("Failed to create directory {}: {err}", &args.directory);
+            return;
+        }
+    }
+
+    for (index, (offset, cert)) in certs.iter().enumerate() {
+        let name = format!("cert-{index}.pem");
+        println!("{name}: {} bytes at 0x{offset:x}", cert.der.len());
+        println!("  Subject:    {}", cert.subject);
+        println!("  Issuer:     {}", cert.issuer);
+        println!("  Serial:     {}", cert.serial);
+        println!("  Not before: {}", cert.not_before);
+        println!("  Not after:  {}", cert.not_after);
+
+        if !args.list {
+            let path = Path::new(&args.directory).join(&name);
+            if let Err(err) = fs::write(&path, der::pem("CERTIFICATE", &cert.der)) {
+                eprintln!("Failed to write {}: {err}", path.display());
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use byteorder::{ByteOrder, LittleEndian};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+};
+
+// ChromeOS kernel partition begins with struct vb2_keyblock,
+// followed by struct vb2_kernel_preamble and then the body
+const KEYBLOCK_MAGIC: &[u8] = b"CHROMEOS";
+const KEYBLOCK_HEADER_SIZE: usize = 112;
+const PREAMBLE_HEADER_SIZE: usize = 116;
+
+// the body is laid out as kernel, command line, boot params and bootloader
+const CROS_CONFIG_SIZE: u64 = 4096;
+const CROS_PARAMS_SIZE: u64 = 4096;
+
+pub struct VBlock {
+    pub keyblock_version: (u32, u32),
+    pub preamble_version: (u32, u32),
+    pub kernel_version: u32,
+    pub body_load_address: u64,
+    pub bootloader_address: u64,
+    pub flags: u32,
+    pub cmdline: String,
+    pub body_offset: u64,
+    pub body_size: u64,
+}
+
+impl fmt::Display for VBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "ChromeOS kernel partition, keyblock v{}.{}, preamble v{}.{}",
+            self.keyblock_version.0,
+            self.keyblock_version.1,
+            self.preamble_version.0,
+            self.preamble_version.1
+        )?;
+        writeln!(f, "Kernel version: {}", self.kernel_version)?;
+        writeln!(f, "Body load:      0x{:x}", self.body_load_address)?;
+        writeln!(f, "Body size:      {}", self.body_size)?;
+        writeln!(f, "Bootloader:     0x{:x}", self.bootloader_address)?;
+        writeln!(f, "Flags:          0x{:x}", self.flags)?;
+        write!(f, "Command line:   {}", self.cmdline)
+    }
+}
+
+pub fn parse(file: &mut File) -> Result<VBlock, io::Error> {
+    let mut keyblock = [0u8; KEYBLOCK_HEADER_SIZE];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut keyblock)?;
+    if &keyblock[..8] != KEYBLOCK_MAGIC {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+    let keyblock_size = LittleEndian::read_u32(&keyblock[16..20]) as u64;
+
+    let mut preamble = [0u8; PREAMBLE_HEADER_SIZE];
+    file.seek(SeekFrom::Start(keyblock_size))?;
+    file.read_exact(&mut preamble)?;
+    let preamble_size = LittleEndian::read_u32(&preamble[0..4]) as u64;
+    let preamble_version = (
+        LittleEndian::read_u32(&preamble[32..36]),
+        LittleEndian::read_u32(&preamble[36..40]),
+    );
+
+    // flags are only available since preamble v2.2
+    let flags = if preamble_version >= (2, 2) {
+        LittleEndian::read_u32(&preamble[112..116])
+    } else {
+        0
+    };
+
+    let mut vblock = VBlock {
+        keyblock_version: (
+            LittleEndian::read_u32(&keyblock[8..12]),
+            LittleEndian::read_u32(&keyblock[12..16]),
+        ),
+        preamble_version,
+        kernel_version: LittleEndian::read_u32(&preamble[40..44]),
+        body_load_address: LittleEndian::read_u64(&preamble[48..56]),
+        bootloader_address: LittleEndian::read_u64(&preamble[56..64]),
+        flags,
+        cmdline: String::new(),
+        body_offset: keyblock_size + preamble_size,
+        // data_size of body_signature
+        body_size: LittleEndian::read_u32(&preamble[88..92]) as u64,
+    };
+
+    if vblock.preamble_version.0 != 2
+        || vblock.body_size == 0
+        || vblock.body_offset + vblock.body_size > file.metadata()?.len()
+    {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    // command line locates right before the boot params and bootloader
+    let cmdline_offset = vblock
+        .body_load_address
+        .checked_add(CROS_CONFIG_SIZE + CROS_PARAMS_SIZE)
+        .and_then(|end| vblock.bootloader_address.checked_sub(end));
+    if let Some(offset) = cmdline_offset.filter(|offset| offset < &vblock.body_size) {
+        let mut cmdline = vec![0u8; CROS_CONFIG_SIZE as usize];
+        file.seek(SeekFrom::Start(vblock.body_offset + offset))?;
+        let len = Read::by_ref(file)
+            .take(CROS_CONFIG_SIZE)
+            .read(&mut cmdline)?;
+        let len = cmdline[..len].iter().position(|&b| b == 0).unwrap_or(len);
+        vblock.cmdline = String::from_utf8_lossy(&cmdline[..len]).into_owned();
+    }
+
+    Ok(vblock)
+}
@@ -0,0 +1,145 @@
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+};
+
+// "070701" for newc format, and "070702" for newc with checksum
+pub const NEWC_MAGIC: &[u8] = b"07070";
+const HEADER_SIZE: u64 = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFLNK: u32 = 0o120000;
+
+pub struct Entry {
+    pub path: String,
+    pub mode: u32,
+    pub size: u64,
+    // the offset of the entry data in archive
+    pub offset: u64,
+}
+
+pub struct Archive {
+    pub entries: Vec<Entry>,
+    // the size till the end of the last trailer
+    pub size: u64,
+}
+
+fn hex(field: &[u8]) -> Result<u32, io::Error> {
+    std::str::from_utf8(field)
+        .ok()
+        .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+        .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))
+}
+
+fn align4(offset: u64) -> u64 {
+    offset.div_ceil(4) * 4
+}
+
+pub fn is_cpio(header: &[u8]) -> bool {
+    header.starts_with(NEWC_MAGIC) && matches!(header.get(5), Some(b'1' | b'2'))
+}
+
+// parse the newc archives starting from offset, the kernel accepts
+// multiple archives concatenated with zero paddings in between
+pub fn parse(file: &mut File, start: u64) -> Result<Archive, io::Error> {
+    let len = file.metadata()?.len();
+    let mut entries = Vec::new();
+    let mut offset = start;
+    let mut end = start;
+    let mut header = [0u8; HEADER_SIZE as usize];
+
+    while offset + HEADER_SIZE <= len {
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header)?;
+        if header.starts_with(b"\0\0\0\0") && end != start {
+            let zeros = header
+                .chunks_exact(4)
+                .take_while(|word| word == b"\0\0\0\0");
+            offset += zeros.count() as u64 * 4;
+            continue;
+        }
+        if !is_cpio(&header) {
+            break;
+        }
+
+        let mode = hex(&header[14..22])?;
+        let size = hex(&header[54..62])? as u64;
+        let namesize = hex(&header[94..102])? as u64;
+        if namesize == 0 {
+            return Err(io::Error::from(ErrorKind::InvalidData));
+        }
+
+        let data_offset = align4(offset + HEADER_SIZE + namesize);
+        if data_offset + size > len {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+
+        let mut name = vec![0u8; namesize as usize];
+        file.read_exact(&mut name)?;
+        let path = String::from_utf8_lossy(&name[..name.len() - 1]).into_owned();
+        offset = align4(data_offset + size);
+
+        if path == TRAILER {
+            end = offset;
+        } else {
+            entries.push(Entry {
+                path,
+                mode,
+                size,
+                offset: data_offset,
+            });
+        }
+    }
+
+    if end == start {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+    Ok(Archive {
+        entries,
+        size: end - start,
+    })
+}
+
+pub fn read_entry(file: &mut File, entry: &Entry) -> Result<Vec<u8>, io::Error> {
+    let mut data = vec![0u8; entry.size as usize];
+    file.seek(SeekFrom::Start(entry.offset))?;
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+// the file mode in the form of `ls -l`, e.g. drwxr-xr-x
+pub fn mode_string(mode: u32) -> String {
+    let kind = match mode & S_IFMT {
+        0o140000 => 's',
+        S_IFLNK => 'l',
+        0o060000 => 'b',
+        0o040000 => 'd',
+        0o020000 => 'c',
+        0o010000 => 'p',
+        _ => '-',
+    };
+    let mut string = String::from(kind);
+    for (i, permission) in "rwxrwxrwx".chars().enumerate() {
+        string.push(if mode & (0o400 >> i) != 0 {
+            permission
+        } else {
+            '-'
+        });
+    }
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_string() {
+        assert_eq!(mode_string(0o040755), "drwxr-xr-x");
+        assert_eq!(mode_string(0o100644), "-rw-r--r--");
+        assert_eq!(mode_string(0o120777), "lrwxrwxrwx");
+        assert_eq!(mode_string(0o020600), "crw-------");
+    }
+}
@@ -0,0 +1,230 @@
+use std::io::{self, ErrorKind};
+
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_BIT_STRING: u8 = 0x03;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_UTF8_STRING: u8 = 0x0c;
+pub const TAG_PRINTABLE_STRING: u8 = 0x13;
+pub const TAG_T61_STRING: u8 = 0x14;
+pub const TAG_IA5_STRING: u8 = 0x16;
+pub const TAG_UTC_TIME: u8 = 0x17;
+pub const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub const TAG_BMP_STRING: u8 = 0x1e;
+pub const TAG_SEQUENCE: u8 = 0x30;
+pub const TAG_SET: u8 = 0x31;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// a DER encoded TLV, i.e. tag, length and value
+pub struct Tlv<'a> {
+    pub tag: u8,
+    pub value: &'a [u8],
+    // the whole encoding including the tag and length
+    pub raw: &'a [u8],
+}
+
+impl<'a> Tlv<'a> {
+    // the TLV at the beginning of data and the bytes after it
+    pub fn parse(data: &'a [u8]) -> Result<(Tlv<'a>, &'a [u8]), io::Error> {
+        let invalid = || io::Error::from(ErrorKind::InvalidData);
+        let (&tag, rest) = data.split_first().ok_or_else(invalid)?;
+        // high tag numbers aren't used in certificates
+        if tag & 0x1f == 0x1f {
+            return Err(invalid());
+        }
+        let (&len, mut rest) = rest.split_first().ok_or_else(invalid)?;
+        let len = if len & 0x80 == 0 {
+            len as usize
+        } else {
+            // the long form of length in at most 4 bytes, without indefinite length
+            let count = (len & 0x7f) as usize;
+            if count == 0 || count > 4 || rest.len() < count {
+                return Err(invalid());
+            }
+            let len = rest[..count]
+                .iter()
+                .fold(0usize, |len, &b| (len << 8) | b as usize);
+            rest = &rest[count..];
+            len
+        };
+        if rest.len() < len {
+            return Err(invalid());
+        }
+
+        let header = data.len() - rest.len();
+        let tlv = Tlv {
+            tag,
+            value: &rest[..len],
+            raw: &data[..header + len],
+        };
+        Ok((tlv, &rest[len..]))
+    }
+
+    // the TLVs within a constructed one
+    pub fn children(&self) -> Result<Vec<Tlv<'a>>, io::Error> {
+        let mut children = Vec::new();
+        let mut rest = self.value;
+        while !rest.is_empty() {
+            let (child, next) = Tlv::parse(rest)?;
+            children.push(child);
+            rest = next;
+        }
+        Ok(children)
+    }
+
+    pub fn expect(self, tag: u8) -> Result<Tlv<'a>, io::Error> {
+        if self.tag == tag {
+            Ok(self)
+        } else {
+            Err(io::Error::from(ErrorKind::InvalidData))
+        }
+    }
+}
+
+// the dotted form of an object identifier, e.g. "2.5.4.3"
+pub fn oid_string(value: &[u8]) -> String {
+    let mut arcs = Vec::new();
+    let mut arc = 0u64;
+    for &b in value {
+        arc = (arc << 7) | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (arc / 40).min(2);
+                arcs.push(first);
+                arcs.push(arc - first * 40);
+            } else {
+                arcs.push(arc);
+            }
+            arc = 0;
+        }
+    }
+    arcs.iter()
+        .map(|arc| arc.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+// the text of a string type
+pub fn string(tlv: &Tlv) -> Option<String> {
+    match tlv.tag {
+        TAG_UTF8_STRING | TAG_PRINTABLE_STRING | TAG_T61_STRING | TAG_IA5_STRING => {
+            Some(String::from_utf8_lossy(tlv.value).into_owned())
+        }
+        TAG_BMP_STRING => {
+            let chars: Vec<u16> = tlv
+                .value
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&chars))
+        }
+        _ => None,
+    }
+}
+
+// UTCTime or GeneralizedTime, e.g. "221118103100Z", as "2022-11-18 10:31:00 UTC"
+pub fn time(tlv: &Tlv) -> Option<String> {
+    let text = std::str::from_utf8(tlv.value).ok()?;
+    let text = text.strip_suffix('Z')?;
+    let (year, rest) = match tlv.tag {
+        TAG_UTC_TIME => {
+            let year: u32 = text.get(..2)?.parse().ok()?;
+            // RFC 5280, two digit years from 50 are of the 20th century
+            (if year >= 50 { 1900 } else { 2000 } + year, &text[2..])
+        }
+        TAG_GENERALIZED_TIME => (text.get(..4)?.parse().ok()?, &text[4..]),
+        _ => return None,
+    };
+    if rest.len() != 10 || !rest.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!(
+        "{year:04}-{}-{} {}:{}:{} UTC",
+        &rest[..2],
+        &rest[2..4],
+        &rest[4..6],
+        &rest[6..8],
+        &rest[8..]
+    ))
+}
+
+fn base64(data: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64[(bits >> (18 - i * 6)) as usize & 0x3f] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+// PEM encoding with 64 characters per line, e.g. of label "CERTIFICATE"
+pub fn pem(label: &str, der: &[u8]) -> String {
+    let encoded = base64(der);
+    let mut text = format!("-----BEGIN {label}-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        text.push_str(std::str::from_utf8(line).unwrap_or_default());
+        text.push('\n');
+    }
+    text.push_str(&format!("-----END {label}-----\n"));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tlv() {
+        let data = b"\x30\x81\x03\x02\x01\x05\xff";
+        let (tlv, rest) = Tlv::parse(data).unwrap();
+        assert_eq!(tlv.tag, TAG_SEQUENCE);
+        assert_eq!(tlv.raw, &data[..6]);
+        assert_eq!(rest, b"\xff");
+        let children = tlv.children().unwrap();
+        assert_eq!(children[0].tag, TAG_INTEGER);
+        assert_eq!(children[0].value, b"\x05");
+
+        assert!(Tlv::parse(b"\x30\x82\x01").is_err());
+        assert!(Tlv::parse(b"\x30\x80\x00\x00").is_err());
+    }
+
+    #[test]
+    fn test_oid_time() {
+        assert_eq!(oid_string(b"\x55\x04\x03"), "2.5.4.3");
+        assert_eq!(
+            oid_string(b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x0b"),
+            "1.2.840.113549.1.1.11"
+        );
+        let utc = Tlv::parse(b"\x17\x0d221118103100Z").unwrap().0;
+        assert_eq!(time(&utc).as_deref(), Some("2022-11-18 10:31:00 UTC"));
+        let generalized = Tlv::parse(b"\x18\x0f21221025103100Z").unwrap().0;
+        assert_eq!(
+            time(&generalized).as_deref(),
+            Some("2122-10-25 10:31:00 UTC")
+        );
+    }
+
+    #[test]
+    fn test_pem() {
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(
+            pem("CERTIFICATE", b"foo"),
+            "-----BEGIN CERTIFICATE-----\nZm9v\n-----END CERTIFICATE-----\n"
+        );
+    }
+}
@@ -0,0 +1,385 @@
+use crate::{archive, erofs, ext4, fat, squashfs};
+use byteorder::{ByteOrder, LittleEndian};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+};
+
+const SECTOR_SIZE: u64 = 512;
+
+// GPT header is at LBA 1, whose size is usually 512 but may be 4096
+const GPT_SIGNATURE: &[u8] = b"EFI PART";
+const GPT_SECTOR_SIZES: [u64; 2] = [512, 4096];
+const GPT_ENTRY_MIN_SIZE: usize = 128;
+const GPT_ENTRIES_MAX_SIZE: usize = 1 << 20;
+
+// MBR has 4 primary partition entries before the boot signature
+const MBR_ENTRIES_OFFSET: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+const MBR_TYPE_PROTECTIVE: u8 = 0xee;
+const BOOT_SIGNATURE: &[u8] = b"\x55\xaa";
+
+// a file or directory in filesystems, where node is the inode of ext2/3/4
+// or the first cluster of FAT
+pub struct Entry {
+    pub name: String,
+    pub is_dir: bool,
+    pub is_file: bool,
+    pub node: u64,
+    pub size: u64,
+}
+
+enum Filesystem {
+    Fat(fat::Fat),
+    Ext(ext4::Ext4),
+    Squashfs(squashfs::Squashfs),
+    Erofs(erofs::Erofs),
+}
+
+// the depth of directories to walk, which avoids loops in corrupted
+// filesystems
+const WALK_MAX_DEPTH: usize = 32;
+
+// whether the directories or files of a path are searched
+type PathFilter = fn(&str) -> bool;
+
+impl Filesystem {
+    fn name(&self) -> &'static str {
+        match self {
+            Filesystem::Fat(fat) => fat.name(),
+            Filesystem::Ext(ext) => ext.name(),
+            Filesystem::Squashfs(squashfs) => squashfs.name(),
+            Filesystem::Erofs(erofs) => erofs.name(),
+        }
+    }
+
+    // read-only filesystems of firmware, rather than those of disks
+    fn is_read_only(&self) -> bool {
+        matches!(self, Filesystem::Squashfs(_) | Filesystem::Erofs(_))
+    }
+
+    fn read_root(&self, file: &mut File) -> Result<Vec<Entry>, io::Error> {
+        match self {
+            Filesystem::Fat(fat) => fat.read_root(file),
+            Filesystem::Ext(ext) => ext.read_root(file),
+            Filesystem::Squashfs(squashfs) => squashfs.read_root(file),
+            Filesystem::Erofs(erofs) => erofs.read_root(file),
+        }
+    }
+
+    fn read_entries(&self, file: &mut File, dir: &Entry) -> Result<Vec<Entry>, io::Error> {
+        match self {
+            Filesystem::Fat(fat) => fat.read_entries(file, dir),
+            Filesystem::Ext(ext) => ext.read_entries(file, dir),
+            Filesystem::Squashfs(squashfs) => squashfs.read_entries(file, dir),
+            Filesystem::Erofs(erofs) => erofs.read_entries(file, dir),
+        }
+    }
+
+    // the regular files looking like kernels or configs with their paths,
+    // which are searched in the boot directories of disks, or everywhere in
+    // read-only filesystems of firmware, e.g. configs.ko in lib/modules
+    fn boot_files(&self, file: &mut File) -> Vec<(String, Entry)> {
+        let (is_searched, is_wanted): (PathFilter, PathFilter) = if self.is_read_only() {
+            (|_| true, archive::is_kernel_name)
+        } else {
+            (is_boot_dir, is_boot_path)
+        };
+
+        let mut files = Vec::new();
+        let mut dirs = match self.read_root(file) {
+            Ok(entries) => vec![(String::new(), entries, 0)],
+            Err(_) => Vec::new(),
+        };
+        while let Some((dir, entries, depth)) = dirs.pop() {
+            for entry in entries {
+                let path = format!("{dir}/{}", entry.name);
+                if entry.is_dir && depth < WALK_MAX_DEPTH && is_searched(&path) {
+                    if let Ok(entries) = self.read_entries(file, &entry) {
+                        dirs.push((path, entries, depth + 1));
+                    }
+                } else if entry.is_file && is_wanted(&path) {
+                    files.push((path, entry));
+                }
+            }
+        }
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+        files
+    }
+
+    fn read_file(&self, file: &mut File, entry: &Entry) -> Result<File, io::Error> {
+        match self {
+            Filesystem::Fat(fat) => fat.read_file(file, entry),
+            Filesystem::Ext(ext) => ext.read_file(file, entry),
+            Filesystem::Squashfs(squashfs) => squashfs.read_file(file, entry),
+            Filesystem::Erofs(erofs) => erofs.read_file(file, entry),
+        }
+    }
+}
+
+fn parse_filesystem(file: &mut File, offset: u64) -> Option<Filesystem> {
+    if let Ok(ext) = ext4::parse(file, offset) {
+        return Some(Filesystem::Ext(ext));
+    }
+    if let Ok(squashfs) = squashfs::parse(file, offset) {
+        return Some(Filesystem::Squashfs(squashfs));
+    }
+    if let Ok(erofs) = erofs::parse(file, offset) {
+        return Some(Filesystem::Erofs(erofs));
+    }
+    fat::parse(file, offset).ok().map(Filesystem::Fat)
+}
+
+struct Partition {
+    // the partition number as in /dev/sda1, or none if not partitioned
+    number: Option<usize>,
+    size: u64,
+    name: Option<String>,
+    filesystem: Filesystem,
+}
+
+pub struct Disk {
+    pub format: &'static str,
+    partitions: Vec<Partition>,
+}
+
+impl fmt::Display for Disk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format)?;
+        for partition in &self.partitions {
+            let label = match partition.number {
+                Some(number) => format!("Partition {number}:"),
+                None => String::from("Filesystem:"),
+            };
+            write!(
+                f,
+                "\n{label:16}{} of {} bytes",
+                partition.filesystem.name(),
+                partition.size
+            )?;
+            if let Some(name) = &partition.name {
+                write!(f, " ({name})")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// the offsets, sizes and names of GPT partitions
+fn gpt_partitions(file: &mut File) -> Option<Vec<(u64, u64, Option<String>)>> {
+    let mut header = [0u8; 92];
+    let sector_size = GPT_SECTOR_SIZES.into_iter().find(|&sector_size| {
+        file.seek(SeekFrom::Start(sector_size)).is_ok()
+            && file.read_exact(&mut header).is_ok()
+            && header.starts_with(GPT_SIGNATURE)
+    })?;
+    let entries_lba = LittleEndian::read_u64(&header[72..80]);
+    let count = LittleEndian::read_u32(&header[80..84]) as usize;
+    let entry_size = LittleEndian::read_u32(&header[84..88]) as usize;
+    let size = count.checked_mul(entry_size)?;
+    if entry_size < GPT_ENTRY_MIN_SIZE || size > GPT_ENTRIES_MAX_SIZE {
+        return None;
+    }
+    let entries_offset = entries_lba.checked_mul(sector_size)?;
+    if entries_offset.saturating_add(size as u64) > file.metadata().ok()?.len() {
+        return None;
+    }
+
+    let mut entries = vec![0u8; size];
+    file.seek(SeekFrom::Start(entries_offset)).ok()?;
+    file.read_exact(&mut entries).ok()?;
+    let partitions = entries
+        .chunks_exact(entry_size)
+        .map(|entry| {
+            // the unused entries have zero type GUID
+            if entry[0..16].iter().all(|&b| b == 0) {
+                return None;
+            }
+            let first = LittleEndian::read_u64(&entry[32..40]);
+            let last = LittleEndian::read_u64(&entry[40..48]);
+            let name: Vec<u16> = entry[56..128]
+                .chunks_exact(2)
+                .map(LittleEndian::read_u16)
+                .take_while(|&c| c != 0)
+                .collect();
+            let name = (!name.is_empty()).then(|| String::from_utf16_lossy(&name));
+            Some((
+                first.checked_mul(sector_size)?,
+                (last.checked_sub(first)? + 1).checked_mul(sector_size)?,
+                name,
+            ))
+        })
+        .map(|partition| partition.unwrap_or_default())
+        .collect();
+    Some(partitions)
+}
+
+// the offsets and sizes of MBR primary partitions
+fn mbr_partitions(file: &mut File, len: u64) -> Option<Vec<(u64, u64, Option<String>)>> {
+    let mut mbr = [0u8; SECTOR_SIZE as usize];
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_exact(&mut mbr).ok()?;
+    if &mbr[510..512] != BOOT_SIGNATURE {
+        return None;
+    }
+
+    let mut partitions = Vec::new();
+    for entry in mbr[MBR_ENTRIES_OFFSET..510].chunks_exact(MBR_ENTRY_SIZE) {
+        let start = LittleEndian::read_u32(&entry[8..12]) as u64 * SECTOR_SIZE;
+        let size = LittleEndian::read_u32(&entry[12..16]) as u64 * SECTOR_SIZE;
+        // the boot code of other formats, e.g. bzImage, also ends with the
+        // boot signature, so the entries are validated strictly
+        if !matches!(entry[0], 0x00 | 0x80) || start + size > len {
+            return None;
+        }
+        if entry[4] == MBR_TYPE_PROTECTIVE {
+            return None;
+        }
+        partitions.push(match entry[4] {
+            0 => (0, 0, None),
+            _ => (start, size, None),
+        });
+    }
+    Some(partitions)
+}
+
+pub fn parse(file: &mut File) -> Result<Disk, io::Error> {
+    let len = file.metadata()?.len();
+    let partitioned = gpt_partitions(file)
+        .map(|partitions| ("GPT partitioned disk image", partitions))
+        .or_else(|| {
+            mbr_partitions(file, len).map(|partitions| ("MBR partitioned disk image", partitions))
+        });
+    if let Some((format, partitions)) = partitioned {
+        // only the partitions with supported filesystems are kept
+        let partitions: Vec<Partition> = partitions
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (_, size, _))| *size > 0)
+            .filter_map(|(i, (offset, size, name))| {
+                Some(Partition {
+                    number: Some(i + 1),
+                    size,
+                    name,
+                    filesystem: parse_filesystem(file, offset)?,
+                })
+            })
+            .collect();
+        if !partitions.is_empty() {
+            return Ok(Disk { format, partitions });
+        }
+    }
+
+    // the filesystems may be unpartitioned, e.g. the images of partitions,
+    // whose boot sector may look like MBR as well
+    let filesystem =
+        parse_filesystem(file, 0).ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?;
+    Ok(Disk {
+        format: if filesystem.is_read_only() {
+            "Read-only filesystem image"
+        } else {
+            "Filesystem image"
+        },
+        partitions: vec![Partition {
+            number: None,
+            size: len,
+            name: None,
+            filesystem,
+        }],
+    })
+}
+
+// kernels are installed to /boot, or the root of a separate boot partition,
+// and unified kernel images to EFI/Linux of ESP, which are matched
+// case-insensitively as in FAT
+const BOOT_DIR: &str = "/boot";
+const UKI_DIR: &str = "/EFI/Linux";
+
+fn is_boot_dir(path: &str) -> bool {
+    [BOOT_DIR, "/EFI", UKI_DIR]
+        .iter()
+        .any(|dir| dir.eq_ignore_ascii_case(path))
+}
+
+fn is_boot_path(path: &str) -> bool {
+    let (dir, name) = path.rsplit_once('/').unwrap_or_default();
+    if dir.eq_ignore_ascii_case(UKI_DIR) {
+        is_uki_name(name)
+    } else {
+        (dir.is_empty() || dir.eq_ignore_ascii_case(BOOT_DIR)) && is_boot_name(name)
+    }
+}
+
+fn is_boot_name(name: &str) -> bool {
+    name.starts_with("vmlinuz") || name.starts_with("config-")
+}
+
+fn is_uki_name(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with(".efi")
+}
+
+impl Disk {
+    // the kernels and configs in filesystems, with their paths
+    pub fn kernels(&self, file: &mut File) -> archive::Kernels {
+        let mut kernels = Vec::new();
+        for partition in &self.partitions {
+            let filesystem = &partition.filesystem;
+            let location = match partition.number {
+                Some(number) => format!("in partition {number} ({})", filesystem.name()),
+                None => format!("({})", filesystem.name()),
+            };
+            for (path, entry) in filesystem.boot_files(file) {
+                kernels.push((
+                    format!("{path} {location}"),
+                    filesystem.read_file(file, &entry),
+                ));
+            }
+        }
+        kernels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_is_boot_name() {
+        assert!(is_boot_name("vmlinuz-6.1.0-1-amd64"));
+        assert!(is_boot_name("config-6.1.0-1-amd64"));
+        assert!(is_boot_name("vmlinuz"));
+        assert!(!is_boot_name("initrd.img-6.1.0-1-amd64"));
+        assert!(!is_boot_name("System.map-6.1.0-1-amd64"));
+        assert!(is_uki_name("debian-6.1.0-1-amd64.efi"));
+        assert!(is_uki_name("LINUX.EFI"));
+        assert!(!is_uki_name("grubx64.efi.signed"));
+
+        assert!(is_boot_path("/boot/vmlinuz-6.1.0-1-amd64"));
+        assert!(is_boot_path("/vmlinuz"));
+        assert!(is_boot_path("/efi/linux/debian-6.1.0-1-amd64.efi"));
+        assert!(!is_boot_path("/EFI/BOOT/BOOTX64.EFI"));
+        assert!(!is_boot_path("/usr/lib/vmlinuz"));
+        assert!(is_boot_dir("/EFI"));
+        assert!(!is_boot_dir("/usr"));
+    }
+
+    #[test]
+    fn test_gpt_partitions_bounds() {
+        let mut header = vec![0u8; 1024];
+        header[512..520].copy_from_slice(GPT_SIGNATURE);
+        header[584..592].copy_from_slice(&2u64.to_le_bytes());
+        header[592..596].copy_from_slice(&128u32.to_le_bytes());
+        header[596..600].copy_from_slice(&128u32.to_le_bytes());
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&header).unwrap();
+        // the entries beyond the end of file
+        assert!(gpt_partitions(&mut file).is_none());
+
+        header[592..600].copy_from_slice(&[0xff; 8]);
+        file.rewind().unwrap();
+        file.write_all(&header).unwrap();
+        assert!(gpt_partitions(&mut file).is_none());
+    }
+}
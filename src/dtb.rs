@@ -0,0 +1,152 @@
+use crate::fdt;
+use argh::FromArgs;
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+const FDT_MAGIC_STR: &str = r"\xd0\x0d\xfe\xed";
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "extract-dtb",
+    description = "extract the device tree blobs appended to or built into a kernel image"
+)]
+pub struct ExtractDtbArgs {
+    #[argh(positional, description = "kernel image")]
+    image: String,
+
+    #[argh(
+        option,
+        short = 'd',
+        default = "String::from(\".\")",
+        description = "output directory of the .dtb files, default to current directory"
+    )]
+    directory: String,
+
+    #[argh(switch, short = 'l', description = "only list the device tree blobs")]
+    list: bool,
+}
+
+struct Dtb {
+    // where the blob is found, either the image itself or the decompressed kernel
+    source: &'static str,
+    offset: u64,
+    data: Vec<u8>,
+    model: Option<String>,
+    compatible: Vec<String>,
+}
+
+fn read_dtb(file: &mut File, offset: u64) -> Result<(Vec<u8>, fdt::Node), io::Error> {
+    let mut header = [0u8; fdt::FDT_HEADER_SIZE];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut header)?;
+    let totalsize = fdt::parse_header(&header)?.totalsize as usize;
+    if offset.saturating_add(totalsize as u64) > file.metadata()?.len() {
+        return Err(io::Error::from(ErrorKind::UnexpectedEof));
+    }
+
+    let mut data = header.to_vec();
+    data.resize(totalsize, 0);
+    file.read_exact(&mut data[fdt::FDT_HEADER_SIZE..])?;
+    let root = fdt::parse(&data)?;
+    Ok((data, root))
+}
+
+fn scan_dtbs(file: &mut File, base: u64, source: &'static str, dtbs: &mut Vec<Dtb>) {
+    let mut start = 0;
+    while let Ok(offset) = crate::search_regex_from(file, FDT_MAGIC_STR, start) {
+        start = offset + 4;
+        let Ok((data, root)) = read_dtb(file, offset) else {
+            continue;
+        };
+        // look into FIT images for the device tree blobs embedded
+        if root.child("images").is_some() {
+            continue;
+        }
+
+        start = offset + data.len() as u64;
+        if dtbs.iter().all(|dtb| dtb.data != data) {
+            dtbs.push(Dtb {
+                source,
+                offset: base + offset,
+                model: root.property_str("model"),
+                compatible: root.property_strs("compatible"),
+                data,
+            });
+        }
+    }
+}
+
+fn scan_builtin_dtbs(vmlinux: &mut File, dtbs: &mut Vec<Dtb>) -> Result<(), io::Error> {
+    // restrict the search within the built-in device tree blobs if possible
+    let elf = crate::elf::parse(vmlinux)?;
+    let symbols = elf.symbols(vmlinux)?;
+    let (offset, size) = elf
+        .symbol_range(&symbols, "__dtb_start", "__dtb_end")
+        .ok_or_else(|| io::Error::from(ErrorKind::NotFound))?;
+    let mut region = crate::extract_payload(vmlinux, offset, size)?;
+    scan_dtbs(&mut region, offset, "vmlinux", dtbs);
+    Ok(())
+}
+
+fn find_dtbs(file: &mut File) -> Vec<Dtb> {
+    // appended device tree blobs are outside of the compressed kernel
+    let mut dtbs = Vec::new();
+    scan_dtbs(file, 0, "image", &mut dtbs);
+
+    if let Ok(mut vmlinux) = crate::extract_vmlinux(file) {
+        if scan_builtin_dtbs(&mut vmlinux, &mut dtbs).is_err() {
+            scan_dtbs(&mut vmlinux, 0, "vmlinux", &mut dtbs);
+        }
+    }
+    dtbs
+}
+
+pub fn run(args: ExtractDtbArgs) {
+    let mut file = match File::open(&args.image) {
+        Ok(image) => image,
+        Err(err) => {
+            eprintln!("Failed to open file {}: {err}", &args.image);
+            return;
+        }
+    };
+
+    let dtbs = find_dtbs(&mut file);
+    if dtbs.is_empty() {
+        eprintln!("{}: Cannot find device tree blobs.", env!("CARGO_BIN_NAME"));
+        return;
+    }
+
+    if !args.list {
+        if let Err(err) = fs::create_dir_all(&args.directory) {
+            eprintln!("Failed to create directory {}: {err}", &args.directory);
+            return;
+        }
+    }
+
+    for (index, dtb) in dtbs.iter().enumerate() {
+        let name = format!("dtb-{index}.dtb");
+        println!(
+            "{name}: {} bytes at 0x{:x} of {}",
+            dtb.data.len(),
+            dtb.offset,
+            dtb.source
+        );
+        if let Some(model) = &dtb.model {
+            println!("  Model:      {model}");
+        }
+        if !dtb.compatible.is_empty() {
+            println!("  Compatible: {}", dtb.compatible.join(" "));
+        }
+
+        if !args.list {
+            let path = Path::new(&args.directory).join(&name);
+            if let Err(err) = fs::write(&path, &dtb.data) {
+                eprintln!("Failed to write {}: {err}", path.display());
+            }
+        }
+    }
+}
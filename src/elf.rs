@@ -0,0 +1,354 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+};
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+
+pub const ET_REL: u16 = 1;
+pub const ET_CORE: u16 = 4;
+
+pub const PT_LOAD: u32 = 1;
+pub const PT_NOTE: u32 = 4;
+
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_NOBITS: u32 = 8;
+
+// section indexes starting from SHN_LORESERVE are special ones
+const SHN_LORESERVE: u16 = 0xff00;
+
+pub struct Section {
+    pub name: String,
+    pub sh_type: u32,
+    pub addr: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+}
+
+pub struct Segment {
+    pub p_type: u32,
+    pub offset: u64,
+    pub filesz: u64,
+}
+
+pub struct Symbol {
+    pub name: String,
+    pub value: u64,
+    pub shndx: u16,
+}
+
+pub struct Elf {
+    pub class64: bool,
+    pub big_endian: bool,
+    pub elf_type: u16,
+    pub machine: u16,
+    pub segments: Vec<Segment>,
+    pub sections: Vec<Section>,
+}
+
+impl Elf {
+    pub fn u16(&self, data: &[u8]) -> u16 {
+        if self.big_endian {
+            BigEndian::read_u16(data)
+        } else {
+            LittleEndian::read_u16(data)
+        }
+    }
+
+    pub fn u32(&self, data: &[u8]) -> u32 {
+        if self.big_endian {
+            BigEndian::read_u32(data)
+        } else {
+            LittleEndian::read_u32(data)
+        }
+    }
+
+    pub fn u64(&self, data: &[u8]) -> u64 {
+        if self.big_endian {
+            BigEndian::read_u64(data)
+        } else {
+            LittleEndian::read_u64(data)
+        }
+    }
+
+    pub fn read_section(&self, file: &mut File, section: &Section) -> Result<Vec<u8>, io::Error> {
+        if section.sh_type == SHT_NOBITS {
+            return Ok(Vec::new());
+        }
+        read_at(file, section.offset, section.size)
+    }
+
+    pub fn symbols(&self, file: &mut File) -> Result<Vec<Symbol>, io::Error> {
+        let symtab = self
+            .sections
+            .iter()
+            .find(|section| section.sh_type == SHT_SYMTAB)
+            .ok_or_else(|| io::Error::from(ErrorKind::NotFound))?;
+        let strtab = self
+            .sections
+            .get(symtab.link as usize)
+            .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?;
+        let symbols = self.read_section(file, symtab)?;
+        let strings = self.read_section(file, strtab)?;
+
+        let entsize = if self.class64 { 24 } else { 16 };
+        Ok(symbols
+            .chunks_exact(entsize)
+            .map(|sym| {
+                // Elf64_Sym and Elf32_Sym have different field orders
+                let (name, value, shndx) = if self.class64 {
+                    (
+                        self.u32(&sym[0..4]),
+                        self.u64(&sym[8..16]),
+                        self.u16(&sym[6..8]),
+                    )
+                } else {
+                    (
+                        self.u32(&sym[0..4]),
+                        self.u32(&sym[4..8]) as u64,
+                        self.u16(&sym[14..16]),
+                    )
+                };
+                Symbol {
+                    name: cstr(&strings, name as usize),
+                    value,
+                    shndx,
+                }
+            })
+            .collect())
+    }
+
+    pub fn symbol<'a>(&self, symbols: &'a [Symbol], name: &str) -> Option<&'a Symbol> {
+        symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    // the file offset of a symbol, and the bytes left in its section
+    pub fn symbol_offset(&self, symbol: &Symbol) -> Option<(u64, u64)> {
+        if symbol.shndx == 0 || symbol.shndx >= SHN_LORESERVE {
+            return None;
+        }
+        let section = self.sections.get(symbol.shndx as usize)?;
+        if section.sh_type == SHT_NOBITS {
+            return None;
+        }
+
+        // symbol value is an offset within the section in relocatable files,
+        // or a virtual address in executable files
+        let offset = if self.elf_type == ET_REL {
+            symbol.value
+        } else {
+            symbol.value.checked_sub(section.addr)?
+        };
+        if offset > section.size {
+            return None;
+        }
+        Some((section.offset + offset, section.size - offset))
+    }
+
+    // the file offset and size of the data between two symbols, or till the
+    // end of the section containing the start symbol if there is no end one
+    pub fn symbol_range(&self, symbols: &[Symbol], start: &str, end: &str) -> Option<(u64, u64)> {
+        let (offset, remaining) = self.symbol_offset(self.symbol(symbols, start)?)?;
+        let size = match self
+            .symbol(symbols, end)
+            .and_then(|symbol| self.symbol_offset(symbol))
+        {
+            Some((end, _)) if end >= offset && end - offset <= remaining => end - offset,
+            _ => remaining,
+        };
+        Some((offset, size))
+    }
+}
+
+// read the data whose size is taken from the headers, which is checked
+// against the file length before allocating the buffer
+fn read_at(file: &mut File, offset: u64, size: u64) -> Result<Vec<u8>, io::Error> {
+    if offset.saturating_add(size) > file.metadata()?.len() {
+        return Err(io::Error::from(ErrorKind::UnexpectedEof));
+    }
+    let mut data = vec![0u8; size as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn cstr(data: &[u8], offset: usize) -> String {
+    let bytes = data.get(offset..).unwrap_or_default();
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+pub fn parse(file: &mut File) -> Result<Elf, io::Error> {
+    let mut ident = [0u8; 64];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut ident[..52])?;
+    if &ident[..4] != ELF_MAGIC {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    let mut elf = Elf {
+        class64: match ident[4] {
+            ELFCLASS32 => false,
+            ELFCLASS64 => true,
+            _ => return Err(io::Error::from(ErrorKind::InvalidData)),
+        },
+        big_endian: match ident[5] {
+            ELFDATA2LSB => false,
+            ELFDATA2MSB => true,
+            _ => return Err(io::Error::from(ErrorKind::InvalidData)),
+        },
+        elf_type: 0,
+        machine: 0,
+        segments: Vec::new(),
+        sections: Vec::new(),
+    };
+    elf.elf_type = elf.u16(&ident[16..18]);
+    elf.machine = elf.u16(&ident[18..20]);
+
+    let header = if elf.class64 {
+        file.read_exact(&mut ident[52..64])?;
+        &ident[..64]
+    } else {
+        &ident[..52]
+    };
+    let (phoff, phentsize, phnum) = if elf.class64 {
+        (
+            elf.u64(&header[32..40]),
+            elf.u16(&header[54..56]),
+            elf.u16(&header[56..58]),
+        )
+    } else {
+        (
+            elf.u32(&header[28..32]) as u64,
+            elf.u16(&header[42..44]),
+            elf.u16(&header[44..46]),
+        )
+    };
+    let (shoff, shentsize, shnum, shstrndx) = if elf.class64 {
+        (
+            elf.u64(&header[40..48]),
+            elf.u16(&header[58..60]),
+            elf.u16(&header[60..62]),
+            elf.u16(&header[62..64]),
+        )
+    } else {
+        (
+            elf.u32(&header[32..36]) as u64,
+            elf.u16(&header[46..48]),
+            elf.u16(&header[48..50]),
+            elf.u16(&header[50..52]),
+        )
+    };
+
+    let min_phentsize = if elf.class64 { 56 } else { 32 };
+    if phoff != 0 && phnum != 0 && phentsize as usize >= min_phentsize {
+        let table = read_at(file, phoff, phentsize as u64 * phnum as u64)?;
+        for entry in table.chunks_exact(phentsize as usize) {
+            elf.segments.push(if elf.class64 {
+                Segment {
+                    p_type: elf.u32(&entry[0..4]),
+                    offset: elf.u64(&entry[8..16]),
+                    filesz: elf.u64(&entry[32..40]),
+                }
+            } else {
+                Segment {
+                    p_type: elf.u32(&entry[0..4]),
+                    offset: elf.u32(&entry[4..8]) as u64,
+                    filesz: elf.u32(&entry[16..20]) as u64,
+                }
+            });
+        }
+    }
+
+    let min_shentsize = if elf.class64 { 64 } else { 40 };
+    if shoff == 0 || shnum == 0 || (shentsize as usize) < min_shentsize {
+        // no section header table, e.g. stripped by sstrip
+        return Ok(elf);
+    }
+
+    let table = read_at(file, shoff, shentsize as u64 * shnum as u64)?;
+
+    let mut names = Vec::new();
+    for entry in table.chunks_exact(shentsize as usize) {
+        let section = if elf.class64 {
+            Section {
+                name: String::new(),
+                sh_type: elf.u32(&entry[4..8]),
+                addr: elf.u64(&entry[16..24]),
+                offset: elf.u64(&entry[24..32]),
+                size: elf.u64(&entry[32..40]),
+                link: elf.u32(&entry[40..44]),
+            }
+        } else {
+            Section {
+                name: String::new(),
+                sh_type: elf.u32(&entry[4..8]),
+                addr: elf.u32(&entry[12..16]) as u64,
+                offset: elf.u32(&entry[16..20]) as u64,
+                size: elf.u32(&entry[20..24]) as u64,
+                link: elf.u32(&entry[24..28]),
+            }
+        };
+        names.push(elf.u32(&entry[0..4]) as usize);
+        elf.sections.push(section);
+    }
+
+    if let Some(shstrtab) = elf.sections.get(shstrndx as usize) {
+        let strings = elf.read_section(file, shstrtab)?;
+        for (section, name) in elf.sections.iter_mut().zip(names) {
+            section.name = cstr(&strings, name);
+        }
+    }
+
+    Ok(elf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_vmlinux() {
+        let mut file = File::open("tests/data/vmlinux").unwrap();
+        let elf = parse(&mut file).unwrap();
+        assert!(elf.class64);
+        assert!(!elf.big_endian);
+        // EM_X86_64
+        assert_eq!(elf.machine, 62);
+
+        let rodata = elf
+            .sections
+            .iter()
+            .find(|section| section.name == ".rodata")
+            .unwrap();
+        assert_eq!(rodata.addr, 0xffffffff81a00000);
+        assert_eq!(rodata.offset, 0xc00000);
+
+        // symbols are stripped in the vmlinux.bin compressed into bzImage
+        assert!(elf.symbols(&mut file).is_err());
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        // the section header table of 0xffff entries beyond the file
+        let mut header = vec![0u8; 64];
+        header[..4].copy_from_slice(ELF_MAGIC);
+        header[4] = ELFCLASS64;
+        header[5] = ELFDATA2LSB;
+        header[40..48].copy_from_slice(&64u64.to_le_bytes());
+        header[58..60].copy_from_slice(&0xffffu16.to_le_bytes());
+        header[60..62].copy_from_slice(&0xffffu16.to_le_bytes());
+
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&header).unwrap();
+        let err = parse(&mut file).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+}
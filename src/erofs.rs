@@ -0,0 +1,535 @@
+use crate::disk::Entry;
+use byteorder::{ByteOrder, LittleEndian};
+use flate2::read::DeflateDecoder;
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+};
+
+// the superblock of EROFS locates at 1024 bytes, and the inodes are
+// addressed by nid in 32 bytes from the start of metadata
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const SUPERBLOCK_SIZE: usize = 128;
+const EROFS_MAGIC: u32 = 0xe0f5e1e2;
+const INODE_SLOT_SIZE: u64 = 32;
+const INODE_COMPACT_SIZE: u64 = 32;
+const INODE_EXTENDED_SIZE: u64 = 64;
+const XATTR_HEADER_SIZE: u64 = 12;
+const DIRENT_SIZE: usize = 12;
+const NULL_ADDR: u32 = 0xffffffff;
+
+// the compressed data is aligned to the end of physical clusters
+const INCOMPAT_ZERO_PADDING: u32 = 0x1;
+
+const LAYOUT_FLAT_PLAIN: u16 = 0;
+const LAYOUT_COMPRESSED_FULL: u16 = 1;
+const LAYOUT_FLAT_INLINE: u16 = 2;
+const LAYOUT_COMPRESSED_COMPACT: u16 = 3;
+const LAYOUT_CHUNK_BASED: u16 = 4;
+
+const CHUNK_FORMAT_BLKBITS_MASK: u32 = 0x1f;
+const CHUNK_FORMAT_INDEXES: u32 = 0x20;
+
+// struct z_erofs_map_header precedes the indexes of logical clusters
+const MAP_HEADER_SIZE: u64 = 8;
+const ADVISE_COMPACTED_2B: u16 = 0x1;
+const ADVISE_BIG_PCLUSTER_1: u16 = 0x2;
+const ADVISE_INTERLACED_PCLUSTER: u16 = 0x10;
+// the tail packing and fragments store data out of physical clusters
+const ADVISE_UNSUPPORTED: u16 = 0x8 | 0x20;
+const FRAGMENT_INODE_BIT: u8 = 0x80;
+const FULL_INDEX_SIZE: usize = 8;
+
+const LCLUSTER_TYPE_PLAIN: u8 = 0;
+const LCLUSTER_TYPE_HEAD1: u8 = 1;
+const LCLUSTER_TYPE_NONHEAD: u8 = 2;
+const LCLUSTER_TYPE_HEAD2: u8 = 3;
+// the first non-head logical cluster of big physical cluster has its size
+const D0_CBLKCNT: u32 = 1 << 11;
+
+const COMPRESSION_LZ4: u8 = 0;
+const COMPRESSION_LZMA: u8 = 1;
+const COMPRESSION_DEFLATE: u8 = 2;
+const COMPRESSION_ZSTD: u8 = 3;
+
+const FT_REG_FILE: u8 = 1;
+const FT_DIR: u8 = 2;
+
+// MicroLZMA is the raw LZMA data whose first byte, always 0 in the range
+// coder, is replaced by the inverted properties, and it's converted to the
+// .lzma format with the header of the properties and the sizes, where the
+// dictionary isn't larger than the decompressed data
+fn microlzma_to_alone(data: &[u8], len: usize) -> Option<Vec<u8>> {
+    let (&props, data) = data.split_first()?;
+    let dict_size = (len as u32).next_power_of_two().max(4096);
+    let mut stream = vec![!props];
+    stream.extend_from_slice(&dict_size.to_le_bytes());
+    stream.extend_from_slice(&(len as u64).to_le_bytes());
+    stream.push(0);
+    stream.extend_from_slice(data);
+    Some(stream)
+}
+
+struct Inode {
+    // the position of the data inline or the indexes after inode
+    inline: u64,
+    size: u64,
+    layout: u16,
+    // raw_blkaddr, compressed_blocks or chunk format by layout
+    info: u32,
+}
+
+// the logical cluster decoded from full or compacted indexes
+struct Lcluster {
+    kind: u8,
+    clusterofs: u64,
+    blkaddr: u64,
+    // the count of blocks of big physical cluster
+    blocks: Option<u64>,
+}
+
+pub struct Erofs {
+    // the offset of the filesystem in disk image
+    offset: u64,
+    blkszbits: u32,
+    root: u64,
+    meta_blkaddr: u64,
+    incompat: u32,
+}
+
+impl Erofs {
+    pub fn name(&self) -> &'static str {
+        "EROFS"
+    }
+
+    fn block_size(&self) -> u64 {
+        1 << self.blkszbits
+    }
+
+    fn read_at(&self, file: &mut File, pos: u64, len: usize) -> Result<Vec<u8>, io::Error> {
+        let end = self.offset.saturating_add(pos).saturating_add(len as u64);
+        if end > file.metadata()?.len() {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        let mut data = vec![0u8; len];
+        file.seek(SeekFrom::Start(self.offset + pos))?;
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    fn read_inode(&self, file: &mut File, nid: u64) -> Result<Inode, io::Error> {
+        let pos = nid
+            .checked_mul(INODE_SLOT_SIZE)
+            .and_then(|slot| slot.checked_add(self.meta_blkaddr << self.blkszbits))
+            .ok_or(ErrorKind::InvalidData)?;
+        let data = self.read_at(file, pos, INODE_COMPACT_SIZE as usize)?;
+        let format = LittleEndian::read_u16(&data[0..2]);
+        let xattr_count = LittleEndian::read_u16(&data[2..4]) as u64;
+        // bit 0 of format is set for the extended inode
+        let (inode_size, size) = match format & 1 {
+            0 => (
+                INODE_COMPACT_SIZE,
+                LittleEndian::read_u32(&data[8..12]) as u64,
+            ),
+            _ => (INODE_EXTENDED_SIZE, LittleEndian::read_u64(&data[8..16])),
+        };
+        let xattr_size = match xattr_count {
+            0 => 0,
+            _ => XATTR_HEADER_SIZE + (xattr_count - 1) * 4,
+        };
+        Ok(Inode {
+            inline: pos + inode_size + xattr_size,
+            size,
+            layout: (format >> 1) & 0x7,
+            info: LittleEndian::read_u32(&data[16..20]),
+        })
+    }
+
+    fn read_chunks(
+        &self,
+        file: &mut File,
+        inode: &Inode,
+        dst: &mut impl Write,
+    ) -> Result<(), io::Error> {
+        let chunk_size = self.block_size() << (inode.info & CHUNK_FORMAT_BLKBITS_MASK);
+        let count = inode.size.div_ceil(chunk_size) as usize;
+        // the chunk indexes are aligned to their sizes after the inode
+        let (entry_size, addr_offset) = match inode.info & CHUNK_FORMAT_INDEXES {
+            0 => (4, 0),
+            _ => (8, 4),
+        };
+        let entries = self.read_at(
+            file,
+            inode.inline.next_multiple_of(entry_size as u64),
+            count
+                .checked_mul(entry_size)
+                .ok_or(ErrorKind::InvalidData)?,
+        )?;
+
+        let mut remaining = inode.size;
+        for entry in entries.chunks_exact(entry_size) {
+            let len = remaining.min(chunk_size);
+            match LittleEndian::read_u32(&entry[addr_offset..addr_offset + 4]) {
+                NULL_ADDR => io::copy(&mut io::repeat(0).take(len), dst)?,
+                blkaddr => {
+                    file.seek(SeekFrom::Start(
+                        self.offset + ((blkaddr as u64) << self.blkszbits),
+                    ))?;
+                    io::copy(&mut Read::by_ref(file).take(len), dst)?
+                }
+            };
+            remaining -= len;
+        }
+        Ok(())
+    }
+
+    // the value of a logical cluster in compacted indexes, with its type
+    fn compacted_bits(pack: &[u8], lobits: u32, pos: usize) -> (u32, u8) {
+        let value = LittleEndian::read_u32(&pack[pos / 8..pos / 8 + 4]) >> (pos % 8);
+        (value & ((1 << lobits) - 1), ((value >> lobits) & 3) as u8)
+    }
+
+    // the logical cluster in a pack of compacted indexes, where the heads
+    // have the physical block counted from the one at the end of pack
+    fn unpack_compacted(
+        pack: &[u8],
+        count: usize,
+        i: usize,
+        lobits: u32,
+        big_pcluster: bool,
+    ) -> Result<Lcluster, io::Error> {
+        let encodebits = (pack.len() - 4) * 8 / count;
+        let (lo, kind) = Self::compacted_bits(pack, lobits, encodebits * i);
+        if kind == LCLUSTER_TYPE_NONHEAD {
+            return Ok(Lcluster {
+                kind,
+                clusterofs: 0,
+                blkaddr: 0,
+                blocks: (lo & D0_CBLKCNT != 0).then_some((lo & !D0_CBLKCNT) as u64),
+            });
+        }
+
+        let mut blocks: u64 = if big_pcluster { 0 } else { 1 };
+        let mut j = i as isize;
+        while j > 0 {
+            j -= 1;
+            let (lo, kind) = Self::compacted_bits(pack, lobits, encodebits * j as usize);
+            if kind != LCLUSTER_TYPE_NONHEAD {
+                blocks += 1;
+            } else if !big_pcluster {
+                // jump to the head of the non-head logical clusters
+                j -= lo as isize;
+                if j >= 0 {
+                    blocks += 1;
+                }
+            } else if lo & D0_CBLKCNT != 0 {
+                j -= 1;
+                blocks += (lo & !D0_CBLKCNT) as u64;
+            } else if lo <= 1 {
+                return Err(io::Error::from(ErrorKind::InvalidData));
+            } else {
+                j -= lo as isize - 2;
+            }
+        }
+        let base = LittleEndian::read_u32(&pack[pack.len() - 4..]) as u64;
+        Ok(Lcluster {
+            kind,
+            clusterofs: lo as u64,
+            blkaddr: base + blocks,
+            blocks: None,
+        })
+    }
+
+    fn lclusters(
+        &self,
+        file: &mut File,
+        inode: &Inode,
+        advise: u16,
+        lclusterbits: u32,
+    ) -> Result<Vec<Lcluster>, io::Error> {
+        let count = inode.size.div_ceil(1 << lclusterbits) as usize;
+        let start = inode.inline.next_multiple_of(8) + MAP_HEADER_SIZE;
+        if inode.layout == LAYOUT_COMPRESSED_FULL {
+            let size = count
+                .checked_mul(FULL_INDEX_SIZE)
+                .ok_or(ErrorKind::InvalidData)?;
+            let indexes = self.read_at(file, start, size)?;
+            return Ok(indexes
+                .chunks_exact(FULL_INDEX_SIZE)
+                .map(|index| {
+                    let kind = (LittleEndian::read_u16(&index[0..2]) & 3) as u8;
+                    let delta = LittleEndian::read_u16(&index[4..6]) as u32;
+                    Lcluster {
+                        kind,
+                        clusterofs: LittleEndian::read_u16(&index[2..4]) as u64,
+                        blkaddr: LittleEndian::read_u32(&index[4..8]) as u64,
+                        blocks: (kind == LCLUSTER_TYPE_NONHEAD && delta & D0_CBLKCNT != 0)
+                            .then_some((delta & !D0_CBLKCNT) as u64),
+                    }
+                })
+                .collect());
+        }
+
+        // the compacted indexes begin with 4-byte ones up to 32-byte alignment,
+        // followed by 2-byte ones in packs of 16 if advised, and then 4-byte
+        // ones in packs of 2
+        let initial = match (32 - start % 32) as usize / 4 {
+            8 => 0,
+            initial => initial.min(count),
+        };
+        let compacted_2b = match advise & ADVISE_COMPACTED_2B {
+            0 => 0,
+            _ => (count - initial) / 16 * 16,
+        };
+        if lclusterbits > 14 || (compacted_2b > 0 && lclusterbits > 12) {
+            return Err(io::Error::from(ErrorKind::Unsupported));
+        }
+        let regions = [
+            (initial, 4, 2),
+            (compacted_2b, 2, 16),
+            (count - initial - compacted_2b, 4, 2),
+        ];
+        let size = regions
+            .iter()
+            .try_fold(0usize, |sum, (n, size, vcnt)| {
+                n.next_multiple_of(*vcnt)
+                    .checked_mul(*size)?
+                    .checked_add(sum)
+            })
+            .ok_or(ErrorKind::InvalidData)?;
+        let indexes = self.read_at(file, start, size)?;
+
+        let lobits = lclusterbits.max(D0_CBLKCNT.ilog2() + 1);
+        let big_pcluster = advise & ADVISE_BIG_PCLUSTER_1 != 0;
+        let mut lclusters = Vec::with_capacity(count);
+        let mut offset = 0;
+        for (n, size, vcnt) in regions {
+            for i in 0..n {
+                let pack = &indexes[offset + i / vcnt * vcnt * size..][..vcnt * size];
+                lclusters.push(Self::unpack_compacted(
+                    pack,
+                    vcnt,
+                    i % vcnt,
+                    lobits,
+                    big_pcluster,
+                )?);
+            }
+            offset += n.next_multiple_of(vcnt) * size;
+        }
+        Ok(lclusters)
+    }
+
+    fn decompress(&self, algorithm: u8, data: &[u8], len: usize) -> Result<Vec<u8>, io::Error> {
+        let invalid = |_| io::Error::from(ErrorKind::InvalidData);
+        let data = match self.incompat & INCOMPAT_ZERO_PADDING {
+            0 => data,
+            _ => &data[data.iter().position(|&b| b != 0).unwrap_or(data.len())..],
+        };
+        let mut decompressed = Vec::with_capacity(len);
+        match algorithm {
+            COMPRESSION_LZ4 => {
+                decompressed.resize(len, 0);
+                let len =
+                    lz4_flex::block::decompress_into(data, &mut decompressed).map_err(invalid)?;
+                decompressed.truncate(len);
+            }
+            COMPRESSION_LZMA => {
+                let stream = microlzma_to_alone(data, len).ok_or(ErrorKind::InvalidData)?;
+                decompressed = lzma::decompress(&stream)
+                    .map_err(|_| io::Error::from(ErrorKind::InvalidData))?;
+            }
+            COMPRESSION_DEFLATE => {
+                DeflateDecoder::new(data).read_to_end(&mut decompressed)?;
+            }
+            COMPRESSION_ZSTD => decompressed = zstd::bulk::decompress(data, len)?,
+            _ => return Err(io::Error::from(ErrorKind::Unsupported)),
+        }
+        Ok(decompressed)
+    }
+
+    fn read_compressed(
+        &self,
+        file: &mut File,
+        inode: &Inode,
+        dst: &mut impl Write,
+    ) -> Result<(), io::Error> {
+        let header = self.read_at(
+            file,
+            inode.inline.next_multiple_of(8),
+            MAP_HEADER_SIZE as usize,
+        )?;
+        let advise = LittleEndian::read_u16(&header[4..6]);
+        let algorithms = header[6];
+        if advise & ADVISE_UNSUPPORTED != 0 || header[7] & FRAGMENT_INODE_BIT != 0 {
+            return Err(io::Error::from(ErrorKind::Unsupported));
+        }
+        let lclusterbits = self.blkszbits + (header[7] & 0x7) as u32;
+        let lclusters = self.lclusters(file, inode, advise, lclusterbits)?;
+
+        // each extent begins at a head logical cluster, and ends at the next one
+        let heads: Vec<(u64, &Lcluster, u64)> = lclusters
+            .iter()
+            .enumerate()
+            .filter(|(_, lcluster)| lcluster.kind != LCLUSTER_TYPE_NONHEAD)
+            .map(|(lcn, lcluster)| {
+                let blocks = match lclusters.get(lcn + 1) {
+                    Some(next) if next.kind == LCLUSTER_TYPE_NONHEAD => next.blocks.unwrap_or(1),
+                    _ => 1,
+                };
+                (
+                    ((lcn as u64) << lclusterbits) + lcluster.clusterofs,
+                    lcluster,
+                    blocks,
+                )
+            })
+            .collect();
+
+        let mut written = 0;
+        for (i, &(start, lcluster, blocks)) in heads.iter().enumerate() {
+            let end = heads
+                .get(i + 1)
+                .map_or(inode.size, |(end, _, _)| *end)
+                .min(inode.size);
+            if start != written || end < start {
+                return Err(io::Error::from(ErrorKind::InvalidData));
+            }
+            let len = (end - start) as usize;
+            let pcluster = self.read_at(
+                file,
+                lcluster.blkaddr << self.blkszbits,
+                (blocks << self.blkszbits) as usize,
+            )?;
+            let data = match lcluster.kind {
+                LCLUSTER_TYPE_PLAIN if advise & ADVISE_INTERLACED_PCLUSTER != 0 => {
+                    // the data is rotated by its offset in the block
+                    let shift = (start % pcluster.len() as u64) as usize;
+                    [&pcluster[shift..], &pcluster[..shift]].concat()
+                }
+                LCLUSTER_TYPE_PLAIN => pcluster,
+                LCLUSTER_TYPE_HEAD1 => self.decompress(algorithms & 0xf, &pcluster, len)?,
+                LCLUSTER_TYPE_HEAD2 => self.decompress(algorithms >> 4, &pcluster, len)?,
+                _ => unreachable!(),
+            };
+            dst.write_all(data.get(..len).ok_or(ErrorKind::UnexpectedEof)?)?;
+            written = end;
+        }
+        if written != inode.size {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        Ok(())
+    }
+
+    fn read_data(
+        &self,
+        file: &mut File,
+        inode: &Inode,
+        dst: &mut impl Write,
+    ) -> Result<(), io::Error> {
+        let block_size = self.block_size();
+        match inode.layout {
+            LAYOUT_FLAT_PLAIN | LAYOUT_FLAT_INLINE => {
+                // the last block is inline after the inode if tail packed
+                let blocks = match inode.layout {
+                    LAYOUT_FLAT_PLAIN => inode.size.div_ceil(block_size),
+                    _ => inode.size.div_ceil(block_size).saturating_sub(1),
+                };
+                let len = inode.size.min(blocks.saturating_mul(block_size));
+                file.seek(SeekFrom::Start(
+                    self.offset + ((inode.info as u64) << self.blkszbits),
+                ))?;
+                io::copy(&mut Read::by_ref(file).take(len), dst)?;
+                if len < inode.size {
+                    let tail = self.read_at(file, inode.inline, (inode.size - len) as usize)?;
+                    dst.write_all(&tail)?;
+                }
+                Ok(())
+            }
+            LAYOUT_CHUNK_BASED => self.read_chunks(file, inode, dst),
+            LAYOUT_COMPRESSED_FULL | LAYOUT_COMPRESSED_COMPACT => {
+                self.read_compressed(file, inode, dst)
+            }
+            _ => Err(io::Error::from(ErrorKind::Unsupported)),
+        }
+    }
+
+    fn entries(&self, file: &mut File, nid: u64) -> Result<Vec<Entry>, io::Error> {
+        let inode = self.read_inode(file, nid)?;
+        let mut data = Vec::new();
+        self.read_data(file, &inode, &mut data)?;
+
+        // each block begins with the dirents, followed by their names
+        let mut entries = Vec::new();
+        for block in data.chunks(self.block_size() as usize) {
+            let nameoff = |i: usize| {
+                block
+                    .get(i * DIRENT_SIZE + 8..i * DIRENT_SIZE + 10)
+                    .map(|nameoff| LittleEndian::read_u16(nameoff) as usize)
+            };
+            let count = nameoff(0).unwrap_or(0) / DIRENT_SIZE;
+            for i in 0..count {
+                let dirent = &block[i * DIRENT_SIZE..(i + 1) * DIRENT_SIZE];
+                let start = nameoff(i).unwrap_or(block.len());
+                let end = match i + 1 {
+                    next if next < count => nameoff(next).unwrap_or(block.len()),
+                    _ => block.len(),
+                };
+                let name = block.get(start..end).unwrap_or_default();
+                // the last name may be padded with zeros to the block end
+                let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+                let name = String::from_utf8_lossy(&name[..len]).into_owned();
+                if name == "." || name == ".." {
+                    continue;
+                }
+
+                let nid = LittleEndian::read_u64(&dirent[0..8]);
+                let file_type = dirent[10];
+                let size = match file_type {
+                    FT_REG_FILE => self.read_inode(file, nid)?.size,
+                    _ => 0,
+                };
+                entries.push(Entry {
+                    name,
+                    is_dir: file_type == FT_DIR,
+                    is_file: file_type == FT_REG_FILE,
+                    node: nid,
+                    size,
+                });
+            }
+        }
+        Ok(entries)
+    }
+
+    pub fn read_root(&self, file: &mut File) -> Result<Vec<Entry>, io::Error> {
+        self.entries(file, self.root)
+    }
+
+    pub fn read_entries(&self, file: &mut File, dir: &Entry) -> Result<Vec<Entry>, io::Error> {
+        self.entries(file, dir.node)
+    }
+
+    pub fn read_file(&self, file: &mut File, entry: &Entry) -> Result<File, io::Error> {
+        let inode = self.read_inode(file, entry.node)?;
+        let mut dst = tempfile::tempfile()?;
+        self.read_data(file, &inode, &mut dst)?;
+        Ok(dst)
+    }
+}
+
+pub fn parse(file: &mut File, offset: u64) -> Result<Erofs, io::Error> {
+    let mut superblock = [0u8; SUPERBLOCK_SIZE];
+    file.seek(SeekFrom::Start(offset + SUPERBLOCK_OFFSET))?;
+    file.read_exact(&mut superblock)?;
+
+    let blkszbits = superblock[12] as u32;
+    if LittleEndian::read_u32(&superblock[0..4]) != EROFS_MAGIC || !(9..=16).contains(&blkszbits) {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    Ok(Erofs {
+        offset,
+        blkszbits,
+        root: LittleEndian::read_u16(&superblock[14..16]) as u64,
+        meta_blkaddr: LittleEndian::read_u32(&superblock[40..44]) as u64,
+        incompat: LittleEndian::read_u32(&superblock[80..84]),
+    })
+}
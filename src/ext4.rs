@@ -0,0 +1,345 @@
+use crate::disk::Entry;
+use byteorder::{ByteOrder, LittleEndian};
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+};
+
+// the superblock of ext2, ext3 and ext4 locates at 1024 bytes
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const SUPERBLOCK_SIZE: usize = 1024;
+const EXT_MAGIC: u16 = 0xef53;
+const ROOT_INODE: u32 = 2;
+
+const COMPAT_HAS_JOURNAL: u32 = 0x4;
+const INCOMPAT_EXTENTS: u32 = 0x40;
+const INCOMPAT_64BIT: u32 = 0x80;
+const INCOMPAT_FLEX_BG: u32 = 0x200;
+
+const EXTENTS_FL: u32 = 0x80000;
+const INLINE_DATA_FL: u32 = 0x10000000;
+const EXTENT_MAGIC: u16 = 0xf30a;
+// the extents longer than this are uninitialized, which read as zeros
+const EXTENT_INIT_MAX_LEN: u16 = 32768;
+
+const S_IFMT: u16 = 0o170000;
+const S_IFREG: u16 = 0o100000;
+const S_IFDIR: u16 = 0o040000;
+
+struct Inode {
+    mode: u16,
+    size: u64,
+    flags: u32,
+    block: [u8; 60],
+}
+
+pub struct Ext4 {
+    // the offset of the filesystem in disk image
+    offset: u64,
+    block_size: u64,
+    inodes_per_group: u32,
+    inode_size: u64,
+    // the inode table of each block group
+    inode_tables: Vec<u64>,
+    compat: u32,
+    incompat: u32,
+}
+
+impl Ext4 {
+    pub fn name(&self) -> &'static str {
+        if self.incompat & (INCOMPAT_EXTENTS | INCOMPAT_64BIT | INCOMPAT_FLEX_BG) != 0 {
+            "ext4"
+        } else if self.compat & COMPAT_HAS_JOURNAL != 0 {
+            "ext3"
+        } else {
+            "ext2"
+        }
+    }
+
+    fn read_block(&self, file: &mut File, block: u64) -> Result<Vec<u8>, io::Error> {
+        let mut data = vec![0u8; self.block_size as usize];
+        file.seek(SeekFrom::Start(self.offset + block * self.block_size))?;
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    fn read_inode(&self, file: &mut File, inode: u32) -> Result<Inode, io::Error> {
+        let index = inode
+            .checked_sub(1)
+            .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?;
+        let table = self
+            .inode_tables
+            .get((index / self.inodes_per_group) as usize)
+            .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?;
+        let mut data = [0u8; 128];
+        file.seek(SeekFrom::Start(
+            self.offset
+                + table * self.block_size
+                + (index % self.inodes_per_group) as u64 * self.inode_size,
+        ))?;
+        file.read_exact(&mut data)?;
+
+        let mut block = [0u8; 60];
+        block.copy_from_slice(&data[40..100]);
+        Ok(Inode {
+            mode: LittleEndian::read_u16(&data[0..2]),
+            size: LittleEndian::read_u32(&data[4..8]) as u64
+                | (LittleEndian::read_u32(&data[108..112]) as u64) << 32,
+            flags: LittleEndian::read_u32(&data[32..36]),
+            block,
+        })
+    }
+
+    // the physical blocks of extent tree node, indexed by logical blocks
+    fn extent_blocks(
+        &self,
+        file: &mut File,
+        node: &[u8],
+        count: usize,
+        blocks: &mut Vec<u64>,
+        depth: usize,
+    ) -> Result<(), io::Error> {
+        let invalid = || io::Error::from(ErrorKind::InvalidData);
+        if LittleEndian::read_u16(&node[0..2]) != EXTENT_MAGIC || depth > 5 {
+            return Err(invalid());
+        }
+        let entries = LittleEndian::read_u16(&node[2..4]) as usize;
+        let leaf = LittleEndian::read_u16(&node[6..8]) == 0;
+        for i in 0..entries {
+            let entry = node.get(12 + i * 12..24 + i * 12).ok_or_else(invalid)?;
+            if leaf {
+                let logical = LittleEndian::read_u32(&entry[0..4]) as usize;
+                let len = LittleEndian::read_u16(&entry[4..6]);
+                let start = (LittleEndian::read_u16(&entry[6..8]) as u64) << 32
+                    | LittleEndian::read_u32(&entry[8..12]) as u64;
+                let (len, start) = if len > EXTENT_INIT_MAX_LEN {
+                    ((len - EXTENT_INIT_MAX_LEN) as usize, None)
+                } else {
+                    (len as usize, Some(start))
+                };
+                // the blocks beyond the size of the file are ignored
+                let len = len.min(count.saturating_sub(logical));
+                if len == 0 {
+                    continue;
+                }
+                if blocks.len() < logical + len {
+                    blocks.resize(logical + len, 0);
+                }
+                for j in 0..len {
+                    blocks[logical + j] = start.map_or(0, |start| start + j as u64);
+                }
+            } else {
+                let child = (LittleEndian::read_u16(&entry[8..10]) as u64) << 32
+                    | LittleEndian::read_u32(&entry[4..8]) as u64;
+                let child = self.read_block(file, child)?;
+                self.extent_blocks(file, &child, count, blocks, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    // the physical blocks of indirect block map, where 0 is a hole
+    fn mapped_blocks(
+        &self,
+        file: &mut File,
+        block: u64,
+        level: u32,
+        count: usize,
+        blocks: &mut Vec<u64>,
+    ) -> Result<(), io::Error> {
+        let per_block = (self.block_size / 4) as usize;
+        if level == 0 || block == 0 {
+            let len = per_block.pow(level).min(count - blocks.len());
+            if level == 0 {
+                blocks.push(block);
+            } else {
+                blocks.resize(blocks.len() + len, 0);
+            }
+            return Ok(());
+        }
+        let data = self.read_block(file, block)?;
+        for entry in data.chunks_exact(4) {
+            if blocks.len() >= count {
+                break;
+            }
+            let child = LittleEndian::read_u32(entry) as u64;
+            self.mapped_blocks(file, child, level - 1, count, blocks)?;
+        }
+        Ok(())
+    }
+
+    fn read_data(
+        &self,
+        file: &mut File,
+        inode: &Inode,
+        dst: &mut impl Write,
+    ) -> Result<(), io::Error> {
+        if inode.flags & INLINE_DATA_FL != 0 {
+            let len = (inode.size as usize).min(inode.block.len());
+            return dst.write_all(&inode.block[..len]);
+        }
+
+        // the size is checked before allocating the blocks for it, and the
+        // sparse files larger than the image aren't supported
+        if inode.size > file.metadata()?.len() {
+            return Err(io::Error::from(ErrorKind::InvalidData));
+        }
+        let count = inode.size.div_ceil(self.block_size) as usize;
+        let mut blocks = Vec::new();
+        if inode.flags & EXTENTS_FL != 0 {
+            self.extent_blocks(file, &inode.block, count, &mut blocks, 0)?;
+        } else {
+            // 12 direct blocks, and then single, double and triple indirect ones
+            for (i, entry) in inode.block.chunks_exact(4).enumerate() {
+                if blocks.len() >= count {
+                    break;
+                }
+                let level = i.saturating_sub(11) as u32;
+                let block = LittleEndian::read_u32(entry) as u64;
+                self.mapped_blocks(file, block, level, count, &mut blocks)?;
+            }
+        }
+        blocks.resize(count, 0);
+
+        let mut remaining = inode.size;
+        let zeros = vec![0u8; self.block_size as usize];
+        for block in blocks {
+            let len = remaining.min(self.block_size) as usize;
+            if block == 0 {
+                dst.write_all(&zeros[..len])?;
+            } else {
+                dst.write_all(&self.read_block(file, block)?[..len])?;
+            }
+            remaining -= len as u64;
+        }
+        Ok(())
+    }
+
+    fn entries(&self, file: &mut File, inode: &Inode) -> Result<Vec<Entry>, io::Error> {
+        let mut data = Vec::new();
+        // inline directories are never used for /boot
+        if inode.flags & INLINE_DATA_FL == 0 {
+            self.read_data(file, inode, &mut data)?;
+        }
+
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while offset + 8 <= data.len() {
+            let number = LittleEndian::read_u32(&data[offset..offset + 4]);
+            let rec_len = LittleEndian::read_u16(&data[offset + 4..offset + 6]) as usize;
+            let name_len = data[offset + 6] as usize;
+            if rec_len < 8 {
+                break;
+            }
+            let name = data
+                .get(offset + 8..offset + 8 + name_len)
+                .unwrap_or_default();
+            let name = String::from_utf8_lossy(name).into_owned();
+            offset += rec_len;
+            if number == 0 || name == "." || name == ".." {
+                continue;
+            }
+
+            let child = self.read_inode(file, number)?;
+            entries.push(Entry {
+                name,
+                is_dir: child.mode & S_IFMT == S_IFDIR,
+                is_file: child.mode & S_IFMT == S_IFREG,
+                node: number as u64,
+                size: child.size,
+            });
+        }
+        Ok(entries)
+    }
+
+    pub fn read_root(&self, file: &mut File) -> Result<Vec<Entry>, io::Error> {
+        let root = self.read_inode(file, ROOT_INODE)?;
+        self.entries(file, &root)
+    }
+
+    pub fn read_entries(&self, file: &mut File, dir: &Entry) -> Result<Vec<Entry>, io::Error> {
+        let inode = self.read_inode(file, dir.node as u32)?;
+        self.entries(file, &inode)
+    }
+
+    pub fn read_file(&self, file: &mut File, entry: &Entry) -> Result<File, io::Error> {
+        let inode = self.read_inode(file, entry.node as u32)?;
+        let mut dst = tempfile::tempfile()?;
+        self.read_data(file, &inode, &mut dst)?;
+        Ok(dst)
+    }
+}
+
+pub fn parse(file: &mut File, offset: u64) -> Result<Ext4, io::Error> {
+    let mut superblock = [0u8; SUPERBLOCK_SIZE];
+    file.seek(SeekFrom::Start(offset + SUPERBLOCK_OFFSET))?;
+    file.read_exact(&mut superblock)?;
+
+    let invalid = || io::Error::from(ErrorKind::InvalidData);
+    let log_block_size = LittleEndian::read_u32(&superblock[24..28]);
+    if LittleEndian::read_u16(&superblock[56..58]) != EXT_MAGIC || log_block_size > 6 {
+        return Err(invalid());
+    }
+    let block_size = 1024u64 << log_block_size;
+    let blocks_per_group = LittleEndian::read_u32(&superblock[32..36]) as u64;
+    let inodes_per_group = LittleEndian::read_u32(&superblock[40..44]);
+    let first_data_block = LittleEndian::read_u32(&superblock[20..24]) as u64;
+    // the revision 0 has fixed inode size
+    let inode_size = match LittleEndian::read_u32(&superblock[76..80]) {
+        0 => 128,
+        _ => LittleEndian::read_u16(&superblock[88..90]) as u64,
+    };
+    let compat = LittleEndian::read_u32(&superblock[92..96]);
+    let incompat = LittleEndian::read_u32(&superblock[96..100]);
+    let desc_size = match incompat & INCOMPAT_64BIT {
+        0 => 32,
+        _ => LittleEndian::read_u16(&superblock[254..256]) as usize,
+    };
+    let blocks = match incompat & INCOMPAT_64BIT {
+        0 => 0,
+        _ => (LittleEndian::read_u32(&superblock[336..340]) as u64) << 32,
+    } | LittleEndian::read_u32(&superblock[4..8]) as u64;
+    let len = file.metadata()?.len().saturating_sub(offset);
+    if blocks_per_group == 0
+        || inodes_per_group == 0
+        || inode_size < 128
+        || desc_size < 32
+        || blocks.checked_mul(block_size).ok_or_else(invalid)? > len
+    {
+        return Err(invalid());
+    }
+
+    // the group descriptors follow the superblock in the next block
+    let groups = blocks
+        .checked_sub(first_data_block)
+        .ok_or_else(invalid)?
+        .div_ceil(blocks_per_group) as usize;
+    let size = groups.checked_mul(desc_size).ok_or_else(invalid)?;
+    let start = (first_data_block + 1) * block_size;
+    if start.saturating_add(size as u64) > len {
+        return Err(io::Error::from(ErrorKind::UnexpectedEof));
+    }
+    let mut descriptors = vec![0u8; size];
+    file.seek(SeekFrom::Start(offset + start))?;
+    file.read_exact(&mut descriptors)?;
+    let inode_tables = descriptors
+        .chunks_exact(desc_size)
+        .map(|desc| {
+            let high = match desc_size {
+                64.. => LittleEndian::read_u32(&desc[40..44]) as u64,
+                _ => 0,
+            };
+            high << 32 | LittleEndian::read_u32(&desc[8..12]) as u64
+        })
+        .collect();
+
+    Ok(Ext4 {
+        offset,
+        block_size,
+        inodes_per_group,
+        inode_size,
+        inode_tables,
+        compat,
+        incompat,
+    })
+}
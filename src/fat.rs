@@ -0,0 +1,273 @@
+use crate::disk::Entry;
+use byteorder::{ByteOrder, LittleEndian};
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+};
+
+const DIR_ENTRY_SIZE: usize = 32;
+const ATTR_LONG_NAME: u8 = 0x0f;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const DELETED: u8 = 0xe5;
+// the lowercase flags of base name and extension in NT reserved byte
+const LOWERCASE_BASE: u8 = 0x08;
+const LOWERCASE_EXT: u8 = 0x10;
+
+#[derive(PartialEq)]
+enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+pub struct Fat {
+    fat_type: FatType,
+    // the offset of the volume in disk image
+    offset: u64,
+    cluster_size: u64,
+    // the offset of cluster 2 in volume
+    data_offset: u64,
+    // the fixed root directory of FAT12 and FAT16, or the root cluster of FAT32
+    root_offset: u64,
+    root_size: u64,
+    root_cluster: u32,
+    table: Vec<u8>,
+}
+
+impl Fat {
+    pub fn name(&self) -> &'static str {
+        match self.fat_type {
+            FatType::Fat12 => "FAT12",
+            FatType::Fat16 => "FAT16",
+            FatType::Fat32 => "FAT32",
+        }
+    }
+
+    fn next_cluster(&self, cluster: u32) -> Option<u32> {
+        let n = cluster as usize;
+        let (next, end) = match self.fat_type {
+            FatType::Fat12 => {
+                let value = LittleEndian::read_u16(self.table.get(n + n / 2..n + n / 2 + 2)?);
+                let value = if n % 2 == 0 {
+                    value & 0xfff
+                } else {
+                    value >> 4
+                };
+                (value as u32, 0xff8)
+            }
+            FatType::Fat16 => (
+                LittleEndian::read_u16(self.table.get(n * 2..n * 2 + 2)?) as u32,
+                0xfff8,
+            ),
+            FatType::Fat32 => (
+                LittleEndian::read_u32(self.table.get(n * 4..n * 4 + 4)?) & 0x0fffffff,
+                0x0ffffff8,
+            ),
+        };
+        (2..end).contains(&next).then_some(next)
+    }
+
+    // the data of cluster chain, limited by size if known
+    fn read_chain(
+        &self,
+        file: &mut File,
+        cluster: u32,
+        size: Option<u64>,
+        dst: &mut impl Write,
+    ) -> Result<(), io::Error> {
+        let mut remaining = size.unwrap_or(u64::MAX);
+        let mut cluster = (cluster >= 2).then_some(cluster);
+        let mut buf = vec![0u8; self.cluster_size as usize];
+        // the clusters in a chain never exceed the table, unless it loops
+        let mut count = 0;
+        while let Some(current) = cluster.filter(|_| remaining > 0) {
+            count += 1;
+            if count > self.table.len() {
+                return Err(io::Error::from(ErrorKind::InvalidData));
+            }
+            let len = remaining.min(self.cluster_size) as usize;
+            file.seek(SeekFrom::Start(
+                self.offset + self.data_offset + (current as u64 - 2) * self.cluster_size,
+            ))?;
+            file.read_exact(&mut buf[..len])?;
+            dst.write_all(&buf[..len])?;
+            remaining -= len as u64;
+            cluster = self.next_cluster(current);
+        }
+        if size.is_some() && remaining > 0 {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        Ok(())
+    }
+
+    fn root_data(&self, file: &mut File) -> Result<Vec<u8>, io::Error> {
+        let mut data = Vec::new();
+        if self.fat_type == FatType::Fat32 {
+            self.read_chain(file, self.root_cluster, None, &mut data)?;
+        } else {
+            data.resize(self.root_size as usize, 0);
+            file.seek(SeekFrom::Start(self.offset + self.root_offset))?;
+            file.read_exact(&mut data)?;
+        }
+        Ok(data)
+    }
+
+    fn entries(data: &[u8]) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        let mut long_name: Vec<u16> = Vec::new();
+        for entry in data.chunks_exact(DIR_ENTRY_SIZE) {
+            match entry[0] {
+                0 => break,
+                DELETED => {
+                    long_name.clear();
+                    continue;
+                }
+                _ => {}
+            }
+            let attr = entry[11];
+            if attr & ATTR_LONG_NAME == ATTR_LONG_NAME {
+                // the long name entries are stored in reverse order
+                let chars = [&entry[1..11], &entry[14..26], &entry[28..32]].concat();
+                let mut part: Vec<u16> = chars
+                    .chunks_exact(2)
+                    .map(LittleEndian::read_u16)
+                    .take_while(|&c| c != 0)
+                    .collect();
+                if entry[0] & 0x40 != 0 {
+                    long_name.clear();
+                }
+                part.append(&mut long_name);
+                long_name = part;
+                continue;
+            }
+            if attr & ATTR_VOLUME_ID != 0 {
+                long_name.clear();
+                continue;
+            }
+
+            let name = if long_name.is_empty() {
+                short_name(entry)
+            } else {
+                String::from_utf16_lossy(&long_name)
+            };
+            long_name.clear();
+            if name == "." || name == ".." {
+                continue;
+            }
+            let cluster = (LittleEndian::read_u16(&entry[20..22]) as u32) << 16
+                | LittleEndian::read_u16(&entry[26..28]) as u32;
+            entries.push(Entry {
+                name,
+                is_dir: attr & ATTR_DIRECTORY != 0,
+                is_file: attr & ATTR_DIRECTORY == 0,
+                node: cluster as u64,
+                size: LittleEndian::read_u32(&entry[28..32]) as u64,
+            });
+        }
+        entries
+    }
+
+    pub fn read_root(&self, file: &mut File) -> Result<Vec<Entry>, io::Error> {
+        Ok(Self::entries(&self.root_data(file)?))
+    }
+
+    pub fn read_entries(&self, file: &mut File, dir: &Entry) -> Result<Vec<Entry>, io::Error> {
+        let mut data = Vec::new();
+        self.read_chain(file, dir.node as u32, None, &mut data)?;
+        Ok(Self::entries(&data))
+    }
+
+    pub fn read_file(&self, file: &mut File, entry: &Entry) -> Result<File, io::Error> {
+        let mut dst = tempfile::tempfile()?;
+        self.read_chain(file, entry.node as u32, Some(entry.size), &mut dst)?;
+        Ok(dst)
+    }
+}
+
+// 8.3 name, e.g. "VMLINUZ EFI" as "VMLINUZ.EFI"
+fn short_name(entry: &[u8]) -> String {
+    let case = |part: &[u8], lower: bool| {
+        let part = String::from_utf8_lossy(part).trim_end().to_string();
+        if lower {
+            part.to_ascii_lowercase()
+        } else {
+            part
+        }
+    };
+    let base = case(&entry[0..8], entry[12] & LOWERCASE_BASE != 0);
+    let ext = case(&entry[8..11], entry[12] & LOWERCASE_EXT != 0);
+    if ext.is_empty() {
+        base
+    } else {
+        format!("{base}.{ext}")
+    }
+}
+
+pub fn parse(file: &mut File, offset: u64) -> Result<Fat, io::Error> {
+    let mut boot = [0u8; 512];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut boot)?;
+
+    let invalid = || io::Error::from(ErrorKind::InvalidData);
+    let bytes_per_sector = LittleEndian::read_u16(&boot[11..13]) as u64;
+    let sectors_per_cluster = boot[13] as u64;
+    let reserved = LittleEndian::read_u16(&boot[14..16]) as u64;
+    let fats = boot[16] as u64;
+    let root_entries = LittleEndian::read_u16(&boot[17..19]) as u64;
+    let total = match LittleEndian::read_u16(&boot[19..21]) {
+        0 => LittleEndian::read_u32(&boot[32..36]) as u64,
+        total => total as u64,
+    };
+    let fat_size = match LittleEndian::read_u16(&boot[22..24]) {
+        0 => LittleEndian::read_u32(&boot[36..40]) as u64,
+        size => size as u64,
+    };
+    // the boot sector begins with a jump instruction
+    if !matches!(boot[0], 0xeb | 0xe9)
+        || &boot[510..512] != b"\x55\xaa"
+        || !(512..=4096).contains(&bytes_per_sector)
+        || !bytes_per_sector.is_power_of_two()
+        || !sectors_per_cluster.is_power_of_two()
+        || reserved == 0
+        || !(1..=2).contains(&fats)
+        || fat_size == 0
+    {
+        return Err(invalid());
+    }
+
+    let root_sectors = (root_entries * DIR_ENTRY_SIZE as u64).div_ceil(bytes_per_sector);
+    let data_sectors = reserved + fats * fat_size + root_sectors;
+    let clusters = total.checked_sub(data_sectors).ok_or_else(invalid)? / sectors_per_cluster;
+    // the type is determined by the count of clusters only
+    let fat_type = if clusters < 4085 {
+        FatType::Fat12
+    } else if clusters < 65525 {
+        FatType::Fat16
+    } else {
+        FatType::Fat32
+    };
+
+    let table_offset = offset.saturating_add(reserved * bytes_per_sector);
+    let table_size = fat_size * bytes_per_sector;
+    if table_offset.saturating_add(table_size) > file.metadata()?.len() {
+        return Err(io::Error::from(ErrorKind::UnexpectedEof));
+    }
+    let mut table = vec![0u8; table_size as usize];
+    file.seek(SeekFrom::Start(table_offset))?;
+    file.read_exact(&mut table)?;
+
+    Ok(Fat {
+        root_cluster: match fat_type {
+            FatType::Fat32 => LittleEndian::read_u32(&boot[44..48]),
+            _ => 0,
+        },
+        fat_type,
+        offset,
+        cluster_size: sectors_per_cluster * bytes_per_sector,
+        data_offset: data_sectors * bytes_per_sector,
+        root_offset: (reserved + fats * fat_size) * bytes_per_sector,
+        root_size: root_entries * DIR_ENTRY_SIZE as u64,
+        table,
+    })
+}
@@ -0,0 +1,158 @@
+use byteorder::{BigEndian, ByteOrder};
+use std::io::{self, ErrorKind};
+
+// flattened device tree header and structure block tokens, in big endian
+pub const FDT_MAGIC: u32 = 0xd00dfeed;
+pub const FDT_HEADER_SIZE: usize = 40;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+pub struct Header {
+    pub totalsize: u32,
+    pub off_dt_struct: u32,
+    pub off_dt_strings: u32,
+    pub version: u32,
+    pub size_dt_strings: u32,
+}
+
+#[derive(Default)]
+pub struct Node {
+    pub name: String,
+    pub props: Vec<(String, Vec<u8>)>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    pub fn property(&self, name: &str) -> Option<&[u8]> {
+        self.props
+            .iter()
+            .find(|(prop, _)| prop == name)
+            .map(|(_, value)| &value[..])
+    }
+
+    pub fn property_str(&self, name: &str) -> Option<String> {
+        self.property_strs(name).into_iter().next()
+    }
+
+    // string list properties like "compatible" are NUL-separated
+    pub fn property_strs(&self, name: &str) -> Vec<String> {
+        self.property(name)
+            .map(|value| {
+                value
+                    .split(|&b| b == 0)
+                    .filter(|s| !s.is_empty())
+                    .map(|s| String::from_utf8_lossy(s).into_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn property_u32(&self, name: &str) -> Option<u32> {
+        self.property(name)
+            .filter(|value| value.len() == 4)
+            .map(BigEndian::read_u32)
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|node| node.name == name)
+    }
+}
+
+pub fn parse_header(data: &[u8]) -> Result<Header, io::Error> {
+    if data.len() < FDT_HEADER_SIZE || BigEndian::read_u32(&data[0..4]) != FDT_MAGIC {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    let header = Header {
+        totalsize: BigEndian::read_u32(&data[4..8]),
+        off_dt_struct: BigEndian::read_u32(&data[8..12]),
+        off_dt_strings: BigEndian::read_u32(&data[12..16]),
+        version: BigEndian::read_u32(&data[20..24]),
+        size_dt_strings: BigEndian::read_u32(&data[32..36]),
+    };
+
+    // sanity check the header since the magic number alone is weak
+    let totalsize = header.totalsize as u64;
+    if header.version < 16
+        || header.version > 17
+        || totalsize < FDT_HEADER_SIZE as u64
+        || header.off_dt_struct as u64 >= totalsize
+        || header.off_dt_strings as u64 + header.size_dt_strings as u64 > totalsize
+    {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    Ok(header)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, io::Error> {
+    data.get(offset..offset + 4)
+        .map(BigEndian::read_u32)
+        .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))
+}
+
+fn read_cstr(data: &[u8], offset: usize) -> Result<&[u8], io::Error> {
+    let bytes = data
+        .get(offset..)
+        .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?;
+    let len = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?;
+    Ok(&bytes[..len])
+}
+
+pub fn parse(data: &[u8]) -> Result<Node, io::Error> {
+    let header = parse_header(data)?;
+    let data = data
+        .get(..header.totalsize as usize)
+        .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?;
+    let strings = &data[header.off_dt_strings as usize..][..header.size_dt_strings as usize];
+
+    // the nodes being parsed, the root node is popped at the end
+    let mut stack: Vec<Node> = Vec::new();
+    let mut offset = header.off_dt_struct as usize;
+    loop {
+        let token = read_u32(data, offset)?;
+        offset += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = read_cstr(data, offset)?;
+                offset += (name.len() + 1 + 3) & !3;
+                stack.push(Node {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    ..Default::default()
+                });
+            }
+            FDT_END_NODE => {
+                let node = stack
+                    .pop()
+                    .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => return Ok(node),
+                }
+            }
+            FDT_PROP => {
+                let len = read_u32(data, offset)? as usize;
+                let nameoff = read_u32(data, offset + 4)? as usize;
+                offset += 8;
+                let value = data
+                    .get(offset..offset + len)
+                    .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?;
+                offset += (len + 3) & !3;
+                let name = read_cstr(strings, nameoff)?;
+                stack
+                    .last_mut()
+                    .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?
+                    .props
+                    .push((String::from_utf8_lossy(name).into_owned(), value.to_vec()));
+            }
+            FDT_NOP => {}
+            // FDT_END or unknown token before the root node ends
+            _ => return Err(io::Error::from(ErrorKind::InvalidData)),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::fdt;
+use std::{
+    fmt,
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+};
+
+pub struct Kernel {
+    pub name: String,
+    pub description: Option<String>,
+    pub compression: String,
+    pub data: Vec<u8>,
+}
+
+impl fmt::Display for Kernel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FIT kernel:   /images/{}", self.name)?;
+        if let Some(description) = &self.description {
+            write!(f, " ({description})")?;
+        }
+        write!(
+            f,
+            ", {} bytes, compression: {}",
+            self.data.len(),
+            self.compression
+        )
+    }
+}
+
+impl Kernel {
+    pub fn payload(&self) -> Result<File, io::Error> {
+        let decompress: Option<crate::Decompress> = match self.compression.as_str() {
+            "none" => None,
+            "gzip" => Some(crate::gunzip),
+            "bzip2" => Some(crate::bunzip2),
+            "lzma" => Some(crate::unlzma),
+            "lzo" => Some(crate::unlzo),
+            "lz4" => Some(crate::unlz4),
+            "zstd" => Some(crate::unzstd),
+            _ => return Err(io::Error::from(ErrorKind::Unsupported)),
+        };
+
+        crate::decompress_data(&self.data, decompress)
+    }
+}
+
+fn is_kernel(node: &fdt::Node) -> bool {
+    match node.property_str("type") {
+        Some(image_type) => image_type == "kernel" || image_type == "kernel_noload",
+        None => node.name.starts_with("kernel"),
+    }
+}
+
+pub fn parse(file: &mut File) -> Result<Vec<Kernel>, io::Error> {
+    let mut header = [0u8; fdt::FDT_HEADER_SIZE];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+    let totalsize = fdt::parse_header(&header)?.totalsize as usize;
+    let len = file.metadata()?.len();
+    if totalsize as u64 > len {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    let mut blob = header.to_vec();
+    blob.resize(totalsize, 0);
+    file.read_exact(&mut blob[fdt::FDT_HEADER_SIZE..])?;
+    let root = fdt::parse(&blob)?;
+
+    let images = root
+        .child("images")
+        .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?;
+
+    let mut kernels = Vec::new();
+    for node in images.children.iter().filter(|node| is_kernel(node)) {
+        // the data is either embedded in the "data" property, or external
+        // to the FIT, placed after the tree or at an absolute position
+        let data = if let Some(data) = node.property("data") {
+            data.to_vec()
+        } else if let Some(size) = node.property_u32("data-size") {
+            let offset = if let Some(offset) = node.property_u32("data-offset") {
+                ((totalsize + 3) & !3) as u64 + offset as u64
+            } else if let Some(position) = node.property_u32("data-position") {
+                position as u64
+            } else {
+                continue;
+            };
+            if offset.saturating_add(size as u64) > len {
+                return Err(io::Error::from(ErrorKind::InvalidData));
+            }
+            let mut data = vec![0u8; size as usize];
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut data)?;
+            data
+        } else {
+            continue;
+        };
+
+        kernels.push(Kernel {
+            name: node.name.clone(),
+            description: node.property_str("description"),
+            compression: node
+                .property_str("compression")
+                .unwrap_or_else(|| String::from("none")),
+            data,
+        });
+    }
+
+    if kernels.is_empty() {
+        return Err(io::Error::from(ErrorKind::NotFound));
+    }
+
+    Ok(kernels)
+}
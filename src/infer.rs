@@ -0,0 +1,465 @@
+use crate::{elf, info, kallsyms};
+use argh::FromArgs;
+use regex::bytes::RegexBuilder;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    fs::File,
+    io::{self, Read, Seek, Write},
+};
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "infer",
+    description = "infer a partial config from the symbols and strings of a kernel image"
+)]
+pub struct InferArgs {
+    #[argh(positional, description = "kernel image")]
+    image: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        })
+    }
+}
+
+pub enum Signature {
+    // a function or variable only built with the option
+    Symbol(&'static str),
+    // a flag in the linux_banner, e.g. "SMP"
+    Banner(&'static str),
+    // a message only built with the option
+    Message(&'static str),
+}
+
+use Signature::{Banner, Message, Symbol};
+
+// an option may have several signatures, the most confident one found is taken
+const SIGNATURES: &[(&str, Signature)] = &[
+    ("CONFIG_ACPI", Symbol("acpi_bus_register_driver")),
+    ("CONFIG_ACPI", Message("ACPI: Interpreter enabled")),
+    ("CONFIG_AIO", Symbol("aio_complete")),
+    ("CONFIG_AUDIT", Symbol("audit_log_start")),
+    (
+        "CONFIG_AUDIT",
+        Message("audit: initializing netlink subsys"),
+    ),
+    ("CONFIG_BINFMT_ELF", Symbol("load_elf_binary")),
+    ("CONFIG_BINFMT_MISC", Symbol("load_misc_binary")),
+    ("CONFIG_BINFMT_SCRIPT", Symbol("load_script")),
+    ("CONFIG_BLK_DEV_DM", Symbol("dm_table_create")),
+    ("CONFIG_BLK_DEV_INITRD", Symbol("initrd_load")),
+    (
+        "CONFIG_BLK_DEV_INITRD",
+        Message("Initramfs unpacking failed"),
+    ),
+    ("CONFIG_BLK_DEV_LOOP", Symbol("loop_add")),
+    ("CONFIG_BLOCK", Symbol("submit_bio")),
+    ("CONFIG_BPF_SYSCALL", Symbol("bpf_map_new_fd")),
+    ("CONFIG_BRIDGE", Symbol("br_dev_xmit")),
+    ("CONFIG_BT", Symbol("hci_register_dev")),
+    ("CONFIG_BTRFS_FS", Symbol("btrfs_sync_fs")),
+    ("CONFIG_BTRFS_FS", Message("Btrfs loaded")),
+    ("CONFIG_CGROUPS", Symbol("cgroup_mkdir")),
+    ("CONFIG_COMPACTION", Symbol("compact_zone")),
+    ("CONFIG_CONFIGFS_FS", Symbol("configfs_register_subsystem")),
+    ("CONFIG_COREDUMP", Symbol("do_coredump")),
+    ("CONFIG_CPU_FREQ", Symbol("cpufreq_register_driver")),
+    ("CONFIG_CPU_IDLE", Symbol("cpuidle_register_driver")),
+    ("CONFIG_CRYPTO", Symbol("crypto_alloc_base")),
+    ("CONFIG_DEBUG_FS", Symbol("debugfs_create_file")),
+    ("CONFIG_DEVTMPFS", Symbol("devtmpfs_create_node")),
+    ("CONFIG_DEVTMPFS", Message("devtmpfs: initialized")),
+    ("CONFIG_DRM", Symbol("drm_dev_register")),
+    ("CONFIG_EFI", Symbol("efi_mem_desc_lookup")),
+    ("CONFIG_EPOLL", Symbol("do_epoll_wait")),
+    ("CONFIG_EVENTFD", Symbol("eventfd_signal")),
+    ("CONFIG_EXT4_FS", Symbol("ext4_fill_super")),
+    ("CONFIG_EXT4_FS", Message("EXT4-fs")),
+    ("CONFIG_FANOTIFY", Symbol("fanotify_handle_event")),
+    ("CONFIG_FB", Symbol("register_framebuffer")),
+    ("CONFIG_FUNCTION_TRACER", Symbol("ftrace_set_filter")),
+    ("CONFIG_FUSE_FS", Symbol("fuse_fill_super")),
+    ("CONFIG_FUTEX", Symbol("do_futex")),
+    ("CONFIG_HIBERNATION", Symbol("hibernate")),
+    ("CONFIG_HOTPLUG_CPU", Symbol("cpu_device_down")),
+    ("CONFIG_HUGETLBFS", Symbol("hugetlbfs_fill_super")),
+    ("CONFIG_I2C", Symbol("i2c_register_driver")),
+    ("CONFIG_IKCONFIG", Symbol("ikconfig_read_current")),
+    ("CONFIG_IKCONFIG", Message("IKCFG_ST")),
+    ("CONFIG_IKHEADERS", Symbol("ikheaders_read")),
+    ("CONFIG_INET", Symbol("inet_init")),
+    ("CONFIG_INOTIFY_USER", Symbol("inotify_handle_inode_event")),
+    ("CONFIG_INPUT", Symbol("input_register_device")),
+    ("CONFIG_IO_URING", Symbol("io_uring_setup")),
+    ("CONFIG_IPV6", Symbol("inet6_bind")),
+    ("CONFIG_KALLSYMS", Symbol("kallsyms_lookup_name")),
+    ("CONFIG_KASAN", Symbol("kasan_report")),
+    (
+        "CONFIG_KASAN",
+        Message("KernelAddressSanitizer initialized"),
+    ),
+    ("CONFIG_KCOV", Symbol("__sanitizer_cov_trace_pc")),
+    ("CONFIG_KPROBES", Symbol("register_kprobe")),
+    ("CONFIG_KSM", Symbol("ksm_madvise")),
+    ("CONFIG_KVM", Symbol("kvm_init")),
+    ("CONFIG_LOCKDEP", Symbol("lock_acquire")),
+    ("CONFIG_MAGIC_SYSRQ", Symbol("handle_sysrq")),
+    ("CONFIG_MD", Symbol("md_run")),
+    ("CONFIG_MMC", Symbol("mmc_add_host")),
+    ("CONFIG_MODULES", Symbol("load_module")),
+    ("CONFIG_MODULE_SIG", Symbol("mod_verify_sig")),
+    ("CONFIG_MODULE_SIG", Message("~Module signature appended~")),
+    ("CONFIG_NET", Symbol("sock_register")),
+    ("CONFIG_NET", Message("NET: Registered ")),
+    ("CONFIG_NETFILTER", Symbol("nf_register_net_hook")),
+    ("CONFIG_NET_NS", Symbol("copy_net_ns")),
+    ("CONFIG_NET_SCHED", Symbol("qdisc_create")),
+    ("CONFIG_NFS_FS", Symbol("nfs_alloc_client")),
+    ("CONFIG_NF_CONNTRACK", Symbol("nf_conntrack_alloc")),
+    ("CONFIG_NF_TABLES", Symbol("nft_register_expr")),
+    ("CONFIG_NUMA", Symbol("node_reclaim")),
+    ("CONFIG_NVME_CORE", Symbol("nvme_init_ctrl")),
+    ("CONFIG_OVERLAY_FS", Symbol("ovl_fill_super")),
+    ("CONFIG_PACKET", Symbol("packet_create")),
+    ("CONFIG_PCI", Symbol("pci_bus_read_config_byte")),
+    (
+        "CONFIG_PERF_EVENTS",
+        Symbol("perf_event_create_kernel_counter"),
+    ),
+    ("CONFIG_PID_NS", Symbol("copy_pid_ns")),
+    ("CONFIG_POSIX_MQUEUE", Symbol("mq_init_ns")),
+    ("CONFIG_PREEMPT", Banner("PREEMPT")),
+    ("CONFIG_PREEMPT_DYNAMIC", Banner("PREEMPT_DYNAMIC")),
+    ("CONFIG_PREEMPT_RT", Banner("PREEMPT_RT")),
+    ("CONFIG_PRINTK", Symbol("vprintk_emit")),
+    ("CONFIG_PROC_FS", Symbol("proc_fill_super")),
+    ("CONFIG_PSI", Symbol("psi_task_change")),
+    ("CONFIG_QUOTA", Symbol("dquot_initialize")),
+    ("CONFIG_SCSI", Symbol("scsi_add_host_with_dma")),
+    ("CONFIG_SECCOMP", Symbol("__secure_computing")),
+    ("CONFIG_SECURITY", Symbol("security_init")),
+    ("CONFIG_SECURITY_APPARMOR", Symbol("apparmor_init")),
+    ("CONFIG_SECURITY_APPARMOR", Message("AppArmor initialized")),
+    ("CONFIG_SECURITY_SELINUX", Symbol("selinux_init")),
+    (
+        "CONFIG_SECURITY_SELINUX",
+        Message("SELinux:  Initializing."),
+    ),
+    (
+        "CONFIG_SERIAL_8250",
+        Symbol("serial8250_register_8250_port"),
+    ),
+    ("CONFIG_SHMEM", Symbol("shmem_fill_super")),
+    ("CONFIG_SIGNALFD", Symbol("do_signalfd4")),
+    ("CONFIG_SLUB", Symbol("slub_cpu_dead")),
+    ("CONFIG_SMP", Symbol("smp_call_function")),
+    ("CONFIG_SMP", Banner("SMP")),
+    ("CONFIG_SND", Symbol("snd_card_new")),
+    ("CONFIG_SPI", Symbol("spi_register_controller")),
+    ("CONFIG_SQUASHFS", Symbol("squashfs_fill_super")),
+    ("CONFIG_STACKPROTECTOR", Symbol("__stack_chk_fail")),
+    ("CONFIG_SUSPEND", Symbol("pm_suspend")),
+    ("CONFIG_SWAP", Symbol("si_swapinfo")),
+    ("CONFIG_SYSFS", Symbol("sysfs_create_file_ns")),
+    ("CONFIG_SYSVIPC", Symbol("ipc_init")),
+    ("CONFIG_TIMERFD", Symbol("timerfd_read")),
+    (
+        "CONFIG_TRANSPARENT_HUGEPAGE",
+        Symbol("split_huge_page_to_list"),
+    ),
+    ("CONFIG_TTY", Symbol("tty_register_driver")),
+    ("CONFIG_TUN", Symbol("tun_chr_open")),
+    ("CONFIG_UBSAN", Symbol("__ubsan_handle_out_of_bounds")),
+    ("CONFIG_UNIX", Symbol("unix_release")),
+    ("CONFIG_USB", Symbol("usb_register_driver")),
+    ("CONFIG_USER_NS", Symbol("create_user_ns")),
+    ("CONFIG_VFAT_FS", Symbol("vfat_fill_super")),
+    ("CONFIG_VIRTIO", Symbol("register_virtio_driver")),
+    ("CONFIG_VIRTIO_BLK", Symbol("virtblk_probe")),
+    ("CONFIG_VIRTIO_NET", Symbol("virtnet_probe")),
+    ("CONFIG_VT", Symbol("vt_ioctl")),
+    ("CONFIG_XFS_FS", Symbol("xfs_fs_fill_super")),
+    ("CONFIG_XFS_FS", Message("SGI XFS with")),
+    ("CONFIG_ZRAM", Symbol("zram_add")),
+    ("CONFIG_ZSWAP", Symbol("zswap_store")),
+    ("CONFIG_ZSWAP", Message("zswap: loaded using pool")),
+];
+
+#[derive(Default)]
+pub struct Evidence {
+    // the symbols in kallsyms or the ELF symbol table
+    pub symbols: HashSet<String>,
+    // whether the symbols are all of the kernel, so an absent one isn't built
+    pub complete: bool,
+    // the names in __ksymtab_strings
+    pub exports: HashSet<String>,
+    pub banner_flags: Vec<String>,
+    pub strings: HashSet<&'static str>,
+}
+
+pub struct Inferred {
+    pub option: &'static str,
+    pub enabled: bool,
+    pub confidence: Confidence,
+    pub reason: String,
+}
+
+pub fn infer(evidence: &Evidence) -> Vec<Inferred> {
+    let mut options: BTreeMap<&str, Option<Inferred>> = BTreeMap::new();
+    for (option, signature) in SIGNATURES {
+        let found = match *signature {
+            Symbol(name) if evidence.symbols.contains(name) => {
+                Some((Confidence::High, format!("symbol {name}")))
+            }
+            Symbol(name) if evidence.exports.contains(name) => {
+                Some((Confidence::Medium, format!("exported symbol {name}")))
+            }
+            Banner(flag) if evidence.banner_flags.iter().any(|f| f == flag) => {
+                Some((Confidence::Medium, format!("banner flag {flag}")))
+            }
+            Message(text) if evidence.strings.contains(text) => {
+                Some((Confidence::Low, format!("string \"{text}\"")))
+            }
+            _ => None,
+        };
+
+        let best = options.entry(option).or_default();
+        if let Some((confidence, reason)) = found {
+            let better = match best {
+                Some(best) => confidence > best.confidence,
+                None => true,
+            };
+            if better {
+                *best = Some(Inferred {
+                    option,
+                    enabled: true,
+                    confidence,
+                    reason,
+                });
+            }
+        }
+    }
+
+    options
+        .into_iter()
+        .filter_map(|(option, inferred)| {
+            inferred.or_else(|| {
+                // none of the symbols in a complete kallsyms, but it may be a
+                // module or the symbols may be renamed or inlined, so it's
+                // only weak evidence
+                let symbols: Vec<&str> = SIGNATURES
+                    .iter()
+                    .filter_map(|(name, signature)| match signature {
+                        Symbol(symbol) if *name == option => Some(*symbol),
+                        _ => None,
+                    })
+                    .collect();
+                (evidence.complete && !symbols.is_empty()).then(|| Inferred {
+                    option,
+                    enabled: false,
+                    confidence: Confidence::Low,
+                    reason: format!("no symbol {} built in", symbols.join(", ")),
+                })
+            })
+        })
+        .collect()
+}
+
+fn find_strings(data: &[u8]) -> HashSet<&'static str> {
+    let texts: Vec<&'static str> = SIGNATURES
+        .iter()
+        .filter_map(|(_, signature)| match signature {
+            Message(text) => Some(*text),
+            _ => None,
+        })
+        .collect();
+    let pattern = texts
+        .iter()
+        .map(|text| regex::escape(text))
+        .collect::<Vec<_>>()
+        .join("|");
+    let Ok(re) = RegexBuilder::new(&pattern).unicode(false).build() else {
+        return HashSet::new();
+    };
+    re.find_iter(data)
+        .filter_map(|m| texts.iter().find(|text| text.as_bytes() == m.as_bytes()))
+        .copied()
+        .collect()
+}
+
+fn collect_evidence(vmlinux: &mut File) -> Evidence {
+    let mut data = Vec::new();
+    if vmlinux
+        .rewind()
+        .and_then(|_| vmlinux.read_to_end(&mut data))
+        .is_err()
+    {
+        return Evidence::default();
+    }
+
+    let mut evidence = Evidence {
+        strings: find_strings(&data),
+        banner_flags: info::find_banner(vmlinux)
+            .and_then(|banner| banner.flags)
+            .map(|flags| flags.split_whitespace().map(String::from).collect())
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+
+    if let Ok(kallsyms) = kallsyms::parse(&data) {
+        evidence.complete = true;
+        evidence.symbols = kallsyms
+            .symbols
+            .into_iter()
+            .map(|symbol| symbol.name)
+            .collect();
+    }
+    if let Ok(elf) = elf::parse(vmlinux) {
+        if !evidence.complete {
+            if let Ok(symbols) = elf.symbols(vmlinux) {
+                evidence.complete = !symbols.is_empty();
+                evidence.symbols = symbols.into_iter().map(|symbol| symbol.name).collect();
+            }
+        }
+        // the names of exported symbols, which are kept even if stripped
+        if let Some(section) = elf
+            .sections
+            .iter()
+            .find(|section| section.name == "__ksymtab_strings")
+        {
+            if let Ok(strings) = elf.read_section(vmlinux, section) {
+                evidence.exports = strings
+                    .split(|&b| b == 0)
+                    .filter(|name| !name.is_empty())
+                    .map(|name| String::from_utf8_lossy(name).into_owned())
+                    .collect();
+            }
+        }
+    }
+    evidence
+}
+
+pub fn run(args: InferArgs) {
+    let mut file = match File::open(&args.image) {
+        Ok(image) => image,
+        Err(err) => {
+            eprintln!("Failed to open file {}: {err}", &args.image);
+            return;
+        }
+    };
+
+    let mut vmlinux = match crate::extract_vmlinux(&mut file) {
+        Ok(vmlinux) => vmlinux,
+        Err(_) => {
+            eprintln!("{}: Cannot find vmlinux.", env!("CARGO_BIN_NAME"));
+            return;
+        }
+    };
+
+    let evidence = collect_evidence(&mut vmlinux);
+    let inferred = infer(&evidence);
+    if inferred.is_empty() {
+        eprintln!(
+            "{}: Cannot infer any config options.",
+            env!("CARGO_BIN_NAME")
+        );
+        return;
+    }
+
+    // stop quietly once stdout is closed, e.g. piped to head
+    _ = write_inferred(&mut io::stdout().lock(), &inferred);
+}
+
+fn write_inferred(out: &mut impl Write, inferred: &[Inferred]) -> Result<(), io::Error> {
+    writeln!(out, "#")?;
+    writeln!(
+        out,
+        "# INFERRED by {} from the kernel image, NOT the original config.",
+        env!("CARGO_BIN_NAME")
+    )?;
+    writeln!(
+        out,
+        "# Only a few options are guessed, and modules (=m) can't be detected."
+    )?;
+    writeln!(out, "#")?;
+    writeln!(
+        out,
+        "# high:   a symbol of the option is in kallsyms or the symbol table"
+    )?;
+    writeln!(out, "# medium: an exported symbol or banner flag")?;
+    writeln!(
+        out,
+        "# low:    a message of the option is in the kernel, or no symbol in kallsyms"
+    )?;
+    writeln!(out, "#")?;
+    for inferred in inferred {
+        writeln!(out)?;
+        writeln!(out, "# {}: {}", inferred.confidence, inferred.reason)?;
+        if inferred.enabled {
+            writeln!(out, "{}=y", inferred.option)?;
+        } else {
+            writeln!(out, "# {} is not set", inferred.option)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer() {
+        let mut evidence = Evidence {
+            symbols: HashSet::from([String::from("ext4_fill_super")]),
+            exports: HashSet::from([String::from("smp_call_function")]),
+            banner_flags: vec![String::from("SMP")],
+            strings: HashSet::from(["EXT4-fs", "SGI XFS with"]),
+            ..Default::default()
+        };
+
+        let inferred = infer(&evidence);
+        let find = |inferred: &[Inferred], option| {
+            inferred
+                .iter()
+                .find(|inferred| inferred.option == option)
+                .map(|inferred| (inferred.enabled, inferred.confidence))
+        };
+        assert_eq!(
+            find(&inferred, "CONFIG_EXT4_FS"),
+            Some((true, Confidence::High))
+        );
+        assert_eq!(
+            find(&inferred, "CONFIG_SMP"),
+            Some((true, Confidence::Medium))
+        );
+        assert_eq!(
+            find(&inferred, "CONFIG_XFS_FS"),
+            Some((true, Confidence::Low))
+        );
+        assert_eq!(find(&inferred, "CONFIG_BTRFS_FS"), None);
+
+        // an option without its symbols in a complete kallsyms isn't built in
+        evidence.complete = true;
+        let inferred = infer(&evidence);
+        assert_eq!(
+            find(&inferred, "CONFIG_BTRFS_FS"),
+            Some((false, Confidence::Low))
+        );
+        assert_eq!(find(&inferred, "CONFIG_PREEMPT_RT"), None);
+    }
+}
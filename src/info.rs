@@ -0,0 +1,372 @@
+use argh::FromArgs;
+use flate2::Crc;
+use regex::Regex;
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+// the longest banner to be read
+const BANNER_MAX_LEN: u64 = 1024;
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "info",
+    description = "show the structure, version and build information of a kernel image"
+)]
+pub struct InfoArgs {
+    #[argh(positional, description = "kernel image")]
+    image: String,
+}
+
+// the linux_banner, e.g. "Linux version 6.1.0 (user@host) (gcc (GCC) 12.2.0,
+// GNU ld (GNU Binutils) 2.39.0) #1 SMP PREEMPT_DYNAMIC Fri Nov 18 10:35:23 UTC 2022"
+#[derive(Default)]
+pub struct Banner {
+    pub text: String,
+    pub release: String,
+    pub built_by: Option<String>,
+    pub toolchain: Option<String>,
+    pub build_number: Option<String>,
+    pub flags: Option<String>,
+    pub build_date: Option<String>,
+}
+
+impl Banner {
+    pub fn parse(text: &str) -> Option<Banner> {
+        let re = Regex::new(
+            r"^Linux version (\S+)(?: \(([^() ]*)\))?(?: \((.*)\))? #(\S*)(?: (.*?))??(?: ((?:Mon|Tue|Wed|Thu|Fri|Sat|Sun) .*))?$",
+        )
+        .ok()?;
+        let caps = re.captures(text)?;
+        let group = |i| {
+            caps.get(i)
+                .map(|m| m.as_str().trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        Some(Banner {
+            text: text.to_string(),
+            release: group(1)?,
+            built_by: group(2),
+            toolchain: group(3),
+            build_number: group(4),
+            flags: group(5),
+            build_date: group(6),
+        })
+    }
+}
+
+// the header comment of config, e.g. "# Linux/x86 6.1.0 Kernel Configuration"
+pub struct ConfigHeader {
+    pub arch: String,
+    pub version: String,
+}
+
+impl ConfigHeader {
+    pub fn parse(config: &str) -> Option<ConfigHeader> {
+        let re = Regex::new(r"(?m)^# Linux/(\S+) (\S+) Kernel Configuration$").ok()?;
+        let caps = re.captures(config)?;
+        Some(ConfigHeader {
+            arch: caps[1].to_string(),
+            version: caps[2].to_string(),
+        })
+    }
+}
+
+// the value of a string option in config, with the quotes removed
+pub fn config_string(config: &str, name: &str) -> Option<String> {
+    let prefix = format!("{name}=");
+    config
+        .lines()
+        .find_map(|line| line.strip_prefix(&prefix))
+        .map(|value| value.trim_matches('"').replace("\\\"", "\""))
+}
+
+fn read_banner(file: &mut File, offset: u64) -> Result<String, io::Error> {
+    let mut text = Vec::new();
+    file.seek(SeekFrom::Start(offset))?;
+    Read::by_ref(file)
+        .take(BANNER_MAX_LEN)
+        .read_to_end(&mut text)?;
+    let len = text
+        .iter()
+        .position(|&b| b == 0 || b == b'\n')
+        .unwrap_or(text.len());
+    Ok(String::from_utf8_lossy(&text[..len]).into_owned())
+}
+
+pub fn find_banner(file: &mut File) -> Option<Banner> {
+    // there may be a placeholder banner without build number and date,
+    // e.g. in init/version.c since v6.1, so the most complete one is chosen
+    let mut banners = Vec::new();
+    let mut start = 0;
+    while let Ok(offset) = crate::search_regex_from(file, crate::vmlinux::LINUX_BANNER, start) {
+        if let Some(banner) = read_banner(file, offset)
+            .ok()
+            .and_then(|text| Banner::parse(&text))
+        {
+            banners.push(banner);
+        }
+        start = offset + 1;
+    }
+    banners
+        .into_iter()
+        .max_by_key(|banner| (banner.build_number.is_some(), banner.build_date.is_some()))
+}
+
+// the format of a kernel image which isn't unwrapped as a container
+fn image_format(file: &mut File) -> &'static str {
+    let mut header = Vec::new();
+    let read = file
+        .rewind()
+        .and_then(|_| Read::by_ref(file).take(0x240).read_to_end(&mut header));
+    if read.is_err() {
+        return "unknown";
+    }
+    let at = |offset: usize, magic: &[u8]| header.get(offset..offset + magic.len()) == Some(magic);
+
+    if at(0, b"MZ") {
+        // x86 boot protocol, arm64 and RISC-V images with EFI stub
+        if at(0x202, b"HdrS") {
+            "bzImage (PE/COFF)"
+        } else if at(0x38, b"ARM\x64") {
+            "arm64 Image (PE/COFF)"
+        } else if at(0x38, b"RSC\x05") {
+            "RISC-V Image (PE/COFF)"
+        } else if at(4, b"zimg") {
+            "EFI zboot image (PE/COFF)"
+        } else {
+            "PE/COFF"
+        }
+    } else if at(0x202, b"HdrS") {
+        "bzImage"
+    } else if at(0, b"\x7fELF") {
+        "ELF"
+    } else if at(0x38, b"ARM\x64") {
+        "arm64 Image"
+    } else if at(0x38, b"RSC\x05") {
+        "RISC-V Image"
+    } else {
+        "raw data"
+    }
+}
+
+// the reason why no kernel is found in an image, with the compression
+// whose decoder failed if any
+struct Failure {
+    compression: Option<(&'static str, u64)>,
+    error: io::Error,
+}
+
+// the kernel found in the innermost image with the format of that image,
+// the failure is returned as a result so that the trace is kept
+type Probe = (&'static str, Result<crate::Vmlinux, Failure>);
+
+fn probe(file: &mut File) -> Result<Probe, io::Error> {
+    let format = image_format(file);
+    Ok((
+        format,
+        crate::scan_vmlinux(file).map_err(|_| diagnose(file)),
+    ))
+}
+
+fn diagnose(file: &mut File) -> Failure {
+    // the compressed data is expected at the first magic found, which is
+    // decompressed again to get the error of its decoder
+    let Some((name, offset, decompress)) = crate::COMPRESSIONS
+        .into_iter()
+        .filter_map(|(name, pattern, decompress)| {
+            let offset = crate::search_regex(file, pattern).ok()?;
+            Some((name, offset, decompress))
+        })
+        .min_by_key(|&(_, offset, _)| offset)
+    else {
+        return Failure {
+            compression: None,
+            error: io::Error::new(io::ErrorKind::NotFound, "no compressed data found"),
+        };
+    };
+    let error = match crate::decompress_from(file, offset, decompress) {
+        Ok(_) => io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the decompressed data isn't a kernel",
+        ),
+        Err(err) => err,
+    };
+    Failure {
+        compression: Some((name, offset)),
+        error,
+    }
+}
+
+pub fn run(args: InfoArgs) {
+    let mut file = match File::open(&args.image) {
+        Ok(image) => image,
+        Err(err) => {
+            eprintln!("Failed to open file {}: {err}", &args.image);
+            return;
+        }
+    };
+
+    let mut trace = Vec::new();
+    let probed = crate::unwrap_image(&mut file, probe, &mut trace).map(|mut probes| {
+        // the first kernel found is shown, or the first failure if none
+        let index = probes
+            .iter()
+            .position(|(_, (_, vmlinux))| vmlinux.is_ok())
+            .unwrap_or(0);
+        probes.swap_remove(index).1
+    });
+
+    // the format of the innermost image is appended to the containers
+    // unwrapped, unless it's just the raw data of their payload
+    let inner = match &probed {
+        Ok((format, _)) => *format,
+        Err(_) if trace.is_empty() => image_format(&mut file),
+        Err(_) => "raw data",
+    };
+    if trace.is_empty() || inner != "raw data" {
+        trace.push(String::from(inner));
+    }
+    let format = trace.join(" > ");
+
+    let (compression, mut vmlinux) = match probed {
+        Ok((_, Ok(vmlinux))) => vmlinux,
+        Ok((_, Err(failure))) => {
+            let compression = failure.compression.map_or_else(
+                || String::from("none"),
+                |(name, offset)| format!("{name} at offset 0x{offset:x}"),
+            );
+            println!("{:14}{format}", "Format:");
+            println!("{:14}{compression}", "Compression:");
+            println!("{:14}{}", "Error:", failure.error);
+            eprintln!("{}: Cannot find vmlinux.", env!("CARGO_BIN_NAME"));
+            return;
+        }
+        Err(err) => {
+            println!("{:14}{format}", "Format:");
+            println!("{:14}{err}", "Error:");
+            eprintln!("{}: Cannot find vmlinux.", env!("CARGO_BIN_NAME"));
+            return;
+        }
+    };
+
+    let mut fields = vec![("Format", Some(format))];
+    fields.push((
+        "Compression",
+        Some(match compression {
+            Some((name, offset)) => format!("{name} at offset 0x{offset:x}"),
+            None => String::from("none"),
+        }),
+    ));
+    if compression.is_some() {
+        fields.push((
+            "Decompressed",
+            vmlinux
+                .metadata()
+                .ok()
+                .map(|metadata| format!("{} bytes", metadata.len())),
+        ));
+    }
+
+    // the offsets of the config markers within the decompressed kernel
+    let start = crate::search_regex(&vmlinux, "IKCFG_ST").ok();
+    let end = start.and_then(|start| crate::search_regex_from(&vmlinux, "IKCFG_ED", start).ok());
+    fields.push(("IKCFG_ST", start.map(|offset| format!("0x{offset:x}"))));
+    fields.push(("IKCFG_ED", end.map(|offset| format!("0x{offset:x}"))));
+
+    let config = crate::scan_config(&mut vmlinux).unwrap_or_default();
+    if !config.is_empty() {
+        let mut crc = Crc::new();
+        crc.update(&config);
+        fields.push(("Config size", Some(format!("{} bytes", config.len()))));
+        fields.push(("Config CRC32", Some(format!("0x{:08x}", crc.sum()))));
+    }
+    let config = String::from_utf8_lossy(&config);
+    let header = ConfigHeader::parse(&config);
+    let banner = find_banner(&mut vmlinux).unwrap_or_default();
+
+    let version = if banner.release.is_empty() {
+        header.as_ref().map(|header| header.version.clone())
+    } else {
+        Some(banner.release.clone())
+    };
+    // CONFIG_CC_VERSION_TEXT is available since v5.8
+    let compiler = config_string(&config, "CONFIG_CC_VERSION_TEXT").or_else(|| {
+        banner
+            .toolchain
+            .as_ref()
+            .map(|toolchain| toolchain.split(", ").next().unwrap_or_default().to_string())
+    });
+
+    fields.extend([
+        ("Version", version),
+        ("Architecture", header.map(|header| header.arch)),
+        ("Compiler", compiler),
+        ("Toolchain", banner.toolchain),
+        ("Built by", banner.built_by),
+        ("Build number", banner.build_number),
+        ("Build flags", banner.flags),
+        ("Build date", banner.build_date),
+    ]);
+    for (name, value) in fields {
+        if let Some(value) = value {
+            println!("{:14}{value}", format!("{name}:"));
+        }
+    }
+    if !banner.text.is_empty() {
+        println!("{:14}{}", "Banner:", banner.text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_banner() {
+        let banner = Banner::parse(
+            "Linux version 6.1.0-rc5+ (yestyle@Yarch) (gcc (GCC) 12.2.0, GNU ld (GNU Binutils) 2.39.0) #11 SMP PREEMPT_DYNAMIC Fri Nov 18 10:35:23 NZDT 2022",
+        )
+        .unwrap();
+        assert_eq!(banner.release, "6.1.0-rc5+");
+        assert_eq!(banner.built_by.as_deref(), Some("yestyle@Yarch"));
+        assert_eq!(
+            banner.toolchain.as_deref(),
+            Some("gcc (GCC) 12.2.0, GNU ld (GNU Binutils) 2.39.0")
+        );
+        assert_eq!(banner.build_number.as_deref(), Some("11"));
+        assert_eq!(banner.flags.as_deref(), Some("SMP PREEMPT_DYNAMIC"));
+        assert_eq!(
+            banner.build_date.as_deref(),
+            Some("Fri Nov 18 10:35:23 NZDT 2022")
+        );
+
+        // the placeholder in init/version.c
+        let banner = Banner::parse(
+            "Linux version 6.1.0-rc5+ (yestyle@Yarch) (gcc (GCC) 12.2.0, GNU ld (GNU Binutils) 2.39.0) # SMP ",
+        )
+        .unwrap();
+        assert_eq!(banner.build_number, None);
+        assert_eq!(banner.flags.as_deref(), Some("SMP"));
+        assert_eq!(banner.build_date, None);
+
+        assert!(Banner::parse("Linux version %s (%s)").is_none());
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = "#\n# Automatically generated file; DO NOT EDIT.\n\
+                      # Linux/arm64 6.6.0 Kernel Configuration\n#\n\
+                      CONFIG_CC_VERSION_TEXT=\"clang version 17.0.6\"\n";
+        let header = ConfigHeader::parse(config).unwrap();
+        assert_eq!(header.arch, "arm64");
+        assert_eq!(header.version, "6.6.0");
+        assert_eq!(
+            config_string(config, "CONFIG_CC_VERSION_TEXT").as_deref(),
+            Some("clang version 17.0.6")
+        );
+        assert_eq!(config_string(config, "CONFIG_CC_VERSION"), None);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::cpio;
+use argh::FromArgs;
+use regex::bytes::RegexBuilder;
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+};
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "extract-initramfs",
+    description = "extract the initramfs built into a kernel image"
+)]
+pub struct ExtractInitramfsArgs {
+    #[argh(positional, description = "kernel image")]
+    image: String,
+
+    #[argh(
+        option,
+        short = 'o',
+        description = "output file of the cpio archive, default to standard output"
+    )]
+    output: Option<String>,
+
+    #[argh(
+        switch,
+        short = 'l',
+        description = "list the entries of the cpio archive"
+    )]
+    list: bool,
+}
+
+struct Initramfs {
+    offset: u64,
+    compression: Option<&'static str>,
+    // the decompressed cpio archive
+    archive: File,
+}
+
+fn starts_with(file: &mut File, offset: u64, pattern: &str) -> bool {
+    let mut head = Vec::new();
+    let read = file
+        .seek(SeekFrom::Start(offset))
+        .and_then(|_| Read::by_ref(file).take(16).read_to_end(&mut head));
+    match RegexBuilder::new(&format!("^{pattern}"))
+        .unicode(false)
+        .build()
+    {
+        Ok(re) => read.is_ok() && re.is_match(&head),
+        Err(_) => false,
+    }
+}
+
+// load the initramfs at offset, whose size is unknown if found by scanning
+fn load(file: &mut File, offset: u64, size: Option<u64>) -> Result<Initramfs, io::Error> {
+    if starts_with(file, offset, "07070[12]") {
+        let size = match size {
+            Some(size) => size,
+            None => cpio::parse(file, offset)?.size,
+        };
+        let mut archive = crate::extract_payload(file, offset, size)?;
+        cpio::parse(&mut archive, 0)?;
+        return Ok(Initramfs {
+            offset,
+            compression: None,
+            archive,
+        });
+    }
+
+    for (name, pattern, decompress) in crate::COMPRESSIONS {
+        if !starts_with(file, offset, pattern) {
+            continue;
+        }
+        let mut archive = match size {
+            Some(size) => {
+                let mut payload = crate::extract_payload(file, offset, size)?;
+                crate::decompress_from(&mut payload, 0, decompress)?
+            }
+            None => crate::decompress_from(file, offset, decompress)?,
+        };
+        cpio::parse(&mut archive, 0)?;
+        return Ok(Initramfs {
+            offset,
+            compression: Some(name),
+            archive,
+        });
+    }
+
+    Err(io::Error::from(ErrorKind::InvalidData))
+}
+
+fn find_initramfs_elf(file: &mut File) -> Result<Initramfs, io::Error> {
+    let elf = crate::elf::parse(file)?;
+    let symbols = elf.symbols(file)?;
+    let not_found = || io::Error::from(ErrorKind::NotFound);
+
+    let (offset, _) = elf
+        .symbol(&symbols, "__initramfs_start")
+        .and_then(|symbol| elf.symbol_offset(symbol))
+        .ok_or_else(not_found)?;
+
+    // __initramfs_size is a variable holding the size of the initramfs,
+    // which is an unsigned long
+    let (size_offset, _) = elf
+        .symbol(&symbols, "__initramfs_size")
+        .and_then(|symbol| elf.symbol_offset(symbol))
+        .ok_or_else(not_found)?;
+    let mut size = [0u8; 8];
+    let len = if elf.class64 { 8 } else { 4 };
+    file.seek(SeekFrom::Start(size_offset))?;
+    file.read_exact(&mut size[..len])?;
+    let size = if elf.class64 {
+        elf.u64(&size)
+    } else {
+        elf.u32(&size) as u64
+    };
+    if size == 0 {
+        return Err(not_found());
+    }
+
+    load(file, offset, Some(size))
+}
+
+fn scan_initramfs(file: &mut File) -> Result<Initramfs, io::Error> {
+    // init/initramfs.c itself has the magic strings, and there may be
+    // other compressed data, so every match is checked to be a cpio archive
+    let patterns = [r"07070[12]"]
+        .into_iter()
+        .chain(crate::COMPRESSIONS.map(|(_, pattern, _)| pattern));
+    for pattern in patterns {
+        let mut start = 0;
+        while let Ok(offset) = crate::search_regex_from(file, pattern, start) {
+            if let Ok(initramfs) = load(file, offset, None) {
+                return Ok(initramfs);
+            }
+            start = offset + 1;
+        }
+    }
+
+    Err(io::Error::from(ErrorKind::NotFound))
+}
+
+fn list(initramfs: &mut Initramfs) -> Result<(), io::Error> {
+    let archive = cpio::parse(&mut initramfs.archive, 0)?;
+    for entry in &archive.entries {
+        print!(
+            "{} {:>10} {}",
+            cpio::mode_string(entry.mode),
+            entry.size,
+            entry.path
+        );
+        if entry.mode & cpio::S_IFMT == cpio::S_IFLNK {
+            let target = cpio::read_entry(&mut initramfs.archive, entry)?;
+            print!(" -> {}", String::from_utf8_lossy(&target));
+        }
+        println!();
+    }
+    Ok(())
+}
+
+pub fn run(args: ExtractInitramfsArgs) {
+    let mut file = match File::open(&args.image) {
+        Ok(image) => image,
+        Err(err) => {
+            eprintln!("Failed to open file {}: {err}", &args.image);
+            return;
+        }
+    };
+
+    let found = crate::extract_vmlinux(&mut file).and_then(|mut vmlinux| {
+        find_initramfs_elf(&mut vmlinux).or_else(|_| scan_initramfs(&mut vmlinux))
+    });
+    let mut initramfs = match found {
+        Ok(initramfs) => initramfs,
+        Err(_) => {
+            eprintln!(
+                "{}: Cannot find built-in initramfs.",
+                env!("CARGO_BIN_NAME")
+            );
+            return;
+        }
+    };
+    eprintln!(
+        "Found {} initramfs at offset 0x{:x}",
+        initramfs.compression.unwrap_or("uncompressed"),
+        initramfs.offset
+    );
+
+    let result = if args.list {
+        list(&mut initramfs)
+    } else {
+        let archive = &mut initramfs.archive;
+        archive
+            .rewind()
+            .and_then(|_| match &args.output {
+                Some(output) => {
+                    File::create(output).and_then(|mut dst| io::copy(archive, &mut dst))
+                }
+                None => io::copy(archive, &mut io::stdout()),
+            })
+            .map(|_| ())
+    };
+    if let Err(err) = result {
+        eprintln!("Failed to write initramfs: {err}");
+    }
+}
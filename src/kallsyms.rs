@@ -0,0 +1,795 @@
+use crate::elf;
+use argh::FromArgs;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use log::debug;
+use regex::bytes::RegexBuilder;
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, Write},
+};
+
+// the tokens of digits are never replaced as they are used in symbol names,
+// so they are at their own indexes in kallsyms_token_table
+const TOKEN_DIGITS: &str = r"0\x001\x002\x003\x004\x005\x006\x007\x008\x009\x00";
+
+const EM_NONE: u16 = 0;
+const EM_AARCH64: u16 = 183;
+const EM_RISCV: u16 = 243;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHN_ABS: u16 = 0xfff1;
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "kallsyms",
+    description = "recover the symbols of a kernel from its kallsyms tables"
+)]
+pub struct KallsymsArgs {
+    #[argh(positional, description = "kernel image")]
+    image: String,
+
+    #[argh(
+        option,
+        short = 'o',
+        description = "output file, default to standard output"
+    )]
+    output: Option<String>,
+
+    #[argh(
+        switch,
+        description = "write an ELF file with symbol table instead of System.map"
+    )]
+    elf: bool,
+}
+
+pub struct Symbol {
+    pub address: u64,
+    pub kind: char,
+    pub name: String,
+}
+
+pub struct Kallsyms {
+    pub big_endian: bool,
+    pub ptr_size: usize,
+    pub symbols: Vec<Symbol>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.data.get(offset..offset + 2)?;
+        Some(if self.big_endian {
+            BigEndian::read_u16(bytes)
+        } else {
+            LittleEndian::read_u16(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?;
+        Some(if self.big_endian {
+            BigEndian::read_u32(bytes)
+        } else {
+            LittleEndian::read_u32(bytes)
+        })
+    }
+
+    fn uint(&self, offset: usize, size: usize) -> Option<u64> {
+        if size == 4 {
+            return self.u32(offset).map(|value| value as u64);
+        }
+        let bytes = self.data.get(offset..offset + 8)?;
+        Some(if self.big_endian {
+            BigEndian::read_u64(bytes)
+        } else {
+            LittleEndian::read_u64(bytes)
+        })
+    }
+}
+
+fn align_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+// kallsyms_token_table with its start and the end of kallsyms_token_index
+fn token_table(reader: &Reader, digits: usize) -> Option<(usize, usize, Vec<Vec<u8>>)> {
+    let data = reader.data;
+    let mut start = digits;
+    for _ in 0..b'0' {
+        // the NUL of the previous token is right before the start
+        start = data[..start.checked_sub(1)?]
+            .iter()
+            .rposition(|&b| b == 0)?
+            + 1;
+    }
+
+    let mut tokens = Vec::new();
+    let mut offsets = Vec::new();
+    let mut end = start;
+    for _ in 0..256 {
+        let len = data[end..].iter().position(|&b| b == 0)?;
+        offsets.push(end - start);
+        tokens.push(data[end..end + len].to_vec());
+        end += len + 1;
+    }
+
+    // kallsyms_token_index follows with the offsets of tokens
+    for align in [4, 8] {
+        let index = align_up(end, align);
+        if (0..256).all(|i| reader.u16(index + i * 2) == Some(offsets[i] as u16)) {
+            return Some((start, index + 512, tokens));
+        }
+    }
+    None
+}
+
+// kallsyms_markers right before kallsyms_token_table, which are the offsets
+// in kallsyms_names of every 256 symbols, so the first one is always 0
+fn markers(reader: &Reader, token_table: usize, size: usize) -> Option<(usize, Vec<u64>)> {
+    let mut offset = token_table;
+    // skip the alignment padding
+    while token_table - offset < 8 && reader.uint(offset.checked_sub(size)?, size)? == 0 {
+        offset -= size;
+    }
+
+    let mut markers = Vec::new();
+    loop {
+        offset = offset.checked_sub(size)?;
+        let marker = reader.uint(offset, size)?;
+        if markers.last().is_some_and(|&last| marker >= last) {
+            return None;
+        }
+        markers.push(marker);
+        if marker == 0 {
+            break;
+        }
+    }
+    markers.reverse();
+    Some((offset, markers))
+}
+
+// the length of a symbol in kallsyms_names, which takes two bytes if
+// it's longer than 127 since v6.1
+fn name_len(data: &[u8], offset: usize) -> Option<(usize, usize)> {
+    let len = *data.get(offset)? as usize;
+    if len & 0x80 != 0 {
+        let high = *data.get(offset + 1)? as usize;
+        Some(((len & 0x7f) | (high << 7), 2))
+    } else {
+        Some((len, 1))
+    }
+}
+
+fn check_names(data: &[u8], names: usize, num_syms: usize, markers: &[u64]) -> Option<usize> {
+    let mut offset = names;
+    for i in 0..num_syms {
+        if i % 256 == 0 && markers.get(i / 256) != Some(&((offset - names) as u64)) {
+            return None;
+        }
+        let (len, size) = name_len(data, offset)?;
+        offset += size + len;
+    }
+    Some(offset)
+}
+
+// kallsyms_num_syms and kallsyms_names which are right before kallsyms_markers
+fn names(reader: &Reader, markers_start: usize, markers: &[u64]) -> Option<(usize, usize, usize)> {
+    let max = markers.len() * 256;
+    let min = max - 255;
+    let mut offset = markers_start & !3;
+    while offset >= 4 {
+        offset -= 4;
+        let num_syms = reader.u32(offset)? as usize;
+        if !(min..=max).contains(&num_syms) {
+            continue;
+        }
+        // the gap depends on the alignment of the labels, i.e. the pointer size
+        for ptr_size in [4, 8] {
+            let names = offset + ptr_size;
+            if check_names(reader.data, names, num_syms, markers)
+                .is_some_and(|end| end <= markers_start)
+            {
+                return Some((offset, names, ptr_size));
+            }
+        }
+    }
+    None
+}
+
+fn decode_names(data: &[u8], names: usize, num_syms: usize, tokens: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let mut offset = names;
+    let mut decoded = Vec::new();
+    for _ in 0..num_syms {
+        let (len, size) = name_len(data, offset).unwrap_or_default();
+        offset += size;
+        let name = data
+            .get(offset..offset + len)
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|&b| tokens[b as usize].iter().copied())
+            .collect();
+        decoded.push(name);
+        offset += len;
+    }
+    decoded
+}
+
+fn relative_addresses(reader: &Reader, offsets: usize, num_syms: usize, base: u64) -> Vec<u64> {
+    let offsets: Vec<i32> = (0..num_syms)
+        .map(|i| reader.u32(offsets + i * 4).unwrap_or_default() as i32)
+        .collect();
+    if offsets.iter().all(|&offset| offset >= 0) {
+        offsets
+            .iter()
+            .map(|&offset| base.wrapping_add(offset as u64))
+            .collect()
+    } else {
+        // CONFIG_KALLSYMS_ABSOLUTE_PERCPU, the non-negative offsets are absolute
+        // addresses, and the negative ones are relative to the base
+        offsets
+            .iter()
+            .map(|&offset| {
+                if offset >= 0 {
+                    offset as u64
+                } else {
+                    base.wrapping_sub(1).wrapping_sub(offset as i64 as u64)
+                }
+            })
+            .collect()
+    }
+}
+
+fn is_sorted(addresses: &[u64]) -> bool {
+    addresses.windows(2).all(|pair| pair[0] <= pair[1]) && addresses.first() < addresses.last()
+}
+
+fn addresses(
+    reader: &Reader,
+    num_syms_offset: usize,
+    index_end: usize,
+    num_syms: usize,
+    ptr_size: usize,
+) -> Option<Vec<u64>> {
+    let offsets_size = align_up(num_syms * 4, ptr_size);
+
+    // kallsyms_offsets and kallsyms_relative_base are before kallsyms_num_syms
+    // until v6.3, and after kallsyms_token_index since then
+    let before = num_syms_offset
+        .checked_sub(ptr_size + offsets_size)
+        .map(|offsets| (offsets, num_syms_offset - ptr_size));
+    let after = align_up(index_end, ptr_size);
+    let after = Some((after, after + offsets_size));
+    for (offsets, base) in [before, after].into_iter().flatten() {
+        let Some(base) = reader.uint(base, ptr_size) else {
+            continue;
+        };
+        let addresses = relative_addresses(reader, offsets, num_syms, base);
+        if is_sorted(&addresses) {
+            return Some(addresses);
+        }
+    }
+
+    // kallsyms_addresses without CONFIG_KALLSYMS_BASE_RELATIVE
+    let start = num_syms_offset.checked_sub(num_syms * ptr_size)?;
+    let addresses: Vec<u64> = (0..num_syms)
+        .map(|i| {
+            reader
+                .uint(start + i * ptr_size, ptr_size)
+                .unwrap_or_default()
+        })
+        .collect();
+    is_sorted(&addresses).then_some(addresses)
+}
+
+fn parse_at(reader: &Reader, digits: usize) -> Option<Kallsyms> {
+    let (token_start, index_end, tokens) = token_table(reader, digits)?;
+    debug!("Found kallsyms_token_table at 0x{token_start:x}");
+
+    for size in [4, 8] {
+        let Some((markers_start, markers)) = markers(reader, token_start, size) else {
+            continue;
+        };
+        let Some((num_syms_offset, names, ptr_size)) = names(reader, markers_start, &markers)
+        else {
+            continue;
+        };
+        let num_syms = reader.u32(num_syms_offset)? as usize;
+        debug!("Found kallsyms_num_syms {num_syms} at 0x{num_syms_offset:x}");
+
+        let addresses = addresses(reader, num_syms_offset, index_end, num_syms, ptr_size)?;
+        let symbols = decode_names(reader.data, names, num_syms, &tokens)
+            .into_iter()
+            .zip(addresses)
+            .filter(|(name, _)| !name.is_empty())
+            .map(|(name, address)| Symbol {
+                address,
+                // the first character is the type of symbol
+                kind: name[0] as char,
+                name: String::from_utf8_lossy(&name[1..]).into_owned(),
+            })
+            .collect();
+        return Some(Kallsyms {
+            big_endian: reader.big_endian,
+            ptr_size,
+            symbols,
+        });
+    }
+    None
+}
+
+pub fn parse(data: &[u8]) -> Result<Kallsyms, io::Error> {
+    let re = RegexBuilder::new(TOKEN_DIGITS)
+        .unicode(false)
+        .build()
+        .map_err(|_| io::Error::from(ErrorKind::InvalidInput))?;
+
+    for digits in re.find_iter(data) {
+        for big_endian in [false, true] {
+            let reader = Reader { data, big_endian };
+            if let Some(kallsyms) = parse_at(&reader, digits.start()) {
+                return Ok(kallsyms);
+            }
+        }
+    }
+    Err(io::Error::from(ErrorKind::NotFound))
+}
+
+fn system_map(kallsyms: &Kallsyms) -> Vec<u8> {
+    let width = kallsyms.ptr_size * 2;
+    let mut map = Vec::new();
+    for symbol in &kallsyms.symbols {
+        _ = writeln!(
+            map,
+            "{:0width$x} {} {}",
+            symbol.address, symbol.kind, symbol.name
+        );
+    }
+    map
+}
+
+struct ElfWriter {
+    class64: bool,
+    big_endian: bool,
+}
+
+impl ElfWriter {
+    fn u16(&self, out: &mut Vec<u8>, value: u16) {
+        let mut bytes = [0u8; 2];
+        if self.big_endian {
+            BigEndian::write_u16(&mut bytes, value);
+        } else {
+            LittleEndian::write_u16(&mut bytes, value);
+        }
+        out.extend_from_slice(&bytes);
+    }
+
+    fn u32(&self, out: &mut Vec<u8>, value: u32) {
+        let mut bytes = [0u8; 4];
+        if self.big_endian {
+            BigEndian::write_u32(&mut bytes, value);
+        } else {
+            LittleEndian::write_u32(&mut bytes, value);
+        }
+        out.extend_from_slice(&bytes);
+    }
+
+    // Elf64_Addr, Elf64_Off and Elf64_Xword, or their 32-bit ones
+    fn word(&self, out: &mut Vec<u8>, value: u64) {
+        if !self.class64 {
+            return self.u32(out, value as u32);
+        }
+        let mut bytes = [0u8; 8];
+        if self.big_endian {
+            BigEndian::write_u64(&mut bytes, value);
+        } else {
+            LittleEndian::write_u64(&mut bytes, value);
+        }
+        out.extend_from_slice(&bytes);
+    }
+
+    fn patch(&self, out: &mut [u8], offset: usize, value: u64, size: usize) {
+        let mut bytes = Vec::new();
+        match size {
+            2 => self.u16(&mut bytes, value as u16),
+            4 => self.u32(&mut bytes, value as u32),
+            _ => self.word(&mut bytes, value),
+        }
+        out[offset..offset + bytes.len()].copy_from_slice(&bytes);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn section(
+        &self,
+        out: &mut Vec<u8>,
+        name: u32,
+        sh_type: u32,
+        flags: u64,
+        addr: u64,
+        offset: u64,
+        size: u64,
+        link: u32,
+        info: u32,
+        entsize: u64,
+    ) {
+        self.u32(out, name);
+        self.u32(out, sh_type);
+        self.word(out, flags);
+        self.word(out, addr);
+        self.word(out, offset);
+        self.word(out, size);
+        self.u32(out, link);
+        self.u32(out, info);
+        // sh_addralign
+        self.word(out, if sh_type == SHT_SYMTAB { 8 } else { 1 });
+        self.word(out, entsize);
+    }
+
+    fn symbol(&self, out: &mut Vec<u8>, name: u32, value: u64, info: u8, shndx: u16) {
+        self.u32(out, name);
+        if self.class64 {
+            out.extend_from_slice(&[info, 0]);
+            self.u16(out, shndx);
+            self.word(out, value);
+            self.word(out, 0);
+        } else {
+            self.word(out, value);
+            self.word(out, 0);
+            out.extend_from_slice(&[info, 0]);
+            self.u16(out, shndx);
+        }
+    }
+
+    // the offsets of e_shoff, e_shnum and e_shstrndx
+    fn header_offsets(&self) -> (usize, usize, usize) {
+        if self.class64 {
+            (0x28, 0x3c, 0x3e)
+        } else {
+            (0x20, 0x30, 0x32)
+        }
+    }
+}
+
+// st_info of a symbol by its type in kallsyms, i.e. nm(1) style
+fn symbol_info(kind: char) -> u8 {
+    const STB_LOCAL: u8 = 0;
+    const STB_GLOBAL: u8 = 1;
+    const STB_WEAK: u8 = 2;
+    const STT_NOTYPE: u8 = 0;
+    const STT_OBJECT: u8 = 1;
+    const STT_FUNC: u8 = 2;
+
+    let bind = match kind {
+        'w' | 'W' | 'v' | 'V' => STB_WEAK,
+        _ if kind.is_ascii_uppercase() => STB_GLOBAL,
+        _ => STB_LOCAL,
+    };
+    let symbol_type = match kind.to_ascii_lowercase() {
+        't' | 'w' => STT_FUNC,
+        'd' | 'b' | 'r' | 'v' => STT_OBJECT,
+        _ => STT_NOTYPE,
+    };
+    (bind << 4) | symbol_type
+}
+
+// the sections to place the symbols in, as (address, size, index)
+type Ranges = Vec<(u64, u64, u16)>;
+
+// wrap a raw kernel image into an ELF file with the image as .text
+fn wrap_raw(
+    data: &[u8],
+    kallsyms: &Kallsyms,
+    writer: &ElfWriter,
+) -> (Vec<u8>, Vec<u8>, Vec<u8>, Ranges) {
+    // the kernel image begins with _text
+    let base = kallsyms
+        .symbols
+        .iter()
+        .find(|symbol| symbol.name == "_text")
+        .or_else(|| kallsyms.symbols.first())
+        .map(|symbol| symbol.address)
+        .unwrap_or_default();
+    let machine = if data.get(0x38..0x3c) == Some(b"ARM\x64") {
+        EM_AARCH64
+    } else if data.get(0x38..0x3c) == Some(b"RSC\x05") {
+        EM_RISCV
+    } else {
+        EM_NONE
+    };
+
+    let (ehsize, phentsize, shentsize) = if writer.class64 {
+        (64, 56, 64)
+    } else {
+        (52, 32, 40)
+    };
+    let data_offset = 0x1000 + (base & 0xfff);
+
+    let mut out = vec![0x7f, b'E', b'L', b'F'];
+    out.extend_from_slice(&[
+        if writer.class64 { 2 } else { 1 },
+        if writer.big_endian { 2 } else { 1 },
+        1,
+    ]);
+    out.resize(16, 0);
+    // ET_EXEC
+    writer.u16(&mut out, 2);
+    writer.u16(&mut out, machine);
+    writer.u32(&mut out, 1);
+    writer.word(&mut out, base);
+    writer.word(&mut out, ehsize);
+    // e_shoff is patched later
+    writer.word(&mut out, 0);
+    writer.u32(&mut out, 0);
+    for value in [ehsize, phentsize, 1, shentsize, 0, 0] {
+        writer.u16(&mut out, value as u16);
+    }
+
+    // a single PT_LOAD segment of the whole image, readable, writable and executable
+    writer.u32(&mut out, 1);
+    if writer.class64 {
+        writer.u32(&mut out, 7);
+    }
+    writer.word(&mut out, data_offset);
+    writer.word(&mut out, base);
+    writer.word(&mut out, base);
+    writer.word(&mut out, data.len() as u64);
+    writer.word(&mut out, data.len() as u64);
+    if !writer.class64 {
+        writer.u32(&mut out, 7);
+    }
+    writer.word(&mut out, 0x1000);
+
+    out.resize(data_offset as usize, 0);
+    out.extend_from_slice(data);
+
+    let shstrtab = b"\0.text\0".to_vec();
+    let mut headers = Vec::new();
+    writer.section(&mut headers, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+    writer.section(
+        &mut headers,
+        1,
+        SHT_PROGBITS,
+        SHF_ALLOC | SHF_EXECINSTR,
+        base,
+        data_offset,
+        data.len() as u64,
+        0,
+        0,
+        0,
+    );
+    (out, headers, shstrtab, vec![(base, data.len() as u64, 1)])
+}
+
+pub fn to_elf(vmlinux: &mut File, data: &[u8], kallsyms: &Kallsyms) -> Result<Vec<u8>, io::Error> {
+    let parsed = elf::parse(vmlinux)
+        .ok()
+        .filter(|elf| !elf.sections.is_empty());
+    let writer = ElfWriter {
+        class64: parsed
+            .as_ref()
+            .map_or(kallsyms.ptr_size == 8, |elf| elf.class64),
+        big_endian: parsed
+            .as_ref()
+            .map_or(kallsyms.big_endian, |elf| elf.big_endian),
+    };
+
+    // keep everything of an ELF file and append the symbol table to it,
+    // otherwise wrap the raw image into a new ELF file
+    let (mut out, mut headers, mut shstrtab, ranges) = match &parsed {
+        Some(parsed) => {
+            let (shoff, _, shstrndx) = writer.header_offsets();
+            let reader = Reader {
+                data,
+                big_endian: writer.big_endian,
+            };
+            let shoff = reader.uint(shoff, if writer.class64 { 8 } else { 4 });
+            let shstrndx = reader.u16(shstrndx).unwrap_or_default() as usize;
+            let shentsize = if writer.class64 { 64 } else { 40 };
+            let table = shoff
+                .map(|shoff| shoff as usize)
+                .and_then(|shoff| data.get(shoff..shoff + shentsize * parsed.sections.len()))
+                .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?;
+            let shstrtab = parsed
+                .sections
+                .get(shstrndx)
+                .map(|section| parsed.read_section(vmlinux, section))
+                .transpose()?
+                .unwrap_or_else(|| vec![0]);
+            let ranges = parsed
+                .sections
+                .iter()
+                .enumerate()
+                .filter(|(_, section)| section.addr != 0)
+                .map(|(index, section)| (section.addr, section.size, index as u16))
+                .collect();
+            (data.to_vec(), table.to_vec(), shstrtab, ranges)
+        }
+        None => wrap_raw(data, kallsyms, &writer),
+    };
+    let shentsize = if writer.class64 { 64 } else { 40 };
+    let symtab_index = headers.len() / shentsize;
+
+    // local symbols must precede the global ones
+    let mut symbols: Vec<&Symbol> = kallsyms.symbols.iter().collect();
+    symbols.sort_by_key(|symbol| symbol_info(symbol.kind) >> 4 != 0);
+    let first_global = 1 + symbols
+        .iter()
+        .take_while(|symbol| symbol_info(symbol.kind) >> 4 == 0)
+        .count();
+
+    let mut symtab = Vec::new();
+    let mut strtab = vec![0u8];
+    writer.symbol(&mut symtab, 0, 0, 0, 0);
+    for symbol in symbols {
+        let shndx = ranges
+            .iter()
+            .find(|(addr, size, _)| (*addr..addr + size.max(&1)).contains(&symbol.address))
+            .map_or(SHN_ABS, |(_, _, index)| *index);
+        writer.symbol(
+            &mut symtab,
+            strtab.len() as u32,
+            symbol.address,
+            symbol_info(symbol.kind),
+            shndx,
+        );
+        strtab.extend_from_slice(symbol.name.as_bytes());
+        strtab.push(0);
+    }
+
+    let names = [".symtab", ".strtab", ".shstrtab"].map(|name| {
+        let offset = shstrtab.len() as u32;
+        shstrtab.extend_from_slice(name.as_bytes());
+        shstrtab.push(0);
+        offset
+    });
+
+    out.resize(align_up(out.len(), 8), 0);
+    let symtab_offset = out.len() as u64;
+    out.extend_from_slice(&symtab);
+    let strtab_offset = out.len() as u64;
+    out.extend_from_slice(&strtab);
+    let shstrtab_offset = out.len() as u64;
+    out.extend_from_slice(&shstrtab);
+
+    let entsize = if writer.class64 { 24 } else { 16 };
+    writer.section(
+        &mut headers,
+        names[0],
+        SHT_SYMTAB,
+        0,
+        0,
+        symtab_offset,
+        symtab.len() as u64,
+        symtab_index as u32 + 1,
+        first_global as u32,
+        entsize,
+    );
+    writer.section(
+        &mut headers,
+        names[1],
+        SHT_STRTAB,
+        0,
+        0,
+        strtab_offset,
+        strtab.len() as u64,
+        0,
+        0,
+        0,
+    );
+    writer.section(
+        &mut headers,
+        names[2],
+        SHT_STRTAB,
+        0,
+        0,
+        shstrtab_offset,
+        shstrtab.len() as u64,
+        0,
+        0,
+        0,
+    );
+
+    out.resize(align_up(out.len(), 8), 0);
+    let shoff = out.len() as u64;
+    out.extend_from_slice(&headers);
+
+    let (shoff_offset, shnum_offset, shstrndx_offset) = writer.header_offsets();
+    let shnum = headers.len() / shentsize;
+    writer.patch(&mut out, shoff_offset, shoff, 8);
+    writer.patch(&mut out, shnum_offset, shnum as u64, 2);
+    writer.patch(&mut out, shstrndx_offset, shnum as u64 - 1, 2);
+    Ok(out)
+}
+
+pub fn run(args: KallsymsArgs) {
+    let mut file = match File::open(&args.image) {
+        Ok(image) => image,
+        Err(err) => {
+            eprintln!("Failed to open file {}: {err}", &args.image);
+            return;
+        }
+    };
+
+    let mut data = Vec::new();
+    let found = crate::extract_vmlinux(&mut file).and_then(|mut vmlinux| {
+        vmlinux.rewind()?;
+        vmlinux.read_to_end(&mut data)?;
+        parse(&data).map(|kallsyms| (vmlinux, kallsyms))
+    });
+    let (mut vmlinux, kallsyms) = match found {
+        Ok(found) => found,
+        Err(_) => {
+            eprintln!(
+                "{}: Cannot find kallsyms. Please confirm kernel compiled with CONFIG_KALLSYMS.",
+                env!("CARGO_BIN_NAME")
+            );
+            return;
+        }
+    };
+    eprintln!("Found {} symbols", kallsyms.symbols.len());
+
+    let result = if args.elf {
+        to_elf(&mut vmlinux, &data, &kallsyms)
+    } else {
+        Ok(system_map(&kallsyms))
+    }
+    .and_then(|out| match &args.output {
+        Some(output) => File::create(output).and_then(|mut dst| dst.write_all(&out)),
+        None => io::stdout().write_all(&out),
+    });
+    if let Err(err) = result {
+        eprintln!("Failed to write symbols: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_len() {
+        assert_eq!(name_len(&[0x05], 0), Some((5, 1)));
+        // the long names since v6.1
+        assert_eq!(name_len(&[0x81, 0x02], 0), Some((0x101, 2)));
+        assert_eq!(name_len(&[0x81], 0), None);
+    }
+
+    #[test]
+    fn test_symbol_info() {
+        assert_eq!(symbol_info('T'), 0x12);
+        assert_eq!(symbol_info('t'), 0x02);
+        assert_eq!(symbol_info('D'), 0x11);
+        assert_eq!(symbol_info('b'), 0x01);
+        assert_eq!(symbol_info('W'), 0x22);
+        assert_eq!(symbol_info('A'), 0x10);
+    }
+
+    #[test]
+    fn test_wrap_raw_machine() {
+        let kallsyms = Kallsyms {
+            big_endian: false,
+            ptr_size: 8,
+            symbols: Vec::new(),
+        };
+        let writer = ElfWriter {
+            class64: true,
+            big_endian: false,
+        };
+        let mut image = vec![0u8; 0x40];
+        image[0x30..0x3c].copy_from_slice(b"RISCV\0\0\0RSC\x05");
+        let (out, ..) = wrap_raw(&image, &kallsyms, &writer);
+        assert_eq!(out[18..20], EM_RISCV.to_le_bytes());
+
+        image[0x30..0x3c].copy_from_slice(b"\0\0\0\0\0\0\0\0ARM\x64");
+        let (out, ..) = wrap_raw(&image, &kallsyms, &writer);
+        assert_eq!(out[18..20], EM_AARCH64.to_le_bytes());
+    }
+}
@@ -0,0 +1,151 @@
+use argh::FromArgs;
+use flate2::Crc;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+const XZ_HEADER_SIZE: usize = 12;
+const XZ_FOOTER_SIZE: usize = 12;
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "extract-headers",
+    description = "extract the kernel headers archive embedded by CONFIG_IKHEADERS"
+)]
+pub struct ExtractHeadersArgs {
+    #[argh(positional, description = "kernel image or kheaders.ko")]
+    image: String,
+
+    #[argh(
+        option,
+        short = 'o',
+        description = "output file of kheaders_data.tar.xz, default to standard output"
+    )]
+    output: Option<String>,
+
+    #[argh(
+        option,
+        short = 'd',
+        description = "unpack the headers into a directory instead"
+    )]
+    directory: Option<String>,
+}
+
+fn find_archive_elf(file: &mut File) -> Result<(u64, u64), io::Error> {
+    let elf = crate::elf::parse(file)?;
+    let symbols = elf.symbols(file)?;
+    elf.symbol_range(&symbols, "kernel_headers_data", "kernel_headers_data_end")
+        .ok_or_else(|| io::Error::from(ErrorKind::NotFound))
+}
+
+// the size of a xz stream, found by the stream footer which ends with "YZ"
+// at a multiple of four bytes, and whose CRC32 and flags match the header
+fn xz_stream_size(file: &mut File, offset: u64) -> Result<u64, io::Error> {
+    let mut buff = BufReader::new(Read::by_ref(file));
+    buff.seek(SeekFrom::Start(offset))?;
+
+    let mut header = [0u8; XZ_HEADER_SIZE];
+    buff.read_exact(&mut header)?;
+    let flags = &header[6..8];
+
+    let mut window = [0u8; XZ_FOOTER_SIZE];
+    let mut size = XZ_HEADER_SIZE as u64;
+    loop {
+        window.copy_within(4.., 0);
+        buff.read_exact(&mut window[XZ_FOOTER_SIZE - 4..])?;
+        size += 4;
+
+        if &window[10..12] == b"YZ" && &window[8..10] == flags {
+            let mut crc = Crc::new();
+            crc.update(&window[4..10]);
+            if crc.sum().to_le_bytes() == window[0..4] {
+                return Ok(size);
+            }
+        }
+    }
+}
+
+fn unxz_archive(file: &mut File, offset: u64, size: u64) -> Result<File, io::Error> {
+    let mut payload = crate::extract_payload(file, offset, size)?;
+    payload.rewind()?;
+    let mut dst = tempfile::tempfile()?;
+    crate::unxz(&payload, &mut dst)?;
+
+    let mut header = [0u8; 512];
+    dst.rewind()?;
+    dst.read_exact(&mut header)?;
+    if !crate::tar::is_tar(&header) {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+    Ok(dst)
+}
+
+fn scan_archive(file: &mut File) -> Result<(u64, u64), io::Error> {
+    // there may be other xz streams, e.g. compressed firmware or initramfs,
+    // so check if each of them is a tarball
+    let mut start = 0;
+    loop {
+        let offset = crate::search_regex_from(file, crate::MAGIC_NUMBER_XZ, start)?;
+        if let Ok(size) = xz_stream_size(file, offset) {
+            if unxz_archive(file, offset, size).is_ok() {
+                return Ok((offset, size));
+            }
+        }
+        start = offset + 1;
+    }
+}
+
+fn find_archive(file: &mut File) -> Result<(u64, u64), io::Error> {
+    find_archive_elf(file).or_else(|_| scan_archive(file))
+}
+
+pub fn run(args: ExtractHeadersArgs) {
+    let mut file = match File::open(&args.image) {
+        Ok(image) => image,
+        Err(err) => {
+            eprintln!("Failed to open file {}: {err}", &args.image);
+            return;
+        }
+    };
+
+    // kheaders.ko is an ELF file itself, which is recognized as vmlinux
+    let found = crate::extract_vmlinux(&mut file)
+        .and_then(|mut vmlinux| find_archive(&mut vmlinux).map(|range| (vmlinux, range)));
+    let (mut vmlinux, (offset, size)) = match found {
+        Ok(found) => found,
+        Err(_) => {
+            eprintln!(
+                "{}: Cannot find kernel headers. Please confirm kernel compiled with CONFIG_IKHEADERS.",
+                env!("CARGO_BIN_NAME")
+            );
+            return;
+        }
+    };
+
+    if let Some(dir) = &args.directory {
+        let result = unxz_archive(&mut vmlinux, offset, size).and_then(|mut tar| {
+            fs::create_dir_all(dir)?;
+            crate::tar::unpack(&mut tar, Path::new(dir))
+        });
+        if let Err(err) = result {
+            eprintln!("Failed to unpack kernel headers into {dir}: {err}");
+        }
+        return;
+    }
+
+    let result = vmlinux.seek(SeekFrom::Start(offset)).and_then(|_| {
+        let mut archive = Read::by_ref(&mut vmlinux).take(size);
+        match &args.output {
+            Some(output) => {
+                File::create(output).and_then(|mut dst| io::copy(&mut archive, &mut dst))
+            }
+            None => io::copy(&mut archive, &mut io::stdout()),
+        }
+    });
+    if let Err(err) = result {
+        eprintln!("Failed to write kernel headers: {err}");
+    }
+}
@@ -0,0 +1,74 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use std::{env, str::FromStr, sync::OnceLock, time::Instant};
+
+struct Logger {
+    start: Instant,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "[{:9.3}s {:5}] {}",
+                self.start.elapsed().as_secs_f64(),
+                record.level(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+// parse RUST_LOG like "debug" or "ikconfig=trace", the directives
+// for other crates are ignored
+fn parse_level(directives: &str) -> Option<LevelFilter> {
+    directives
+        .split(',')
+        .filter_map(|directive| match directive.split_once('=') {
+            Some((env!("CARGO_CRATE_NAME"), level)) => Some(level),
+            Some(_) => None,
+            None => Some(directive),
+        })
+        .filter_map(|level| LevelFilter::from_str(level.trim()).ok())
+        .next_back()
+}
+
+// -v for debug and -v -v for trace, otherwise the level in RUST_LOG
+pub fn init(verbose: u8) {
+    let level = match verbose {
+        0 => env::var("RUST_LOG")
+            .ok()
+            .and_then(|directives| parse_level(&directives))
+            .unwrap_or(LevelFilter::Warn),
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+
+    let logger = LOGGER.get_or_init(|| Logger {
+        start: Instant::now(),
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("debug"), Some(LevelFilter::Debug));
+        assert_eq!(parse_level("ikconfig=trace"), Some(LevelFilter::Trace));
+        assert_eq!(parse_level("warn,ikconfig=info"), Some(LevelFilter::Info));
+        assert_eq!(parse_level("regex=trace"), None);
+        assert_eq!(parse_level("verbose"), None);
+    }
+}
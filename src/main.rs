@@ -2,21 +2,61 @@ use argh::FromArgs;
 use byteorder::{BigEndian, ReadBytesExt};
 use bzip2::bufread::BzDecoder;
 use flate2::bufread::GzDecoder;
+use log::{debug, trace};
 use lz4_flex::frame::FrameDecoder as Lz4Decoder;
 use lzma::LzmaReader;
 use regex::bytes::RegexBuilder;
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
     mem::size_of_val,
+    path::{Path, PathBuf},
+    time::Instant,
 };
 use zstd::stream::read::Decoder as ZstdDecoder;
 
+mod android;
+mod archive;
+mod btf;
+mod certs;
+mod chromeos;
+mod cpio;
+mod der;
+mod digest;
+mod disk;
+mod dtb;
+mod elf;
+mod erofs;
+mod ext4;
+mod fat;
+mod fdt;
+mod fit;
+mod infer;
+mod info;
+mod initramfs;
+mod kallsyms;
+mod kheaders;
+mod logger;
+mod modinfo;
+mod package;
+mod rsa;
+mod running;
+mod squashfs;
+mod tar;
+mod uimage;
+mod vmcore;
+mod vmlinux;
+mod zimage;
+
 // search pattern:
 // IKCFG_ST is the start flag of in-kernel config
 // "1f 8b 08" is the first 3 bytes of gzip header
 const IKCFG_ST_FLAG_STR: &str = r"IKCFG_ST\x1f\x8b\x08";
 
+// the plain config files, e.g. /boot/config-* installed by distributions,
+// begin with the header written by kconfig
+const CONFIG_HEADER: &[u8] = b"#\n# Automatically generated file; DO NOT EDIT.\n";
+
 // search patterns for compressed header
 const MAGIC_NUMBER_GZIP: &str = r"\x1f\x8b\x08";
 const MAGIC_NUMBER_XZ: &str = r"\xfd7zXZ\x00";
@@ -27,6 +67,10 @@ const MAGIC_NUMBER_LZ4: &str = r"\x02\x21\x4c\x18";
 const MAGIC_NUMBER_ZSTD: &str = r"\x28\xb5\x2f\xfd";
 
 fn search_regex(file: &File, pattern: &str) -> Result<u64, io::Error> {
+    search_regex_from(file, pattern, 0)
+}
+
+fn search_regex_from(file: &File, pattern: &str, start: u64) -> Result<u64, io::Error> {
     let mut buff = BufReader::new(file);
     let mut bytes = vec![0; 1024];
     // Disable Unicode (\u flag) to search arbitrary (non-UTF-8) bytes
@@ -36,7 +80,7 @@ fn search_regex(file: &File, pattern: &str) -> Result<u64, io::Error> {
         return Err(io::Error::from(ErrorKind::InvalidInput));
     };
 
-    buff.rewind()?;
+    buff.seek(SeekFrom::Start(start))?;
     loop {
         match buff.read(&mut bytes) {
             Ok(read) => {
@@ -51,7 +95,9 @@ fn search_regex(file: &File, pattern: &str) -> Result<u64, io::Error> {
                     continue;
                 }
                 if let Some(m) = re.find(&bytes[..read]) {
-                    return Ok(buff.stream_position().unwrap() - (read - m.start()) as u64);
+                    let offset = buff.stream_position().unwrap() - (read - m.start()) as u64;
+                    debug!("Found '{pattern}' at 0x{offset:x}");
+                    return Ok(offset);
                 } else {
                     // overlap the search around the chunk boundaries
                     // in case the pattern locates across the boundary
@@ -64,20 +110,40 @@ fn search_regex(file: &File, pattern: &str) -> Result<u64, io::Error> {
         }
     }
 
+    trace!("Not found '{pattern}' from 0x{start:x}");
     Err(io::Error::from(ErrorKind::NotFound))
 }
 
-fn dump_config_gzip(file: &mut File, offset: u64) -> Result<(), io::Error> {
+fn read_config_gzip(file: &mut File, offset: u64, size: u64) -> Result<Vec<u8>, io::Error> {
     // seek to offset before passing into GzDecoder
     file.seek(SeekFrom::Start(offset))?;
 
-    // write the decompressed config text to stdout
-    io::copy(&mut GzDecoder::new(BufReader::new(file)), &mut io::stdout()).map(|_| ())
+    // decompress the config text into memory
+    let mut config = Vec::new();
+    GzDecoder::new(BufReader::new(Read::by_ref(file).take(size)))
+        .read_to_end(&mut config)
+        .inspect_err(|err| debug!("Failed to decompress config at 0x{offset:x}: {err}"))?;
+    debug!("Read {} bytes of config at 0x{offset:x}", config.len());
+    Ok(config)
 }
 
-fn dump_config(file: &mut File) -> Result<(), io::Error> {
-    search_regex(file, IKCFG_ST_FLAG_STR)
-        .and_then(|offset| dump_config_gzip(file, offset + "IKCFG_ST".len() as u64))
+fn read_config_elf(file: &mut File) -> Result<Vec<u8>, io::Error> {
+    let elf = elf::parse(file)?;
+    let symbols = elf.symbols(file)?;
+    let (offset, size) = elf
+        .symbol_range(&symbols, "kernel_config_data", "kernel_config_data_end")
+        .ok_or_else(|| io::Error::from(ErrorKind::NotFound))?;
+    debug!("Found kernel_config_data symbols at 0x{offset:x}, {size} bytes");
+    read_config_gzip(file, offset, size)
+}
+
+fn read_config(file: &mut File) -> Result<Vec<u8>, io::Error> {
+    // extract exactly the config data between the symbols if available,
+    // otherwise search for the start flag in the whole file
+    read_config_elf(file).or_else(|_| {
+        search_regex(file, IKCFG_ST_FLAG_STR)
+            .and_then(|offset| read_config_gzip(file, offset + "IKCFG_ST".len() as u64, u64::MAX))
+    })
 }
 
 fn gunzip(src: &File, dst: &mut File) -> Result<(), io::Error> {
@@ -279,52 +345,482 @@ fn unzstd(src: &File, dst: &mut File) -> Result<(), io::Error> {
     Ok(())
 }
 
-fn try_decompress<F>(file: &mut File, pattern: &str, decompress: F) -> Result<(), io::Error>
+type Decompress = fn(&File, &mut File) -> Result<(), io::Error>;
+
+fn decompress_data(data: &[u8], decompress: Option<Decompress>) -> Result<File, io::Error> {
+    let mut src = tempfile::tempfile()?;
+    src.write_all(data)?;
+    src.rewind()?;
+
+    match decompress {
+        Some(decompress) => {
+            let mut dst = tempfile::tempfile()?;
+            decompress(&src, &mut dst)?;
+            Ok(dst)
+        }
+        None => Ok(src),
+    }
+}
+
+// the compression formats supported by kernel, with their magic numbers
+const COMPRESSIONS: [(&str, &str, Decompress); 7] = [
+    ("gzip", MAGIC_NUMBER_GZIP, gunzip),
+    ("xz", MAGIC_NUMBER_XZ, unxz),
+    ("bzip2", MAGIC_NUMBER_BZIP2, bunzip2),
+    ("lzma", MAGIC_NUMBER_LZMA, unlzma),
+    ("lzo", MAGIC_NUMBER_LZO, unlzo),
+    ("lz4", MAGIC_NUMBER_LZ4, unlz4),
+    ("zstd", MAGIC_NUMBER_ZSTD, unzstd),
+];
+
+fn decompress_from<F>(file: &mut File, offset: u64, decompress: F) -> Result<File, io::Error>
 where
     F: Fn(&File, &mut File) -> Result<(), io::Error>,
 {
-    search_regex(file, pattern).and_then(|offset| {
-        // decompress file[offset..] to tempfile to get raw vmlinux
-        file.seek(SeekFrom::Start(offset))?;
-        let mut dst = tempfile::tempfile()?;
-        decompress(file, &mut dst)?;
-
-        // search config_data.gz in raw vmlinux and dump it
-        dump_config(&mut dst)
-    })
+    // decompress file[offset..] to tempfile
+    file.seek(SeekFrom::Start(offset))?;
+    let mut dst = tempfile::tempfile()?;
+    decompress(file, &mut dst)?;
+    Ok(dst)
+}
+
+fn decompress_at(
+    file: &mut File,
+    name: &str,
+    pattern: &str,
+    decompress: Decompress,
+) -> Result<(u64, File), io::Error> {
+    let offset = search_regex(file, pattern)?;
+    debug!("Trying {name} decompression at 0x{offset:x}");
+    let instant = Instant::now();
+    match decompress_from(file, offset, decompress) {
+        Ok(dst) => {
+            debug!(
+                "Decompressed {} bytes of {name} in {:.3}s",
+                dst.metadata()?.len(),
+                instant.elapsed().as_secs_f64()
+            );
+            Ok((offset, dst))
+        }
+        Err(err) => {
+            debug!("Failed to decompress {name} at 0x{offset:x}: {err}");
+            Err(err)
+        }
+    }
+}
+
+fn try_decompress(
+    file: &mut File,
+    name: &str,
+    pattern: &str,
+    decompress: Decompress,
+) -> Result<Vec<u8>, io::Error> {
+    // search config_data.gz in raw vmlinux and read it
+    decompress_at(file, name, pattern, decompress).and_then(|(_, mut dst)| read_config(&mut dst))
+}
+
+fn try_decompress_vmlinux(
+    file: &mut File,
+    name: &str,
+    pattern: &str,
+    decompress: Decompress,
+) -> Result<(u64, File), io::Error> {
+    let (offset, mut dst) = decompress_at(file, name, pattern, decompress)?;
+    if vmlinux::is_vmlinux(&mut dst) {
+        Ok((offset, dst))
+    } else {
+        debug!("The decompressed {name} data isn't a kernel");
+        Err(io::Error::from(ErrorKind::InvalidData))
+    }
+}
+
+fn extract_payload(file: &mut File, offset: u64, size: u64) -> Result<File, io::Error> {
+    // copy file[offset..offset + size] to tempfile
+    file.seek(SeekFrom::Start(offset))?;
+    let mut dst = tempfile::tempfile()?;
+    io::copy(&mut Read::by_ref(file).take(size), &mut dst)?;
+    Ok(dst)
+}
+
+fn read_config_plain(file: &mut File) -> Result<Vec<u8>, io::Error> {
+    let mut config = vec![0u8; CONFIG_HEADER.len()];
+    file.rewind()?;
+    file.read_exact(&mut config)?;
+    if config != CONFIG_HEADER {
+        return Err(io::Error::from(ErrorKind::NotFound));
+    }
+    file.read_to_end(&mut config)?;
+    Ok(config)
+}
+
+fn scan_config(file: &mut File) -> Result<Vec<u8>, io::Error> {
+    if let Ok(config) = read_config(file) {
+        return Ok(config);
+    }
+
+    for (name, pattern, decompress) in COMPRESSIONS {
+        if let Ok(config) = try_decompress(file, name, pattern, decompress) {
+            return Ok(config);
+        }
+    }
+    read_config_plain(file)
+}
+
+// the decompressed kernel, with its compression and offset if compressed
+type Vmlinux = (Option<(&'static str, u64)>, File);
+
+fn scan_vmlinux(file: &mut File) -> Result<Vmlinux, io::Error> {
+    if vmlinux::is_vmlinux(file) {
+        return Ok((None, file.try_clone()?));
+    }
+
+    for (name, pattern, decompress) in COMPRESSIONS {
+        if let Ok((offset, vmlinux)) = try_decompress_vmlinux(file, name, pattern, decompress) {
+            return Ok((Some((name, offset)), vmlinux));
+        }
+    }
+    Err(io::Error::from(ErrorKind::NotFound))
+}
+
+fn decompress_file(file: &mut File) -> Result<(&'static str, File), io::Error> {
+    // only the formats used to compress kernel modules and tarballs are
+    // checked as the whole file compressed, e.g. configs.ko.xz or .tar.bz2
+    let mut magic = [0u8; 6];
+    file.rewind()?;
+    file.read_exact(&mut magic)?;
+    let (name, decompress): (_, Decompress) = if magic.starts_with(b"\x1f\x8b\x08") {
+        ("gzip", gunzip)
+    } else if magic.starts_with(b"\xfd7zXZ\x00") {
+        ("xz", unxz)
+    } else if magic.starts_with(b"\x28\xb5\x2f\xfd") {
+        ("zstd", unzstd)
+    } else if magic.starts_with(b"BZh") && (b'1'..=b'9').contains(&magic[3]) {
+        ("bzip2", bunzip2)
+    } else {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    };
+
+    file.rewind()?;
+    let mut dst = tempfile::tempfile()?;
+    decompress(file, &mut dst)?;
+    Ok((name, dst))
+}
+
+// unwrap the kernels from container formats and then scan them,
+// the containers unwrapped are recorded in the trace, and the results are
+// labeled by the members of containers with multiple kernels, if any
+fn unwrap_image<T>(
+    file: &mut File,
+    scan: fn(&mut File) -> Result<T, io::Error>,
+    trace: &mut Vec<String>,
+) -> Result<Vec<(String, T)>, io::Error> {
+    if let Ok(uimage) = uimage::parse(file) {
+        eprintln!("{uimage}");
+        trace.push(String::from("uImage"));
+        let mut payload = uimage.payload(file)?;
+        return unwrap_image(&mut payload, scan, trace);
+    }
+
+    if let Ok(image) = android::parse(file) {
+        eprintln!("{image}");
+        trace.push(String::from("Android boot image"));
+        let mut payload = extract_payload(file, image.payload_offset, image.payload_size)?;
+        return unwrap_image(&mut payload, scan, trace);
+    }
+
+    if let Ok(vblock) = chromeos::parse(file) {
+        eprintln!("{vblock}");
+        trace.push(String::from("ChromeOS kernel partition"));
+        let mut payload = extract_payload(file, vblock.body_offset, vblock.body_size)?;
+        return unwrap_image(&mut payload, scan, trace);
+    }
+
+    // the memory captured in crash dumps is scanned in overlapping windows
+    if let Ok(vmcore) = vmcore::parse(file) {
+        eprintln!("{vmcore}");
+        trace.push(String::from(vmcore.format));
+        return vmcore
+            .scan(file, scan)
+            .map(|scanned| vec![(String::new(), scanned)]);
+    }
+
+    if let Ok(kernels) = fit::parse(file) {
+        trace.push(String::from("FIT image"));
+        let kernels = kernels
+            .into_iter()
+            .map(|kernel| (kernel.to_string(), kernel.payload()));
+        return unwrap_kernels(kernels, scan, trace);
+    }
+
+    if let Ok(mut package) = package::parse(file) {
+        eprintln!("{package}");
+        trace.push(String::from(package.format));
+        let kernels = package
+            .kernels()?
+            .into_iter()
+            .map(|(path, kernel)| (format!("Package file: {path}"), kernel));
+        return unwrap_kernels(kernels, scan, trace);
+    }
+
+    // only the archives with the members looking like kernels are unwrapped,
+    // otherwise they are scanned as a whole
+    if let Ok(archive) = archive::parse(file) {
+        let kernels = archive.kernels(file, archive::is_kernel_name);
+        if !kernels.is_empty() {
+            trace.push(String::from(archive.format));
+            let kernels = kernels
+                .into_iter()
+                .map(|(path, kernel)| (format!("Archive member: {path}"), kernel));
+            return unwrap_kernels(kernels, scan, trace);
+        }
+    }
+
+    // disk images are unwrapped only if any kernel or config is found in
+    // their filesystems
+    if let Ok(disk) = disk::parse(file) {
+        let kernels = disk.kernels(file);
+        if !kernels.is_empty() {
+            eprintln!("{disk}");
+            trace.push(String::from(disk.format));
+            let kernels = kernels
+                .into_iter()
+                .map(|(path, kernel)| (format!("Disk file: {path}"), kernel));
+            return unwrap_kernels(kernels, scan, trace);
+        }
+    }
+
+    if let Ok((name, mut decompressed)) = decompress_file(file) {
+        debug!(
+            "Decompressed the whole file of {name} to {} bytes",
+            decompressed.metadata()?.len()
+        );
+        trace.push(format!("{name} compressed file"));
+        return unwrap_image(&mut decompressed, scan, trace);
+    }
+
+    // restrict the search within the bounds declared by zImage header,
+    // as appended data (e.g. device tree blob) may confuse the search
+    if let Ok(zimage) = zimage::parse(file) {
+        debug!("Found zImage of {} bytes", zimage.size());
+        trace.push(String::from("zImage"));
+        let mut payload = extract_payload(file, 0, zimage.size())?;
+        return scan(&mut payload).map(|scanned| vec![(String::new(), scanned)]);
+    }
+
+    debug!("Scanning {} bytes", file.metadata()?.len());
+    scan(file).map(|scanned| vec![(String::new(), scanned)])
+}
+
+// containers like FIT image may have multiple kernels, the results of all
+// the successful ones are returned
+fn unwrap_kernels<T>(
+    kernels: impl Iterator<Item = (String, Result<File, io::Error>)>,
+    scan: fn(&mut File) -> Result<T, io::Error>,
+    trace: &mut Vec<String>,
+) -> Result<Vec<(String, T)>, io::Error> {
+    let mut results = Vec::new();
+    let mut failed_trace = None;
+    for (description, kernel) in kernels {
+        eprintln!("{description}");
+        let mut kernel_trace = Vec::new();
+        match kernel.and_then(|mut kernel| unwrap_image(&mut kernel, scan, &mut kernel_trace)) {
+            Ok(scanned) => {
+                // only the trace of the first kernel is kept
+                if results.is_empty() {
+                    trace.append(&mut kernel_trace);
+                }
+                results.extend(scanned.into_iter().map(|(label, scanned)| {
+                    let label = if label.is_empty() {
+                        description.clone()
+                    } else {
+                        format!("{description} > {label}")
+                    };
+                    (label, scanned)
+                }));
+            }
+            Err(_) => {
+                failed_trace.get_or_insert(kernel_trace);
+            }
+        }
+    }
+    if results.is_empty() {
+        // the containers of the first kernel are still recorded on failure
+        trace.extend(failed_trace.unwrap_or_default());
+        return Err(io::Error::from(ErrorKind::NotFound));
+    }
+    Ok(results)
+}
+
+fn extract_config(file: &mut File) -> Result<Vec<u8>, io::Error> {
+    let mut configs = unwrap_image(file, scan_config, &mut Vec::new())?;
+    if configs.len() == 1 {
+        return Ok(configs.swap_remove(0).1);
+    }
+
+    // the configs of multiple kernels are each preceded by a comment line
+    // of the member, so that they can be told apart in the output
+    let mut output = Vec::new();
+    for (label, config) in configs {
+        output.extend_from_slice(format!("# {label}\n").as_bytes());
+        output.extend_from_slice(&config);
+        if !output.ends_with(b"\n") {
+            output.push(b'\n');
+        }
+    }
+    Ok(output)
+}
+
+fn extract_vmlinux(file: &mut File) -> Result<File, io::Error> {
+    // only the first kernel is returned if there are multiple ones
+    let (_, (_, vmlinux)) = unwrap_image(file, scan_vmlinux, &mut Vec::new())?.swap_remove(0);
+    Ok(vmlinux)
+}
+
+fn find_configs_ko(dir: &Path) -> Result<PathBuf, io::Error> {
+    // configs.ko may be compressed, e.g. configs.ko.xz or configs.ko.zst
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if let Ok(path) = find_configs_ko(&path) {
+                return Ok(path);
+            }
+        } else if entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with("configs.ko")
+        {
+            return Ok(path);
+        }
+    }
+
+    Err(io::Error::from(ErrorKind::NotFound))
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    ExtractVmlinux(vmlinux::ExtractVmlinuxArgs),
+    ExtractHeaders(kheaders::ExtractHeadersArgs),
+    ExtractInitramfs(initramfs::ExtractInitramfsArgs),
+    ExtractDtb(dtb::ExtractDtbArgs),
+    ExtractBtf(btf::ExtractBtfArgs),
+    ExtractCerts(certs::ExtractCertsArgs),
+    Info(info::InfoArgs),
+    Infer(infer::InferArgs),
+    Kallsyms(kallsyms::KallsymsArgs),
+    Modinfo(modinfo::ModinfoArgs),
 }
 
 #[derive(FromArgs)]
 #[argh(description = "An utility to extract the .config file from a kernel image")]
 struct Args {
+    #[argh(subcommand)]
+    command: Option<Command>,
+
+    #[argh(
+        switch,
+        short = 'v',
+        description = "print verbose logs, twice for more, or set levels by RUST_LOG"
+    )]
+    verbose: u8,
+
     #[argh(positional, description = "kernel image compiled with CONFIG_IKCONFIG")]
-    image: String,
+    image: Option<String>,
+
+    #[argh(
+        option,
+        description = "find configs.ko in kernel modules directory, e.g. /lib/modules/<version>"
+    )]
+    modules_dir: Option<String>,
+
+    #[argh(switch, description = "extract the config of the running kernel")]
+    running: bool,
+
+    #[argh(
+        option,
+        description = "root directory to find the running kernel, default to /"
+    )]
+    root: Option<String>,
 }
 
 fn main() {
     let args: Args = argh::from_env();
-    let mut file = match File::open(&args.image) {
+    logger::init(args.verbose);
+    match args.command {
+        Some(Command::ExtractVmlinux(args)) => return vmlinux::run(args),
+        Some(Command::ExtractHeaders(args)) => return kheaders::run(args),
+        Some(Command::ExtractInitramfs(args)) => return initramfs::run(args),
+        Some(Command::ExtractDtb(args)) => return dtb::run(args),
+        Some(Command::ExtractBtf(args)) => return btf::run(args),
+        Some(Command::ExtractCerts(args)) => return certs::run(args),
+        Some(Command::Info(args)) => return info::run(args),
+        Some(Command::Infer(args)) => return infer::run(args),
+        Some(Command::Kallsyms(args)) => return kallsyms::run(args),
+        Some(Command::Modinfo(args)) => return modinfo::run(args),
+        None => {}
+    }
+
+    if args.running {
+        if args.image.is_some() || args.modules_dir.is_some() {
+            eprintln!("Please don't specify a kernel image or --modules-dir with --running");
+        } else if let Err(err) =
+            running::dump_config(Path::new(args.root.as_deref().unwrap_or("/")))
+        {
+            eprintln!(
+                "{}: Cannot find the config of running kernel: {err}",
+                env!("CARGO_BIN_NAME")
+            );
+        }
+        return;
+    }
+    if args.root.is_some() {
+        eprintln!("Please specify --root only with --running");
+        return;
+    }
+
+    let image = match (args.image, args.modules_dir) {
+        (Some(image), None) => image,
+        (None, Some(dir)) => match find_configs_ko(Path::new(&dir)) {
+            Ok(path) => {
+                eprintln!("Found {}", path.display());
+                path.to_string_lossy().into_owned()
+            }
+            Err(err) => {
+                eprintln!("Failed to find configs.ko in {dir}: {err}");
+                return;
+            }
+        },
+        _ => {
+            eprintln!("Please specify either a kernel image, --modules-dir or --running");
+            return;
+        }
+    };
+
+    let mut file = match File::open(&image) {
         Ok(image) => image,
         Err(err) => {
-            eprintln!("Failed to open file {}: {err}", &args.image);
+            eprintln!("Failed to open file {image}: {err}");
             return;
         }
     };
 
-    if dump_config(&mut file)
-        .or_else(|_| try_decompress(&mut file, MAGIC_NUMBER_GZIP, gunzip))
-        .or_else(|_| try_decompress(&mut file, MAGIC_NUMBER_XZ, unxz))
-        .or_else(|_| try_decompress(&mut file, MAGIC_NUMBER_BZIP2, bunzip2))
-        .or_else(|_| try_decompress(&mut file, MAGIC_NUMBER_LZMA, unlzma))
-        .or_else(|_| try_decompress(&mut file, MAGIC_NUMBER_LZO, unlzo))
-        .or_else(|_| try_decompress(&mut file, MAGIC_NUMBER_LZ4, unlz4))
-        .or_else(|_| try_decompress(&mut file, MAGIC_NUMBER_ZSTD, unzstd))
-        .is_err()
-    {
-        eprintln!(
-            "{}: Cannot find kernel config. Please confirm kernel compiled with CONFIG_IKCONFIG.",
-            env!("CARGO_BIN_NAME")
-        );
+    match extract_config(&mut file) {
+        Ok(config) => {
+            if let Err(err) = io::stdout().write_all(&config) {
+                eprintln!("Failed to write kernel config: {err}");
+            }
+        }
+        Err(_) => {
+            eprintln!(
+                "{}: Cannot find kernel config. Please confirm kernel compiled with CONFIG_IKCONFIG.",
+                env!("CARGO_BIN_NAME")
+            );
+            eprintln!(
+                "Use `{} infer {image}` to guess a partial config instead.",
+                env!("CARGO_BIN_NAME")
+            );
+        }
     }
 }
 
@@ -369,6 +865,7 @@ mod tests {
     const MAGIC_NUMBER_ZSTD: &[u8] = b"\x28\xb5\x2f\xfd";
     const PATTERN_OFFSET_VMLINUX_ZSTD: u64 = 16063;
 
+    #[allow(clippy::unbuffered_bytes)]
     fn search_bytes(file: &mut File, pattern: &[u8]) -> Result<u64, io::Error> {
         let filelen = file.metadata()?.len();
         let mut start = 0;
@@ -559,51 +1056,51 @@ mod tests {
 
     #[test]
     fn test_search_regex() {
-        let mut file = File::open(PATH_VMLINUX_RAW).unwrap();
+        let file = File::open(PATH_VMLINUX_RAW).unwrap();
         assert_eq!(
-            search_regex(&mut file, IKCFG_ST_FLAG_STR).unwrap(),
+            search_regex(&file, IKCFG_ST_FLAG_STR).unwrap(),
             PATTERN_OFFSET_VMLINUX_RAW
         );
 
-        let mut file = File::open(PATH_VMLINUX_GZIP).unwrap();
+        let file = File::open(PATH_VMLINUX_GZIP).unwrap();
         assert_eq!(
-            search_regex(&mut file, super::MAGIC_NUMBER_GZIP).unwrap(),
+            search_regex(&file, super::MAGIC_NUMBER_GZIP).unwrap(),
             PATTERN_OFFSET_VMLINUX_GZIP
         );
 
-        let mut file = File::open(PATH_VMLINUX_XZ).unwrap();
+        let file = File::open(PATH_VMLINUX_XZ).unwrap();
         assert_eq!(
-            search_regex(&mut file, super::MAGIC_NUMBER_XZ).unwrap(),
+            search_regex(&file, super::MAGIC_NUMBER_XZ).unwrap(),
             PATTERN_OFFSET_VMLINUX_XZ
         );
 
-        let mut file = File::open(PATH_VMLINUX_BZIP2).unwrap();
+        let file = File::open(PATH_VMLINUX_BZIP2).unwrap();
         assert_eq!(
-            search_regex(&mut file, super::MAGIC_NUMBER_BZIP2).unwrap(),
+            search_regex(&file, super::MAGIC_NUMBER_BZIP2).unwrap(),
             PATTERN_OFFSET_VMLINUX_BZIP2
         );
 
-        let mut file = File::open(PATH_VMLINUX_LZMA).unwrap();
+        let file = File::open(PATH_VMLINUX_LZMA).unwrap();
         assert_eq!(
-            search_regex(&mut file, super::MAGIC_NUMBER_LZMA).unwrap(),
+            search_regex(&file, super::MAGIC_NUMBER_LZMA).unwrap(),
             PATTERN_OFFSET_VMLINUX_LZMA
         );
 
-        let mut file = File::open(PATH_VMLINUX_LZO).unwrap();
+        let file = File::open(PATH_VMLINUX_LZO).unwrap();
         assert_eq!(
-            search_regex(&mut file, super::MAGIC_NUMBER_LZO).unwrap(),
+            search_regex(&file, super::MAGIC_NUMBER_LZO).unwrap(),
             PATTERN_OFFSET_VMLINUX_LZO
         );
 
-        let mut file = File::open(PATH_VMLINUX_LZ4).unwrap();
+        let file = File::open(PATH_VMLINUX_LZ4).unwrap();
         assert_eq!(
-            search_regex(&mut file, super::MAGIC_NUMBER_LZ4).unwrap(),
+            search_regex(&file, super::MAGIC_NUMBER_LZ4).unwrap(),
             PATTERN_OFFSET_VMLINUX_LZ4
         );
 
-        let mut file = File::open(PATH_VMLINUX_ZSTD).unwrap();
+        let file = File::open(PATH_VMLINUX_ZSTD).unwrap();
         assert_eq!(
-            search_regex(&mut file, super::MAGIC_NUMBER_ZSTD).unwrap(),
+            search_regex(&file, super::MAGIC_NUMBER_ZSTD).unwrap(),
             PATTERN_OFFSET_VMLINUX_ZSTD
         );
     }
@@ -629,7 +1126,7 @@ mod tests {
         );
 
         let instant = Instant::now();
-        search_regex(&mut file, pattern).unwrap();
+        search_regex(&file, pattern).unwrap();
         println!(
             "{:15}: {:-10} us",
             "search_regex",
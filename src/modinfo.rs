@@ -0,0 +1,316 @@
+use crate::{
+    certs::{self, Certificate},
+    der::{self, Tlv},
+    digest::Hash,
+    rsa,
+};
+use argh::FromArgs;
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Seek},
+};
+
+const MODULE_SIG_STRING: &[u8] = b"~Module signature appended~\n";
+// struct module_signature in include/linux/module_signature.h
+const MODULE_SIGNATURE_SIZE: usize = 12;
+const PKEY_ID_PKCS7: u8 = 2;
+
+const OID_SIGNED_DATA: &[u8] = b"\x2a\x86\x48\x86\xf7\x0d\x01\x07\x02";
+const OID_MESSAGE_DIGEST: &[u8] = b"\x2a\x86\x48\x86\xf7\x0d\x01\x09\x04";
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "modinfo",
+    description = "show the information and signature of a kernel module"
+)]
+pub struct ModinfoArgs {
+    #[argh(positional, description = "kernel module, optionally compressed")]
+    module: String,
+
+    #[argh(
+        option,
+        short = 'k',
+        description = "kernel image to verify the signature with its built-in certificates"
+    )]
+    kernel: Option<String>,
+}
+
+pub enum Signer {
+    IssuerSerial {
+        raw_issuer: Vec<u8>,
+        issuer: String,
+        serial: Vec<u8>,
+    },
+    KeyId(Vec<u8>),
+}
+
+// the SignerInfo of a PKCS#7 SignedData by sign-file
+pub struct Signature {
+    pub signer: Signer,
+    pub hash: Result<Hash, String>,
+    pub algorithm: String,
+    // the authenticated attributes, which are absent with sign-file
+    pub signed_attrs: Option<Vec<u8>>,
+    pub signature: Vec<u8>,
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn algorithm_name(oid: &[u8]) -> String {
+    match oid {
+        b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x01" => String::from("rsaEncryption"),
+        b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x0b" => String::from("sha256WithRSAEncryption"),
+        b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x0c" => String::from("sha384WithRSAEncryption"),
+        b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x0d" => String::from("sha512WithRSAEncryption"),
+        b"\x2a\x86\x48\xce\x3d\x04\x03\x02" => String::from("ecdsa-with-SHA256"),
+        b"\x2a\x86\x48\xce\x3d\x04\x03\x03" => String::from("ecdsa-with-SHA384"),
+        b"\x2a\x86\x48\xce\x3d\x04\x03\x04" => String::from("ecdsa-with-SHA512"),
+        _ => der::oid_string(oid),
+    }
+}
+
+// the first object identifier in AlgorithmIdentifier
+fn algorithm_oid<'a>(algorithm: &Tlv<'a>) -> Result<&'a [u8], io::Error> {
+    algorithm
+        .children()?
+        .into_iter()
+        .next()
+        .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?
+        .expect(der::TAG_OID)
+        .map(|oid| oid.value)
+}
+
+impl Signature {
+    pub fn parse(data: &[u8]) -> Result<Signature, io::Error> {
+        let invalid = || io::Error::from(ErrorKind::InvalidData);
+        let (content_info, _) = Tlv::parse(data)?;
+        let [oid, content] =
+            <[Tlv; 2]>::try_from(content_info.expect(der::TAG_SEQUENCE)?.children()?)
+                .map_err(|_| invalid())?;
+        if oid.expect(der::TAG_OID)?.value != OID_SIGNED_DATA {
+            return Err(invalid());
+        }
+        let (signed_data, _) = Tlv::parse(content.expect(0xa0)?.value)?;
+        // signerInfos is the last one after the optional certificates and crls
+        let signer_infos = signed_data
+            .expect(der::TAG_SEQUENCE)?
+            .children()?
+            .pop()
+            .ok_or_else(invalid)?
+            .expect(der::TAG_SET)?;
+        let signer_info = signer_infos
+            .children()?
+            .into_iter()
+            .next()
+            .ok_or_else(invalid)?
+            .expect(der::TAG_SEQUENCE)?;
+
+        let mut fields = signer_info.children()?.into_iter().skip(1);
+        let sid = fields.next().ok_or_else(invalid)?;
+        let signer = match sid.tag {
+            der::TAG_SEQUENCE => {
+                let [issuer, serial] =
+                    <[Tlv; 2]>::try_from(sid.children()?).map_err(|_| invalid())?;
+                Signer::IssuerSerial {
+                    raw_issuer: issuer.raw.to_vec(),
+                    issuer: certs::name(issuer)?,
+                    serial: serial.expect(der::TAG_INTEGER)?.value.to_vec(),
+                }
+            }
+            // [0] IMPLICIT SubjectKeyIdentifier
+            0x80 => Signer::KeyId(sid.value.to_vec()),
+            _ => return Err(invalid()),
+        };
+
+        let hash = algorithm_oid(&fields.next().ok_or_else(invalid)?)?;
+        let hash = Hash::from_oid(hash).ok_or_else(|| der::oid_string(hash));
+        let mut field = fields.next().ok_or_else(invalid)?;
+        let mut signed_attrs = None;
+        if field.tag == 0xa0 {
+            signed_attrs = Some(field.raw.to_vec());
+            field = fields.next().ok_or_else(invalid)?;
+        }
+        let algorithm = algorithm_name(algorithm_oid(&field)?);
+        let signature = fields
+            .next()
+            .ok_or_else(invalid)?
+            .expect(der::TAG_OCTET_STRING)?
+            .value
+            .to_vec();
+
+        Ok(Signature {
+            signer,
+            hash,
+            algorithm,
+            signed_attrs,
+            signature,
+        })
+    }
+
+    fn is_signed_by(&self, cert: &Certificate) -> bool {
+        match &self.signer {
+            Signer::IssuerSerial {
+                raw_issuer, serial, ..
+            } => *raw_issuer == cert.raw_issuer && *serial == cert.raw_serial,
+            Signer::KeyId(key_id) => cert.subject_key_id.as_ref() == Some(key_id),
+        }
+    }
+
+    // the digest to be signed, which is of the signed attributes if any
+    fn signed_digest(&self, hash: Hash, content: &[u8]) -> Result<Vec<u8>, String> {
+        let digest = hash.digest(content);
+        let Some(attrs) = &self.signed_attrs else {
+            return Ok(digest);
+        };
+        let (attrs, _) = Tlv::parse(attrs).map_err(|err| err.to_string())?;
+        let message_digest = attrs
+            .children()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|attr| attr.children().ok())
+            .find(|attr| {
+                attr.first()
+                    .is_some_and(|oid| oid.value == OID_MESSAGE_DIGEST)
+            })
+            .and_then(|attr| Tlv::parse(attr.get(1)?.value).ok())
+            .map(|(value, _)| value.value.to_vec());
+        if message_digest != Some(digest) {
+            return Err(String::from("message digest mismatch"));
+        }
+        // the attributes are signed as a SET OF instead of [0] IMPLICIT
+        let mut raw = attrs.raw.to_vec();
+        raw[0] = der::TAG_SET;
+        Ok(hash.digest(&raw))
+    }
+
+    pub fn verify(&self, content: &[u8], certs: &[Certificate]) -> Result<String, String> {
+        let cert = certs
+            .iter()
+            .find(|cert| self.is_signed_by(cert))
+            .ok_or_else(|| String::from("no certificate of the signer"))?;
+        let hash = self
+            .hash
+            .clone()
+            .map_err(|hash| format!("unsupported hash algorithm {hash}"))?;
+        let key = rsa::PublicKey::parse(&cert.public_key)
+            .ok_or_else(|| String::from("unsupported public key algorithm"))?;
+        let digest = self.signed_digest(hash, content)?;
+        if key.verify(hash, &digest, &self.signature) {
+            Ok(cert.subject.clone())
+        } else {
+            Err(String::from("bad signature"))
+        }
+    }
+}
+
+// split a module into its content and the PKCS#7 signature appended
+pub fn split_signature(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let data = data.strip_suffix(MODULE_SIG_STRING)?;
+    let (data, info) = data.split_at(data.len().checked_sub(MODULE_SIGNATURE_SIZE)?);
+    if info[2] != PKEY_ID_PKCS7 {
+        return None;
+    }
+    let sig_len = u32::from_be_bytes(info[8..12].try_into().ok()?) as usize;
+    let (content, signature) = data.split_at(data.len().checked_sub(sig_len)?);
+    Some((content, signature))
+}
+
+fn read_modinfo(file: &mut File) -> Result<Vec<(String, String)>, io::Error> {
+    let elf = crate::elf::parse(file)?;
+    let section = elf
+        .sections
+        .iter()
+        .find(|section| section.name == ".modinfo")
+        .ok_or_else(|| io::Error::from(ErrorKind::NotFound))?;
+    let modinfo = elf.read_section(file, section)?;
+    Ok(modinfo
+        .split(|&b| b == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (key, value) = entry.split_once('=')?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect())
+}
+
+fn kernel_certs(path: &str) -> Result<Vec<Certificate>, io::Error> {
+    let mut file = File::open(path)?;
+    let mut vmlinux = crate::extract_vmlinux(&mut file)?;
+    let certs = certs::find_certs(&mut vmlinux)?;
+    Ok(certs.into_iter().map(|(_, cert)| cert).collect())
+}
+
+pub fn run(args: ModinfoArgs) {
+    let mut file = match File::open(&args.module) {
+        Ok(module) => module,
+        Err(err) => {
+            eprintln!("Failed to open file {}: {err}", &args.module);
+            return;
+        }
+    };
+    // modules are signed before compressed
+    if let Ok((_, decompressed)) = crate::decompress_file(&mut file) {
+        file = decompressed;
+    }
+
+    let mut data = Vec::new();
+    let modinfo = file
+        .rewind()
+        .and_then(|_| file.read_to_end(&mut data))
+        .and_then(|_| read_modinfo(&mut file));
+    let modinfo = match modinfo {
+        Ok(modinfo) => modinfo,
+        Err(err) => {
+            eprintln!(
+                "{}: Cannot find .modinfo of {}: {err}",
+                env!("CARGO_BIN_NAME"),
+                &args.module
+            );
+            return;
+        }
+    };
+
+    let print = |key: &str, value: &str| println!("{:16}{value}", format!("{key}:"));
+    print("filename", &args.module);
+    for (key, value) in &modinfo {
+        print(key, value);
+    }
+
+    let split = split_signature(&data);
+    let signature = split.and_then(|(_, signature)| Signature::parse(signature).ok());
+    if let Some(signature) = &signature {
+        print("sig_id", "PKCS#7");
+        match &signature.signer {
+            Signer::IssuerSerial { issuer, serial, .. } => {
+                print("signer", issuer);
+                print("sig_key", &hex(serial));
+            }
+            Signer::KeyId(key_id) => print("sig_key", &hex(key_id)),
+        }
+        match &signature.hash {
+            Ok(hash) => print("sig_hashalgo", hash.name()),
+            Err(hash) => print("sig_hashalgo", hash),
+        }
+        print("sig_algo", &signature.algorithm);
+    }
+
+    let Some(kernel) = &args.kernel else {
+        return;
+    };
+    let result = match (split, &signature) {
+        (Some((content, _)), Some(signature)) => kernel_certs(kernel)
+            .map_err(|err| format!("cannot find certificates in {kernel}: {err}"))
+            .and_then(|certs| signature.verify(content, &certs)),
+        _ => Err(String::from("module isn't signed")),
+    };
+    match result {
+        Ok(subject) => print("sig_verify", &format!("OK by {subject}")),
+        Err(err) => print("sig_verify", &format!("FAILED, {err}")),
+    }
+}
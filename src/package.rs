@@ -0,0 +1,235 @@
+use crate::{archive, tar};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+};
+
+const AR_MAGIC: &[u8] = b"!<arch>\n";
+const AR_HEADER_SIZE: u64 = 60;
+
+// RPM file begins with the lead, followed by the signature header,
+// the header and then the compressed cpio payload
+const RPM_LEAD_MAGIC: &[u8] = b"\xed\xab\xee\xdb";
+const RPM_LEAD_SIZE: u64 = 96;
+const RPM_HEADER_MAGIC: &[u8] = b"\x8e\xad\xe8\x01";
+const RPMTAG_NAME: u32 = 1000;
+const RPMTAG_VERSION: u32 = 1001;
+const RPMTAG_RELEASE: u32 = 1002;
+const RPM_STRING_TYPE: u32 = 6;
+
+pub struct Package {
+    pub format: &'static str,
+    pub name: Option<String>,
+    // the uncompressed tar or cpio archive of the files
+    payload: File,
+}
+
+impl fmt::Display for Package {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format)?;
+        if let Some(name) = &self.name {
+            write!(f, ": {name}")?;
+        }
+        Ok(())
+    }
+}
+
+// the paths of kernel images installed by distributions,
+// e.g. boot/vmlinuz-6.1.0-1-amd64 or lib/modules/6.1.0-1.fc37.x86_64/vmlinuz
+pub fn is_kernel_path(path: &str) -> bool {
+    let path = path.trim_start_matches("./").trim_start_matches('/');
+    if let Some(name) = path.strip_prefix("boot/") {
+        return name.starts_with("vmlinuz-") || name.starts_with("vmlinux-");
+    }
+    path.strip_prefix("lib/modules/")
+        .or_else(|| path.strip_prefix("usr/lib/modules/"))
+        .and_then(|path| path.split_once('/'))
+        .is_some_and(|(_, name)| name == "vmlinuz")
+}
+
+// the uncompressed payload, compressed by gzip, xz or zstd if any
+fn decompress_payload(mut payload: File) -> File {
+    match crate::decompress_file(&mut payload) {
+        Ok((_, decompressed)) => decompressed,
+        Err(_) => payload,
+    }
+}
+
+// the members of ar archive with their offsets and sizes
+fn ar_members(file: &mut File) -> Result<Vec<(String, u64, u64)>, io::Error> {
+    let len = file.metadata()?.len();
+    let mut members = Vec::new();
+    let mut offset = AR_MAGIC.len() as u64;
+    let mut header = [0u8; AR_HEADER_SIZE as usize];
+    while offset + AR_HEADER_SIZE <= len {
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header)?;
+        if &header[58..60] != b"`\n" {
+            return Err(io::Error::from(ErrorKind::InvalidData));
+        }
+        let name = String::from_utf8_lossy(&header[0..16]);
+        let size = String::from_utf8_lossy(&header[48..58])
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| io::Error::from(ErrorKind::InvalidData))?;
+        // GNU ar terminates names with '/'
+        let name = name.trim_end().trim_end_matches('/').to_string();
+        members.push((name, offset + AR_HEADER_SIZE, size));
+        offset += AR_HEADER_SIZE + size.next_multiple_of(2);
+    }
+    Ok(members)
+}
+
+// "Package" and "Version" fields of the control file in control.tar
+fn deb_name(control: &mut File) -> Option<String> {
+    let entry = tar::entries(control)
+        .ok()?
+        .into_iter()
+        .find(|entry| entry.path.trim_start_matches("./") == "control")?;
+    if entry.offset.saturating_add(entry.size) > control.metadata().ok()?.len() {
+        return None;
+    }
+    let mut data = vec![0u8; entry.size as usize];
+    control.seek(SeekFrom::Start(entry.offset)).ok()?;
+    control.read_exact(&mut data).ok()?;
+
+    let data = String::from_utf8_lossy(&data);
+    let field = |name: &str| {
+        data.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .map(str::trim)
+    };
+    Some(format!("{} {}", field("Package")?, field("Version")?))
+}
+
+fn parse_deb(file: &mut File) -> Result<Package, io::Error> {
+    let members = ar_members(file)?;
+    if !matches!(members.first(), Some((name, _, _)) if name == "debian-binary") {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    let mut name = None;
+    let mut payload = None;
+    for (member, offset, size) in members {
+        if member.starts_with("control.tar") {
+            let mut control = decompress_payload(crate::extract_payload(file, offset, size)?);
+            name = deb_name(&mut control);
+        } else if member.starts_with("data.tar") {
+            payload = Some(decompress_payload(crate::extract_payload(
+                file, offset, size,
+            )?));
+        }
+    }
+
+    Ok(Package {
+        format: "Debian package",
+        name,
+        payload: payload.ok_or_else(|| io::Error::from(ErrorKind::NotFound))?,
+    })
+}
+
+// the header structure of index entries and data, returning the string tags
+// and the size of the header
+fn rpm_header(file: &mut File, offset: u64) -> Result<(Vec<(u32, String)>, u64), io::Error> {
+    let mut header = [0u8; 16];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut header)?;
+    if &header[0..4] != RPM_HEADER_MAGIC {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+    let nindex = u32::from_be_bytes(header[8..12].try_into().unwrap()) as u64;
+    let hsize = u32::from_be_bytes(header[12..16].try_into().unwrap()) as u64;
+    let size = 16 + nindex * 16 + hsize;
+    if offset.saturating_add(size) > file.metadata()?.len() {
+        return Err(io::Error::from(ErrorKind::UnexpectedEof));
+    }
+
+    let mut index = vec![0u8; nindex as usize * 16];
+    let mut data = vec![0u8; hsize as usize];
+    file.read_exact(&mut index)?;
+    file.read_exact(&mut data)?;
+
+    let mut tags = Vec::new();
+    for entry in index.chunks_exact(16) {
+        let field = |i: usize| u32::from_be_bytes(entry[i * 4..i * 4 + 4].try_into().unwrap());
+        if field(1) != RPM_STRING_TYPE {
+            continue;
+        }
+        let value = data.get(field(2) as usize..).unwrap_or_default();
+        let len = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+        tags.push((
+            field(0),
+            String::from_utf8_lossy(&value[..len]).into_owned(),
+        ));
+    }
+    Ok((tags, size))
+}
+
+fn parse_rpm(file: &mut File) -> Result<Package, io::Error> {
+    // the signature header is padded to a multiple of 8 bytes
+    let (_, size) = rpm_header(file, RPM_LEAD_SIZE)?;
+    let offset = RPM_LEAD_SIZE + size.next_multiple_of(8);
+    let (tags, size) = rpm_header(file, offset)?;
+    let tag = |tag| {
+        tags.iter()
+            .find(|(found, _)| *found == tag)
+            .map(|(_, value)| value.as_str())
+    };
+    let name = tag(RPMTAG_NAME)
+        .zip(tag(RPMTAG_VERSION))
+        .zip(tag(RPMTAG_RELEASE))
+        .map(|((name, version), release)| format!("{name}-{version}-{release}"));
+
+    let offset = offset + size;
+    let len = file.metadata()?.len();
+    let payload = decompress_payload(crate::extract_payload(
+        file,
+        offset,
+        len.saturating_sub(offset),
+    )?);
+    Ok(Package {
+        format: "RPM package",
+        name,
+        payload,
+    })
+}
+
+pub fn parse(file: &mut File) -> Result<Package, io::Error> {
+    let mut magic = [0u8; 8];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut magic)?;
+    if magic == AR_MAGIC {
+        parse_deb(file)
+    } else if magic.starts_with(RPM_LEAD_MAGIC) {
+        parse_rpm(file)
+    } else {
+        Err(io::Error::from(ErrorKind::InvalidData))
+    }
+}
+
+impl Package {
+    // the kernel images in the package with their paths
+    pub fn kernels(&mut self) -> Result<archive::Kernels, io::Error> {
+        let archive = archive::parse(&mut self.payload)?;
+        Ok(archive.kernels(&mut self.payload, is_kernel_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_kernel_path() {
+        assert!(is_kernel_path("./boot/vmlinuz-6.1.0-1-amd64"));
+        assert!(is_kernel_path("/lib/modules/6.1.0-1.fc37.x86_64/vmlinuz"));
+        assert!(is_kernel_path(
+            "./usr/lib/modules/6.1.0-1.fc37.x86_64/vmlinuz"
+        ));
+        assert!(!is_kernel_path("./boot/config-6.1.0-1-amd64"));
+        assert!(!is_kernel_path(
+            "./lib/modules/6.1.0-1-amd64/kernel/vmlinuz"
+        ));
+    }
+}
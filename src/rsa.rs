@@ -0,0 +1,293 @@
+use crate::{
+    der::{self, Tlv},
+    digest::Hash,
+};
+use std::cmp::Ordering;
+
+// the object identifiers of RSA keys and signatures, i.e. 1.2.840.113549.1.1.*
+const OID_RSA_PREFIX: &[u8] = b"\x2a\x86\x48\x86\xf7\x0d\x01\x01";
+
+pub struct PublicKey {
+    n: Vec<u8>,
+    e: Vec<u8>,
+}
+
+impl PublicKey {
+    // the RSAPublicKey in SubjectPublicKeyInfo of a certificate
+    pub fn parse(spki: &[u8]) -> Option<PublicKey> {
+        let (spki, _) = Tlv::parse(spki).ok()?;
+        let [algorithm, key] = <[Tlv; 2]>::try_from(spki.children().ok()?).ok()?;
+        let oid = algorithm.children().ok()?.into_iter().next()?;
+        if !is_rsa(oid.value) {
+            return None;
+        }
+        // the BIT STRING has no unused bits
+        let key = key
+            .expect(der::TAG_BIT_STRING)
+            .ok()?
+            .value
+            .strip_prefix(&[0])?;
+        let (key, _) = Tlv::parse(key).ok()?;
+        let [n, e] = <[Tlv; 2]>::try_from(key.children().ok()?).ok()?;
+        Some(PublicKey {
+            n: n.expect(der::TAG_INTEGER).ok()?.value.to_vec(),
+            e: e.expect(der::TAG_INTEGER).ok()?.value.to_vec(),
+        })
+    }
+
+    // RSASSA-PKCS1-v1_5 of RFC 8017
+    pub fn verify(&self, hash: Hash, digest: &[u8], signature: &[u8]) -> bool {
+        let n = BigUint::from_bytes(&self.n);
+        let s = BigUint::from_bytes(signature);
+        if s.cmp(&n) != Ordering::Less {
+            return false;
+        }
+        let len = self.n.iter().skip_while(|&&b| b == 0).count();
+        let em = s.mod_pow(&BigUint::from_bytes(&self.e), &n).to_bytes(len);
+
+        // 0x00 0x01 0xff.. 0x00 DigestInfo, compared as a whole with the
+        // encoding expected for the digest
+        let digest_info = digest_info(hash, digest);
+        if len < digest_info.len() + 11 {
+            return false;
+        }
+        let mut expected = vec![0, 1];
+        expected.resize(len - digest_info.len() - 1, 0xff);
+        expected.push(0);
+        expected.extend_from_slice(&digest_info);
+        em == expected
+    }
+}
+
+pub fn is_rsa(oid: &[u8]) -> bool {
+    oid.len() == OID_RSA_PREFIX.len() + 1 && oid.starts_with(OID_RSA_PREFIX)
+}
+
+// DigestInfo ::= SEQUENCE { digestAlgorithm AlgorithmIdentifier, digest OCTET STRING },
+// where the parameters of algorithm are NULL
+fn digest_info(hash: Hash, digest: &[u8]) -> Vec<u8> {
+    // the lengths are short for the digests of SHA-2
+    let tlv = |tag: u8, value: &[u8]| [&[tag, value.len() as u8], value].concat();
+    let algorithm = [tlv(der::TAG_OID, hash.oid()), tlv(der::TAG_NULL, &[])].concat();
+    tlv(
+        der::TAG_SEQUENCE,
+        &[
+            tlv(der::TAG_SEQUENCE, &algorithm),
+            tlv(der::TAG_OCTET_STRING, digest),
+        ]
+        .concat(),
+    )
+}
+
+// unsigned big integer in 32-bit limbs of little endian, only for modular
+// exponentiation of public keys
+#[derive(Clone)]
+struct BigUint(Vec<u32>);
+
+impl BigUint {
+    fn from_bytes(bytes: &[u8]) -> BigUint {
+        let mut limbs: Vec<u32> = bytes
+            .rchunks(4)
+            .map(|chunk| chunk.iter().fold(0, |limb, &b| (limb << 8) | b as u32))
+            .collect();
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        BigUint(limbs)
+    }
+
+    fn to_bytes(&self, len: usize) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self
+            .0
+            .iter()
+            .rev()
+            .flat_map(|limb| limb.to_be_bytes())
+            .collect();
+        let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+        bytes.drain(..zeros);
+        let mut padded = vec![0; len.saturating_sub(bytes.len())];
+        padded.extend_from_slice(&bytes);
+        padded
+    }
+
+    fn bits(&self) -> usize {
+        self.0
+            .last()
+            .map_or(0, |top| self.0.len() * 32 - top.leading_zeros() as usize)
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        self.0
+            .get(index / 32)
+            .is_some_and(|limb| limb >> (index % 32) & 1 == 1)
+    }
+
+    fn cmp(&self, other: &BigUint) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+
+    fn mul(&self, other: &BigUint) -> BigUint {
+        let mut product = vec![0u32; self.0.len() + other.0.len()];
+        for (i, &a) in self.0.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.0.iter().enumerate() {
+                let t = product[i + j] as u64 + a as u64 * b as u64 + carry;
+                product[i + j] = t as u32;
+                carry = t >> 32;
+            }
+            product[i + other.0.len()] = carry as u32;
+        }
+        let mut product = BigUint(product);
+        product.trim();
+        product
+    }
+
+    fn trim(&mut self) {
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+    }
+
+    // self = self * 2 + bit
+    fn shl1(&mut self, bit: bool) {
+        let mut carry = bit as u32;
+        for limb in self.0.iter_mut() {
+            let next = *limb >> 31;
+            *limb = (*limb << 1) | carry;
+            carry = next;
+        }
+        if carry != 0 {
+            self.0.push(carry);
+        }
+    }
+
+    // self -= other, where self >= other
+    fn sub(&mut self, other: &BigUint) {
+        let mut borrow = 0i64;
+        for (i, limb) in self.0.iter_mut().enumerate() {
+            let t = *limb as i64 - other.0.get(i).copied().unwrap_or(0) as i64 - borrow;
+            *limb = t as u32;
+            borrow = (t < 0) as i64;
+        }
+        self.trim();
+    }
+
+    // the remainder by binary long division
+    fn rem(&self, modulus: &BigUint) -> BigUint {
+        let mut rem = BigUint(Vec::new());
+        for i in (0..self.bits()).rev() {
+            rem.shl1(self.bit(i));
+            if rem.cmp(modulus) != Ordering::Less {
+                rem.sub(modulus);
+            }
+        }
+        rem
+    }
+
+    fn mod_pow(&self, exponent: &BigUint, modulus: &BigUint) -> BigUint {
+        let mut result = BigUint(vec![1]).rem(modulus);
+        for i in (0..exponent.bits()).rev() {
+            result = result.mul(&result).rem(modulus);
+            if exponent.bit(i) {
+                result = result.mul(self).rem(modulus);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mod_pow() {
+        let base = BigUint::from_bytes(&[0x12, 0x34, 0x56, 0x78, 0x9a]);
+        let modulus = BigUint::from_bytes(&[0xff, 0xff, 0xff, 0xfb]);
+        let exponent = BigUint::from_bytes(&[0x01, 0x00, 0x01]);
+        // pow(0x123456789a, 65537, 0xfffffffb)
+        assert_eq!(
+            base.mod_pow(&exponent, &modulus).to_bytes(4),
+            0x4eb2701cu32.to_be_bytes()
+        );
+        assert_eq!(BigUint::from_bytes(&[0, 0, 1, 2]).to_bytes(3), [0, 1, 2]);
+
+        // the example of RSA with p = 61 and q = 53
+        let modulus = BigUint::from_bytes(&3233u16.to_be_bytes());
+        let encrypted = BigUint::from_bytes(&[65]).mod_pow(&BigUint::from_bytes(&[17]), &modulus);
+        assert_eq!(encrypted.to_bytes(2), 2790u16.to_be_bytes());
+        let exponent = BigUint::from_bytes(&2753u16.to_be_bytes());
+        assert_eq!(encrypted.mod_pow(&exponent, &modulus).to_bytes(2), [0, 65]);
+
+        // pow(3, p - 1, p) for the Mersenne prime p = 2^127 - 1
+        let mut prime = [0xff; 16];
+        prime[0] = 0x7f;
+        let mut exponent = prime;
+        exponent[15] = 0xfe;
+        let base = BigUint::from_bytes(&[3]);
+        let result = base.mod_pow(
+            &BigUint::from_bytes(&exponent),
+            &BigUint::from_bytes(&prime),
+        );
+        assert_eq!(result.to_bytes(1), [1]);
+    }
+
+    #[test]
+    fn test_digest_info() {
+        // the DER encoding of DigestInfo in RFC 8017
+        let digest = [0xaa; 32];
+        let prefix =
+            b"\x30\x31\x30\x0d\x06\x09\x60\x86\x48\x01\x65\x03\x04\x02\x01\x05\x00\x04\x20";
+        assert_eq!(
+            digest_info(Hash::Sha256, &digest),
+            [&prefix[..], &digest].concat()
+        );
+        let digest = [0xbb; 64];
+        let prefix =
+            b"\x30\x51\x30\x0d\x06\x09\x60\x86\x48\x01\x65\x03\x04\x02\x03\x05\x00\x04\x40";
+        assert_eq!(
+            digest_info(Hash::Sha512, &digest),
+            [&prefix[..], &digest].concat()
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        // the signature is the encoded message itself with the exponent 1
+        let key = PublicKey {
+            n: vec![0xff; 64],
+            e: vec![1],
+        };
+        let digest = Hash::Sha256.digest(b"abc");
+        let info = digest_info(Hash::Sha256, &digest);
+        let mut em = vec![0, 1];
+        em.resize(64 - info.len() - 1, 0xff);
+        em.push(0);
+        em.extend_from_slice(&info);
+        assert!(key.verify(Hash::Sha256, &digest, &em));
+        assert!(!key.verify(Hash::Sha224, &digest[..28], &em));
+        assert!(!key.verify(Hash::Sha256, &Hash::Sha256.digest(b"abd"), &em));
+
+        // the malformed padding
+        let mut malformed = em.clone();
+        malformed[1] = 2;
+        assert!(!key.verify(Hash::Sha256, &digest, &malformed));
+        let mut malformed = em.clone();
+        malformed[5] = 0xfe;
+        assert!(!key.verify(Hash::Sha256, &digest, &malformed));
+
+        // the parameters of algorithm other than NULL, in place of the padding
+        let params = [der::TAG_OCTET_STRING, 2, 0x12, 0x34];
+        let mut forged = vec![0, 1];
+        forged.resize(64 - info.len() - params.len() + 1, 0xff);
+        forged.extend_from_slice(&[0, 0x30, info[1] + 2, 0x30, info[3] + 2]);
+        forged.extend_from_slice(&info[4..15]);
+        forged.extend_from_slice(&params);
+        forged.extend_from_slice(&info[17..]);
+        assert_eq!(forged.len(), 64);
+        assert!(!key.verify(Hash::Sha256, &digest, &forged));
+    }
+}
@@ -0,0 +1,57 @@
+use std::{
+    fs::{self, File},
+    io::{self, Seek, Write},
+    path::Path,
+};
+
+type Dump = fn(&Path) -> Result<(), io::Error>;
+
+fn dump_file(file: &mut File) -> Result<(), io::Error> {
+    file.rewind()?;
+    io::copy(file, &mut io::stdout()).map(|_| ())
+}
+
+fn dump_proc_config(path: &Path) -> Result<(), io::Error> {
+    let src = File::open(path)?;
+    let mut dst = tempfile::tempfile()?;
+    crate::gunzip(&src, &mut dst)?;
+    dump_file(&mut dst)
+}
+
+fn dump_image_config(path: &Path) -> Result<(), io::Error> {
+    let config = crate::extract_config(&mut File::open(path)?)?;
+    io::stdout().write_all(&config)
+}
+
+pub fn dump_config(root: &Path) -> Result<(), io::Error> {
+    // the same as `uname -r` but can be faked by a different root
+    let release = fs::read_to_string(root.join("proc/sys/kernel/osrelease"))?;
+    let release = release.trim();
+    let modules_dir = root.join("lib/modules").join(release);
+
+    let sources: [(_, Dump); 4] = [
+        (root.join("proc/config.gz"), dump_proc_config),
+        (root.join(format!("boot/config-{release}")), |path| {
+            dump_file(&mut File::open(path)?)
+        }),
+        (
+            root.join(format!("boot/vmlinuz-{release}")),
+            dump_image_config,
+        ),
+        (modules_dir.join("vmlinuz"), dump_image_config),
+    ];
+
+    for (path, dump) in sources {
+        if path.exists() && dump(&path).is_ok() {
+            eprintln!("Using {}", path.display());
+            return Ok(());
+        }
+    }
+
+    // in case of CONFIG_IKCONFIG=m but configs.ko isn't loaded, the modules
+    // directory is walked only if none of the above is available
+    let path = crate::find_configs_ko(&modules_dir)?;
+    dump_image_config(&path)?;
+    eprintln!("Using {}", path.display());
+    Ok(())
+}
@@ -0,0 +1,369 @@
+use crate::disk::Entry;
+use byteorder::{ByteOrder, LittleEndian};
+use flate2::read::ZlibDecoder;
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+};
+
+// squashfs 4.0 begins with the superblock, and the inodes, the directories
+// and the fragment entries are stored in the metadata blocks of 8KB each
+const SQUASHFS_MAGIC: &[u8] = b"hsqs";
+const SUPERBLOCK_SIZE: usize = 96;
+const METADATA_SIZE: usize = 8192;
+// the metadata blocks are prefixed by the size, with the bit set if stored
+const METADATA_UNCOMPRESSED: u16 = 0x8000;
+// the sizes of data blocks have the bit set if stored
+const BLOCK_UNCOMPRESSED: u32 = 1 << 24;
+const FRAGMENT_NONE: u32 = 0xffffffff;
+const FRAGMENT_ENTRY_SIZE: usize = 16;
+
+const COMPRESSION_GZIP: u16 = 1;
+const COMPRESSION_LZMA: u16 = 2;
+const COMPRESSION_LZO: u16 = 3;
+const COMPRESSION_XZ: u16 = 4;
+const COMPRESSION_LZ4: u16 = 5;
+const COMPRESSION_ZSTD: u16 = 6;
+
+const INODE_DIR: u16 = 1;
+const INODE_FILE: u16 = 2;
+const INODE_LDIR: u16 = 8;
+const INODE_LFILE: u16 = 9;
+const INODE_HEADER_SIZE: usize = 16;
+const DIR_HEADER_SIZE: usize = 12;
+const DIR_ENTRY_SIZE: usize = 8;
+
+enum Inode {
+    Dir {
+        block: u64,
+        offset: usize,
+        size: usize,
+    },
+    File {
+        blocks_start: u64,
+        size: u64,
+        fragment: u32,
+        fragment_offset: usize,
+        blocks: Vec<u32>,
+    },
+    Other,
+}
+
+pub struct Squashfs {
+    // the offset of the filesystem in disk image
+    offset: u64,
+    compression: u16,
+    block_size: u64,
+    root: u64,
+    inode_table: u64,
+    directory_table: u64,
+    fragment_table: u64,
+}
+
+// the reader of consecutive metadata blocks from a position
+struct Metadata<'a> {
+    fs: &'a Squashfs,
+    next: u64,
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl Metadata<'_> {
+    fn read(&mut self, file: &mut File, len: usize) -> Result<&[u8], io::Error> {
+        while self.data.len() < self.pos + len {
+            let mut header = [0u8; 2];
+            file.seek(SeekFrom::Start(self.fs.offset + self.next))?;
+            file.read_exact(&mut header)?;
+            let header = LittleEndian::read_u16(&header);
+            let size = (header & !METADATA_UNCOMPRESSED) as usize;
+            let mut block = vec![0u8; size];
+            file.read_exact(&mut block)?;
+            if header & METADATA_UNCOMPRESSED == 0 {
+                block = self.fs.decompress(&block, METADATA_SIZE)?;
+            }
+            self.data.extend_from_slice(&block);
+            self.next += 2 + size as u64;
+        }
+        self.pos += len;
+        Ok(&self.data[self.pos - len..self.pos])
+    }
+}
+
+impl Squashfs {
+    pub fn name(&self) -> &'static str {
+        "squashfs"
+    }
+
+    fn decompress(&self, data: &[u8], size: usize) -> Result<Vec<u8>, io::Error> {
+        let mut block = Vec::with_capacity(size);
+        match self.compression {
+            COMPRESSION_GZIP => {
+                ZlibDecoder::new(data).read_to_end(&mut block)?;
+            }
+            // both the legacy lzma and xz streams are detected by the decoder
+            COMPRESSION_LZMA | COMPRESSION_XZ => {
+                block =
+                    lzma::decompress(data).map_err(|_| io::Error::from(ErrorKind::InvalidData))?
+            }
+            // the block is padded with zeros by the decoder
+            COMPRESSION_LZO => {
+                let lzo = minilzo_rs::LZO::init().map_err(|_| io::Error::from(ErrorKind::Other))?;
+                block = lzo
+                    .decompress_safe(data, size)
+                    .map_err(|_| io::Error::from(ErrorKind::InvalidData))?;
+            }
+            COMPRESSION_LZ4 => {
+                block.resize(size, 0);
+                let len = lz4_flex::block::decompress_into(data, &mut block)
+                    .map_err(|_| io::Error::from(ErrorKind::InvalidData))?;
+                block.truncate(len);
+            }
+            COMPRESSION_ZSTD => block = zstd::bulk::decompress(data, size)?,
+            _ => return Err(io::Error::from(ErrorKind::Unsupported)),
+        }
+        Ok(block)
+    }
+
+    // the metadata reader at the position relative to a table
+    fn metadata(&self, table: u64, block: u64, offset: usize) -> Metadata<'_> {
+        Metadata {
+            fs: self,
+            next: table + block,
+            data: Vec::new(),
+            pos: offset,
+        }
+    }
+
+    // the inode referred by the block in inode table and the offset in it
+    fn read_inode(&self, file: &mut File, node: u64) -> Result<Inode, io::Error> {
+        let mut metadata = self.metadata(self.inode_table, node >> 16, (node & 0xffff) as usize);
+        let kind = LittleEndian::read_u16(metadata.read(file, INODE_HEADER_SIZE)?);
+        let inode = match kind {
+            INODE_DIR => {
+                let data = metadata.read(file, 16)?;
+                Inode::Dir {
+                    block: LittleEndian::read_u32(&data[0..4]) as u64,
+                    offset: LittleEndian::read_u16(&data[10..12]) as usize,
+                    size: LittleEndian::read_u16(&data[8..10]) as usize,
+                }
+            }
+            INODE_LDIR => {
+                let data = metadata.read(file, 24)?;
+                Inode::Dir {
+                    block: LittleEndian::read_u32(&data[8..12]) as u64,
+                    offset: LittleEndian::read_u16(&data[18..20]) as usize,
+                    size: LittleEndian::read_u32(&data[4..8]) as usize,
+                }
+            }
+            INODE_FILE | INODE_LFILE => {
+                let (blocks_start, size, fragment, fragment_offset) = if kind == INODE_FILE {
+                    let data = metadata.read(file, 16)?;
+                    (
+                        LittleEndian::read_u32(&data[0..4]) as u64,
+                        LittleEndian::read_u32(&data[12..16]) as u64,
+                        LittleEndian::read_u32(&data[4..8]),
+                        LittleEndian::read_u32(&data[8..12]) as usize,
+                    )
+                } else {
+                    let data = metadata.read(file, 40)?;
+                    (
+                        LittleEndian::read_u64(&data[0..8]),
+                        LittleEndian::read_u64(&data[8..16]),
+                        LittleEndian::read_u32(&data[28..32]),
+                        LittleEndian::read_u32(&data[32..36]) as usize,
+                    )
+                };
+                // the tail of file is stored in a fragment if any
+                let count = match fragment {
+                    FRAGMENT_NONE => size.div_ceil(self.block_size),
+                    _ => size / self.block_size,
+                };
+                let len = (count as usize)
+                    .checked_mul(4)
+                    .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?;
+                let blocks = metadata
+                    .read(file, len)?
+                    .chunks_exact(4)
+                    .map(LittleEndian::read_u32)
+                    .collect();
+                Inode::File {
+                    blocks_start,
+                    size,
+                    fragment,
+                    fragment_offset,
+                    blocks,
+                }
+            }
+            _ => Inode::Other,
+        };
+        Ok(inode)
+    }
+
+    fn read_block(&self, file: &mut File, start: u64, size: u32) -> Result<Vec<u8>, io::Error> {
+        let len = (size & !BLOCK_UNCOMPRESSED) as usize;
+        let mut block = vec![0u8; len];
+        file.seek(SeekFrom::Start(self.offset + start))?;
+        file.read_exact(&mut block)?;
+        if size & BLOCK_UNCOMPRESSED == 0 {
+            block = self.decompress(&block, self.block_size as usize)?;
+        }
+        Ok(block)
+    }
+
+    fn read_fragment(&self, file: &mut File, fragment: u32) -> Result<Vec<u8>, io::Error> {
+        // the fragment table is indexed by the locations of metadata blocks
+        let entries_per_block = (METADATA_SIZE / FRAGMENT_ENTRY_SIZE) as u64;
+        let mut location = [0u8; 8];
+        file.seek(SeekFrom::Start(
+            self.offset + self.fragment_table + fragment as u64 / entries_per_block * 8,
+        ))?;
+        file.read_exact(&mut location)?;
+        let entry = self
+            .metadata(
+                0,
+                LittleEndian::read_u64(&location),
+                (fragment as u64 % entries_per_block) as usize * FRAGMENT_ENTRY_SIZE,
+            )
+            .read(file, FRAGMENT_ENTRY_SIZE)?
+            .to_vec();
+        self.read_block(
+            file,
+            LittleEndian::read_u64(&entry[0..8]),
+            LittleEndian::read_u32(&entry[8..12]),
+        )
+    }
+
+    fn read_data(
+        &self,
+        file: &mut File,
+        inode: &Inode,
+        dst: &mut impl Write,
+    ) -> Result<(), io::Error> {
+        let Inode::File {
+            blocks_start,
+            size,
+            fragment,
+            fragment_offset,
+            blocks,
+        } = inode
+        else {
+            return Err(io::Error::from(ErrorKind::InvalidInput));
+        };
+
+        let mut remaining = *size;
+        let mut start = *blocks_start;
+        for &block in blocks {
+            let len = remaining.min(self.block_size) as usize;
+            // the blocks of zero size are holes
+            let data = match block {
+                0 => vec![0u8; len],
+                _ => self.read_block(file, start, block)?,
+            };
+            dst.write_all(data.get(..len).ok_or(ErrorKind::UnexpectedEof)?)?;
+            remaining -= len as u64;
+            start += (block & !BLOCK_UNCOMPRESSED) as u64;
+        }
+        if remaining > 0 && *fragment != FRAGMENT_NONE {
+            let data = self.read_fragment(file, *fragment)?;
+            let end = fragment_offset + remaining as usize;
+            dst.write_all(
+                data.get(*fragment_offset..end)
+                    .ok_or(ErrorKind::UnexpectedEof)?,
+            )?;
+        }
+        Ok(())
+    }
+
+    // the entries of the directory inode
+    fn entries(&self, file: &mut File, node: u64) -> Result<Vec<Entry>, io::Error> {
+        let Inode::Dir {
+            block,
+            offset,
+            size,
+        } = self.read_inode(file, node)?
+        else {
+            return Err(io::Error::from(ErrorKind::NotFound));
+        };
+
+        // the size includes 3 bytes for "." and ".." which aren't stored
+        let mut listing = self.metadata(self.directory_table, block, offset);
+        let mut remaining = size.saturating_sub(3);
+        let mut found = Vec::new();
+        while remaining >= DIR_HEADER_SIZE {
+            let header = listing.read(file, DIR_HEADER_SIZE)?;
+            let count = LittleEndian::read_u32(&header[0..4]) as usize + 1;
+            let start = LittleEndian::read_u32(&header[4..8]) as u64;
+            remaining -= DIR_HEADER_SIZE;
+            for _ in 0..count {
+                let entry = listing.read(file, DIR_ENTRY_SIZE)?;
+                let offset = LittleEndian::read_u16(&entry[0..2]) as u64;
+                let kind = LittleEndian::read_u16(&entry[4..6]);
+                let name_len = LittleEndian::read_u16(&entry[6..8]) as usize + 1;
+                let name = String::from_utf8_lossy(listing.read(file, name_len)?).into_owned();
+                remaining = remaining.saturating_sub(DIR_ENTRY_SIZE + name_len);
+                found.push((name, kind, start << 16 | offset));
+            }
+        }
+
+        let mut entries = Vec::new();
+        for (name, kind, node) in found {
+            let size = match kind {
+                INODE_FILE => match self.read_inode(file, node)? {
+                    Inode::File { size, .. } => size,
+                    _ => 0,
+                },
+                _ => 0,
+            };
+            entries.push(Entry {
+                name,
+                is_dir: kind == INODE_DIR,
+                is_file: kind == INODE_FILE,
+                node,
+                size,
+            });
+        }
+        Ok(entries)
+    }
+
+    pub fn read_root(&self, file: &mut File) -> Result<Vec<Entry>, io::Error> {
+        self.entries(file, self.root)
+    }
+
+    pub fn read_entries(&self, file: &mut File, dir: &Entry) -> Result<Vec<Entry>, io::Error> {
+        self.entries(file, dir.node)
+    }
+
+    pub fn read_file(&self, file: &mut File, entry: &Entry) -> Result<File, io::Error> {
+        let inode = self.read_inode(file, entry.node)?;
+        let mut dst = tempfile::tempfile()?;
+        self.read_data(file, &inode, &mut dst)?;
+        Ok(dst)
+    }
+}
+
+pub fn parse(file: &mut File, offset: u64) -> Result<Squashfs, io::Error> {
+    let mut superblock = [0u8; SUPERBLOCK_SIZE];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut superblock)?;
+
+    let block_size = LittleEndian::read_u32(&superblock[12..16]) as u64;
+    let block_log = LittleEndian::read_u16(&superblock[22..24]);
+    let version = LittleEndian::read_u16(&superblock[28..30]);
+    if &superblock[0..4] != SQUASHFS_MAGIC
+        || version != 4
+        || !(12..=20).contains(&block_log)
+        || block_size != 1 << block_log
+    {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    Ok(Squashfs {
+        offset,
+        compression: LittleEndian::read_u16(&superblock[20..22]),
+        block_size,
+        root: LittleEndian::read_u64(&superblock[32..40]),
+        inode_table: LittleEndian::read_u64(&superblock[64..72]),
+        directory_table: LittleEndian::read_u64(&superblock[72..80]),
+        fragment_table: LittleEndian::read_u64(&superblock[80..88]),
+    })
+}
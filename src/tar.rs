@@ -0,0 +1,203 @@
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+    os::unix::fs::{symlink, PermissionsExt},
+    path::{Component, Path},
+};
+
+const BLOCK_SIZE: u64 = 512;
+
+pub enum Kind {
+    File,
+    Directory,
+    Symlink(String),
+    Other,
+}
+
+pub struct Entry {
+    pub path: String,
+    pub mode: u32,
+    pub size: u64,
+    pub kind: Kind,
+    // the offset of the entry data in archive
+    pub offset: u64,
+}
+
+fn cstr(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+fn octal(bytes: &[u8]) -> Result<u64, io::Error> {
+    // GNU tar stores large numbers in base-256 with the highest bit set
+    if bytes[0] & 0x80 != 0 {
+        return Ok(bytes[1..]
+            .iter()
+            .fold((bytes[0] & 0x7f) as u64, |n, &b| n << 8 | b as u64));
+    }
+    let digits = cstr(bytes);
+    let digits = digits.trim_matches(|c| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8).map_err(|_| io::Error::from(ErrorKind::InvalidData))
+}
+
+// the value of "path" record in pax extended header, formatted as "%d %s=%s\n"
+fn pax_path(data: &[u8]) -> Option<String> {
+    let mut records = data;
+    while let Some(space) = records.iter().position(|&b| b == b' ') {
+        let len: usize = std::str::from_utf8(&records[..space]).ok()?.parse().ok()?;
+        let record = records.get(space + 1..len.checked_sub(1)?)?;
+        if let Some(path) = record.strip_prefix(b"path=") {
+            return Some(String::from_utf8_lossy(path).into_owned());
+        }
+        records = records.get(len..)?;
+    }
+    None
+}
+
+pub fn is_tar(header: &[u8]) -> bool {
+    // both "ustar\0" of POSIX and "ustar " of GNU
+    header.len() >= BLOCK_SIZE as usize && &header[257..262] == b"ustar"
+}
+
+pub fn entries(file: &mut File) -> Result<Vec<Entry>, io::Error> {
+    let len = file.metadata()?.len();
+    let mut entries = Vec::new();
+    let mut long_name = None;
+    let mut offset = 0;
+    let mut header = [0u8; BLOCK_SIZE as usize];
+
+    while offset + BLOCK_SIZE <= len {
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header)?;
+
+        // the archive ends with zero blocks
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        if !is_tar(&header) {
+            return Err(io::Error::from(ErrorKind::InvalidData));
+        }
+
+        let size = octal(&header[124..136])?;
+        let data_offset = offset + BLOCK_SIZE;
+        if size > len - data_offset {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        offset = size
+            .div_ceil(BLOCK_SIZE)
+            .checked_mul(BLOCK_SIZE)
+            .and_then(|size| size.checked_add(data_offset))
+            .ok_or(ErrorKind::InvalidData)?;
+
+        let typeflag = header[156];
+        let kind = match typeflag {
+            b'0' | b'\0' | b'7' => Kind::File,
+            b'5' => Kind::Directory,
+            b'2' => Kind::Symlink(cstr(&header[157..257])),
+            // GNU long name or pax extended header for the next entry
+            b'L' | b'x' => {
+                let mut data = vec![0u8; size as usize];
+                file.seek(SeekFrom::Start(data_offset))?;
+                file.read_exact(&mut data)?;
+                long_name = if typeflag == b'L' {
+                    Some(cstr(&data))
+                } else {
+                    pax_path(&data)
+                };
+                continue;
+            }
+            _ => Kind::Other,
+        };
+
+        let path = match long_name.take() {
+            Some(path) => path,
+            None => {
+                let name = cstr(&header[0..100]);
+                let prefix = cstr(&header[345..500]);
+                if &header[257..263] == b"ustar\0" && !prefix.is_empty() {
+                    format!("{prefix}/{name}")
+                } else {
+                    name
+                }
+            }
+        };
+
+        entries.push(Entry {
+            path,
+            mode: octal(&header[100..108])? as u32,
+            size,
+            kind,
+            offset: data_offset,
+        });
+    }
+
+    Ok(entries)
+}
+
+// whether the path or any of its parents in the destination directory is
+// a symbolic link, which would be followed to write outside of it
+fn through_symlink(dir: &Path, relative: &Path) -> bool {
+    let mut path = dir.to_path_buf();
+    relative.components().any(|component| {
+        path.push(component);
+        fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+    })
+}
+
+pub fn unpack(file: &mut File, dir: &Path) -> Result<(), io::Error> {
+    for entry in entries(file)? {
+        // never write outside of the destination directory, either by the
+        // path itself or through the symbolic links unpacked before
+        let relative = Path::new(&entry.path);
+        if relative
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+            || through_symlink(dir, relative)
+        {
+            eprintln!("Skipped unsafe path {}", entry.path);
+            continue;
+        }
+        let path = dir.join(relative);
+
+        match &entry.kind {
+            Kind::Directory => fs::create_dir_all(&path)?,
+            Kind::File => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                file.seek(SeekFrom::Start(entry.offset))?;
+                let mut dst = File::create(&path)?;
+                io::copy(&mut Read::by_ref(file).take(entry.size), &mut dst)?;
+                dst.set_permissions(fs::Permissions::from_mode(entry.mode & 0o777))?;
+            }
+            Kind::Symlink(target) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                symlink(target, &path)?;
+            }
+            Kind::Other => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pax_path() {
+        let records = b"30 mtime=1700000000.123456789\n36 path=include/linux/a_long_name.h\n";
+        assert_eq!(
+            pax_path(records).as_deref(),
+            Some("include/linux/a_long_name.h")
+        );
+        assert_eq!(pax_path(b"20 uname=root\n"), None);
+        assert_eq!(pax_path(b"999 path=truncated\n"), None);
+    }
+}
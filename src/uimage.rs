@@ -0,0 +1,250 @@
+use byteorder::{BigEndian, ByteOrder};
+use flate2::Crc;
+use std::{
+    fmt,
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+};
+
+// legacy uImage header is 64 bytes in big endian
+const IH_MAGIC: u32 = 0x27051956;
+const IH_HEADER_SIZE: usize = 64;
+const IH_NAME_LEN: usize = 32;
+
+const IH_TYPE_MULTI: u8 = 4;
+
+const IH_COMP_NONE: u8 = 0;
+const IH_COMP_GZIP: u8 = 1;
+const IH_COMP_BZIP2: u8 = 2;
+const IH_COMP_LZMA: u8 = 3;
+const IH_COMP_LZO: u8 = 4;
+const IH_COMP_LZ4: u8 = 5;
+const IH_COMP_ZSTD: u8 = 6;
+
+pub struct UImage {
+    pub time: u32,
+    pub size: u32,
+    pub load: u32,
+    pub ep: u32,
+    pub dcrc: u32,
+    pub os: u8,
+    pub arch: u8,
+    pub image_type: u8,
+    pub comp: u8,
+    pub name: String,
+}
+
+fn os_name(os: u8) -> &'static str {
+    match os {
+        1 => "OpenBSD",
+        2 => "NetBSD",
+        3 => "FreeBSD",
+        4 => "4_4BSD",
+        5 => "Linux",
+        14 => "VxWorks",
+        16 => "QNX",
+        17 => "U-Boot",
+        18 => "RTEMS",
+        25 => "ARM Trusted Firmware",
+        26 => "Trusted Execution Environment",
+        27 => "RISC-V OpenSBI",
+        28 => "EFI Firmware",
+        _ => "Unknown OS",
+    }
+}
+
+fn arch_name(arch: u8) -> &'static str {
+    match arch {
+        1 => "Alpha",
+        2 => "ARM",
+        3 => "Intel x86",
+        4 => "IA64",
+        5 => "MIPS",
+        6 => "MIPS 64 Bit",
+        7 => "PowerPC",
+        8 => "IBM S390",
+        9 => "SuperH",
+        10 => "SPARC",
+        11 => "SPARC 64 Bit",
+        12 => "M68K",
+        14 => "MicroBlaze",
+        15 => "NIOS II",
+        16 => "Blackfin",
+        17 => "AVR32",
+        19 => "Sandbox",
+        20 => "NDS32",
+        21 => "OpenRISC 1000",
+        22 => "AArch64",
+        23 => "ARC",
+        24 => "AMD x86_64",
+        25 => "Xtensa",
+        26 => "RISC-V",
+        _ => "Unknown Architecture",
+    }
+}
+
+fn type_name(image_type: u8) -> &'static str {
+    match image_type {
+        1 => "Standalone Program",
+        2 => "Kernel Image",
+        3 => "RAMDisk Image",
+        4 => "Multi-File Image",
+        5 => "Firmware",
+        6 => "Script File",
+        7 => "Filesystem Image",
+        8 => "Flat Device Tree",
+        14 => "Kernel Image (no loading done)",
+        _ => "Unknown Image",
+    }
+}
+
+fn comp_name(comp: u8) -> &'static str {
+    match comp {
+        IH_COMP_NONE => "uncompressed",
+        IH_COMP_GZIP => "gzip compressed",
+        IH_COMP_BZIP2 => "bzip2 compressed",
+        IH_COMP_LZMA => "lzma compressed",
+        IH_COMP_LZO => "lzo compressed",
+        IH_COMP_LZ4 => "lz4 compressed",
+        IH_COMP_ZSTD => "zstd compressed",
+        _ => "unknown compression",
+    }
+}
+
+// convert the seconds since epoch to date and time in UTC
+// see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn format_time(time: u32) -> String {
+    let days = (time / 86400) as i64 + 719468;
+    let secs = time % 86400;
+    let era = days / 146097;
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+impl fmt::Display for UImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Image Name:   {}", self.name)?;
+        writeln!(f, "Created:      {}", format_time(self.time))?;
+        writeln!(
+            f,
+            "Image Type:   {} {} {} ({})",
+            arch_name(self.arch),
+            os_name(self.os),
+            type_name(self.image_type),
+            comp_name(self.comp)
+        )?;
+        writeln!(f, "Data Size:    {} Bytes", self.size)?;
+        writeln!(f, "Load Address: {:08x}", self.load)?;
+        write!(f, "Entry Point:  {:08x}", self.ep)
+    }
+}
+
+pub fn parse(file: &mut File) -> Result<UImage, io::Error> {
+    let mut header = [0u8; IH_HEADER_SIZE];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+
+    if BigEndian::read_u32(&header[0..4]) != IH_MAGIC {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    // header checksum is calculated with ih_hcrc field cleared
+    let hcrc = BigEndian::read_u32(&header[4..8]);
+    header[4..8].fill(0);
+    let mut crc = Crc::new();
+    crc.update(&header);
+    if crc.sum() != hcrc {
+        eprintln!("uImage header checksum mismatch");
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    let name = &header[32..32 + IH_NAME_LEN];
+    let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(IH_NAME_LEN)];
+
+    Ok(UImage {
+        time: BigEndian::read_u32(&header[8..12]),
+        size: BigEndian::read_u32(&header[12..16]),
+        load: BigEndian::read_u32(&header[16..20]),
+        ep: BigEndian::read_u32(&header[20..24]),
+        dcrc: BigEndian::read_u32(&header[24..28]),
+        os: header[28],
+        arch: header[29],
+        image_type: header[30],
+        comp: header[31],
+        name: String::from_utf8_lossy(name).into_owned(),
+    })
+}
+
+impl UImage {
+    pub fn payload(&self, file: &mut File) -> Result<File, io::Error> {
+        // the data size is checked before allocating the buffer for it
+        if self.size as u64 > file.metadata()?.len().saturating_sub(IH_HEADER_SIZE as u64) {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        let mut data = vec![0u8; self.size as usize];
+        file.seek(SeekFrom::Start(IH_HEADER_SIZE as u64))?;
+        file.read_exact(&mut data)?;
+
+        let mut crc = Crc::new();
+        crc.update(&data);
+        if crc.sum() != self.dcrc {
+            eprintln!("uImage data checksum mismatch");
+            return Err(io::Error::from(ErrorKind::InvalidData));
+        }
+
+        // multi-file image begins with a zero-terminated list of file sizes,
+        // and the first file is the kernel
+        let data = if self.image_type == IH_TYPE_MULTI {
+            let count = data
+                .chunks_exact(4)
+                .position(|size| BigEndian::read_u32(size) == 0)
+                .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?;
+            if count == 0 {
+                return Err(io::Error::from(ErrorKind::NotFound));
+            }
+            let start = (count + 1) * 4;
+            let end = start + BigEndian::read_u32(&data[..4]) as usize;
+            data.get(start..end)
+                .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?
+        } else {
+            &data[..]
+        };
+
+        let decompress: Option<crate::Decompress> = match self.comp {
+            IH_COMP_NONE => None,
+            IH_COMP_GZIP => Some(crate::gunzip),
+            IH_COMP_BZIP2 => Some(crate::bunzip2),
+            IH_COMP_LZMA => Some(crate::unlzma),
+            IH_COMP_LZO => Some(crate::unlzo),
+            IH_COMP_LZ4 => Some(crate::unlz4),
+            IH_COMP_ZSTD => Some(crate::unzstd),
+            _ => return Err(io::Error::from(ErrorKind::Unsupported)),
+        };
+
+        crate::decompress_data(data, decompress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_time(1668720923), "2022-11-17 21:35:23 UTC");
+        assert_eq!(format_time(951782400), "2000-02-29 00:00:00 UTC");
+    }
+}
@@ -0,0 +1,396 @@
+use crate::elf;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use flate2::read::ZlibDecoder;
+use log::{debug, trace};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+};
+
+// makedumpfile compressed dump begins with struct disk_dump_header,
+// followed by the sub header, the bitmaps, the page descriptors and pages
+const KDUMP_SIGNATURE: &[u8] = b"KDUMP   ";
+const KDUMP_HEADER_SIZE: usize = 440;
+// the offsets of block_size, where struct timeval is aligned differently
+const KDUMP_BLOCK_SIZE_64: usize = 428;
+const KDUMP_BLOCK_SIZE_32: usize = 416;
+// release in struct new_utsname after sysname and nodename
+const KDUMP_RELEASE: usize = 12 + 65 * 2;
+const PAGE_DESC_SIZE: usize = 24;
+
+const DUMP_DH_COMPRESSED_ZLIB: u32 = 0x1;
+const DUMP_DH_COMPRESSED_LZO: u32 = 0x2;
+const DUMP_DH_COMPRESSED_SNAPPY: u32 = 0x4;
+const DUMP_DH_COMPRESSED_ZSTD: u32 = 0x20;
+
+// the memory is scanned in windows, which overlap so that the data across
+// their boundaries, e.g. a whole kernel image, is still within one of them,
+// both are multiples of any page size so that the windows begin at pages
+const WINDOW_SIZE: u64 = 256 << 20;
+const WINDOW_OVERLAP: u64 = 64 << 20;
+
+// struct page_desc of a dumped page
+struct PageDesc {
+    offset: u64,
+    size: u32,
+    flags: u32,
+}
+
+enum Memory {
+    // PT_LOAD segments of offset and size
+    Segments(Vec<(u64, u64)>),
+    Pages {
+        page_size: usize,
+        pages: Vec<PageDesc>,
+    },
+}
+
+pub struct Vmcore {
+    pub format: &'static str,
+    pub version: u32,
+    pub release: Option<String>,
+    memory: Memory,
+}
+
+impl fmt::Display for Vmcore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.memory {
+            Memory::Segments(segments) => write!(
+                f,
+                "{}, {} PT_LOAD segments of {} bytes",
+                self.format,
+                segments.len(),
+                segments.iter().map(|(_, size)| size).sum::<u64>()
+            )?,
+            Memory::Pages { page_size, pages } => write!(
+                f,
+                "{} v{}, {} pages of {page_size} bytes",
+                self.format,
+                self.version,
+                pages.len()
+            )?,
+        }
+        if let Some(release) = &self.release {
+            write!(f, "\nRelease:        {release}")?;
+        }
+        Ok(())
+    }
+}
+
+fn cstr(data: &[u8]) -> String {
+    let len = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..len]).into_owned()
+}
+
+// OSRELEASE in the VMCOREINFO note
+fn vmcoreinfo_release(elf: &elf::Elf, notes: &[u8]) -> Option<String> {
+    let mut notes = notes;
+    while notes.len() >= 12 {
+        let namesz = elf.u32(&notes[0..4]) as usize;
+        let descsz = elf.u32(&notes[4..8]) as usize;
+        let desc_offset = 12 + namesz.next_multiple_of(4);
+        let name = notes.get(12..12 + namesz)?;
+        let desc = notes.get(desc_offset..desc_offset + descsz)?;
+        if cstr(name) == "VMCOREINFO" {
+            return String::from_utf8_lossy(desc)
+                .lines()
+                .find_map(|line| line.strip_prefix("OSRELEASE="))
+                .map(String::from);
+        }
+        notes = notes.get(desc_offset + descsz.next_multiple_of(4)..)?;
+    }
+    None
+}
+
+fn parse_elf(file: &mut File) -> Result<Vmcore, io::Error> {
+    let elf = elf::parse(file)?;
+    if elf.elf_type != elf::ET_CORE {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    let mut release = None;
+    let mut segments = Vec::new();
+    for segment in &elf.segments {
+        match segment.p_type {
+            elf::PT_LOAD if segment.filesz > 0 => segments.push((segment.offset, segment.filesz)),
+            elf::PT_NOTE if release.is_none() => {
+                if segment.offset.saturating_add(segment.filesz) > file.metadata()?.len() {
+                    return Err(io::Error::from(ErrorKind::UnexpectedEof));
+                }
+                let mut notes = vec![0u8; segment.filesz as usize];
+                file.seek(SeekFrom::Start(segment.offset))?;
+                file.read_exact(&mut notes)?;
+                release = vmcoreinfo_release(&elf, &notes);
+            }
+            _ => {}
+        }
+    }
+    if segments.is_empty() {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    Ok(Vmcore {
+        format: "ELF core dump",
+        version: 0,
+        release,
+        memory: Memory::Segments(segments),
+    })
+}
+
+fn parse_kdump<E: ByteOrder>(
+    file: &mut File,
+    header: &[u8],
+    block_size_offset: usize,
+) -> Result<Vmcore, io::Error> {
+    let block_size = E::read_u32(&header[block_size_offset..]) as usize;
+    let sub_hdr_size = E::read_u32(&header[block_size_offset + 4..]) as u64;
+    let bitmap_blocks = E::read_u32(&header[block_size_offset + 8..]) as u64;
+
+    // the 2nd half of the bitmaps marks the dumped pages, each of which
+    // has a page descriptor in order
+    let bitmap_size = bitmap_blocks.saturating_mul(block_size as u64) / 2;
+    let bitmap_offset = sub_hdr_size
+        .saturating_add(1)
+        .saturating_mul(block_size as u64)
+        .saturating_add(bitmap_size);
+    if bitmap_offset.saturating_add(bitmap_size) > file.metadata()?.len() {
+        return Err(io::Error::from(ErrorKind::UnexpectedEof));
+    }
+    let mut bitmap = vec![0u8; bitmap_size as usize];
+    file.seek(SeekFrom::Start(bitmap_offset))?;
+    file.read_exact(&mut bitmap)?;
+    let count: usize = bitmap.iter().map(|b| b.count_ones() as usize).sum();
+
+    // incomplete dumps are truncated, so may be their page descriptors
+    let mut descs = Vec::new();
+    file.seek(SeekFrom::Start(bitmap_offset + bitmap_size))?;
+    Read::by_ref(file)
+        .take((count * PAGE_DESC_SIZE) as u64)
+        .read_to_end(&mut descs)?;
+    let pages = descs
+        .chunks_exact(PAGE_DESC_SIZE)
+        .map(|desc| PageDesc {
+            offset: E::read_u64(&desc[0..8]),
+            size: E::read_u32(&desc[8..12]),
+            flags: E::read_u32(&desc[12..16]),
+        })
+        .collect();
+
+    Ok(Vmcore {
+        format: "kdump compressed dump",
+        version: E::read_u32(&header[8..12]),
+        release: Some(cstr(&header[KDUMP_RELEASE..KDUMP_RELEASE + 65])),
+        memory: Memory::Pages {
+            page_size: block_size,
+            pages,
+        },
+    })
+}
+
+fn is_block_size(size: u32) -> bool {
+    size.is_power_of_two() && (1024..=65536).contains(&size)
+}
+
+pub fn parse(file: &mut File) -> Result<Vmcore, io::Error> {
+    let mut header = [0u8; KDUMP_HEADER_SIZE];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header[..KDUMP_SIGNATURE.len()])?;
+    if header[..KDUMP_SIGNATURE.len()] != *KDUMP_SIGNATURE {
+        return parse_elf(file);
+    }
+    file.read_exact(&mut header[KDUMP_SIGNATURE.len()..])?;
+
+    // the header is in the byte order and the layout of the dumped machine
+    for offset in [KDUMP_BLOCK_SIZE_64, KDUMP_BLOCK_SIZE_32] {
+        if is_block_size(LittleEndian::read_u32(&header[offset..])) {
+            return parse_kdump::<LittleEndian>(file, &header, offset);
+        }
+        if is_block_size(BigEndian::read_u32(&header[offset..])) {
+            return parse_kdump::<BigEndian>(file, &header, offset);
+        }
+    }
+    Err(io::Error::from(ErrorKind::InvalidData))
+}
+
+fn decompress_page(data: &[u8], flags: u32, page_size: usize) -> Result<Vec<u8>, io::Error> {
+    let page = if flags & DUMP_DH_COMPRESSED_ZLIB != 0 {
+        // no more than a byte beyond the page is inflated to tell it's too long
+        let mut page = Vec::with_capacity(page_size);
+        ZlibDecoder::new(data)
+            .take(page_size as u64 + 1)
+            .read_to_end(&mut page)?;
+        page
+    } else if flags & DUMP_DH_COMPRESSED_LZO != 0 {
+        let lzo = minilzo_rs::LZO::init().map_err(|_| io::Error::from(ErrorKind::Other))?;
+        lzo.decompress_safe(data, page_size)
+            .map_err(|_| io::Error::from(ErrorKind::InvalidData))?
+    } else if flags & DUMP_DH_COMPRESSED_ZSTD != 0 {
+        zstd::bulk::decompress(data, page_size)?
+    } else if flags & DUMP_DH_COMPRESSED_SNAPPY != 0 {
+        return Err(io::Error::from(ErrorKind::Unsupported));
+    } else {
+        data.to_vec()
+    };
+
+    if page.len() != page_size {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+    Ok(page)
+}
+
+fn read_page(
+    file: &mut File,
+    desc: &PageDesc,
+    data: &mut Vec<u8>,
+    page_size: usize,
+) -> Result<Vec<u8>, io::Error> {
+    // the data of a page is never larger than the page itself
+    if desc.size as usize > page_size {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+    data.resize(desc.size as usize, 0);
+    file.seek(SeekFrom::Start(desc.offset))?;
+    file.read_exact(data)?;
+    decompress_page(data, desc.flags, page_size)
+}
+
+impl Vmcore {
+    // the size of the captured memory in bytes
+    fn size(&self) -> u64 {
+        match &self.memory {
+            Memory::Segments(segments) => segments.iter().map(|(_, size)| size).sum(),
+            Memory::Pages { page_size, pages } => (pages.len() * page_size) as u64,
+        }
+    }
+
+    // write the captured memory in [start, end), as concatenated in the
+    // order of physical addresses
+    fn write_memory(
+        &self,
+        file: &mut File,
+        start: u64,
+        end: u64,
+        dst: &mut impl Write,
+    ) -> Result<(), io::Error> {
+        match &self.memory {
+            Memory::Segments(segments) => {
+                let mut position = 0;
+                for &(offset, size) in segments {
+                    let from = start.max(position);
+                    let to = end.min(position + size);
+                    if from < to {
+                        file.seek(SeekFrom::Start(offset + from - position))?;
+                        io::copy(&mut Read::by_ref(file).take(to - from), dst)?;
+                    }
+                    position += size;
+                }
+            }
+            Memory::Pages { page_size, pages } => {
+                let page_size = *page_size;
+                let first = (start / page_size as u64) as usize;
+                let last = end.div_ceil(page_size as u64) as usize;
+                let mut data = Vec::new();
+                let mut failed = 0;
+                for desc in pages.get(first..last.min(pages.len())).unwrap_or_default() {
+                    // the pages which can't be decoded are left zeroed,
+                    // so that the following ones are still at the right place
+                    let page = read_page(file, desc, &mut data, page_size).unwrap_or_else(|err| {
+                        trace!("Failed to read page at 0x{:x}: {err}", desc.offset);
+                        failed += 1;
+                        vec![0u8; page_size]
+                    });
+                    dst.write_all(&page)?;
+                }
+                if failed > 0 {
+                    debug!("Failed to read {failed} pages of {}", last - first);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // scan the captured memory in windows till found, rather than copying
+    // all of it at once, as only the window containing the kernel is needed
+    pub fn scan<T>(
+        &self,
+        file: &mut File,
+        scan: fn(&mut File) -> Result<T, io::Error>,
+    ) -> Result<T, io::Error> {
+        self.scan_windows(file, scan, WINDOW_SIZE, WINDOW_OVERLAP)
+    }
+
+    fn scan_windows<T>(
+        &self,
+        file: &mut File,
+        scan: fn(&mut File) -> Result<T, io::Error>,
+        window: u64,
+        overlap: u64,
+    ) -> Result<T, io::Error> {
+        let size = self.size();
+        let mut start = 0;
+        loop {
+            let end = size.min(start + window);
+            let mut memory = tempfile::tempfile()?;
+            let mut writer = BufWriter::new(&mut memory);
+            self.write_memory(file, start, end, &mut writer)?;
+            writer.flush()?;
+            drop(writer);
+            debug!("Scanning {} bytes of memory at 0x{start:x}", end - start);
+            let scanned = scan(&mut memory);
+            if scanned.is_ok() || end == size {
+                return scanned;
+            }
+            start = end - overlap;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan_marker(file: &mut File) -> Result<Vec<u8>, io::Error> {
+        let mut data = Vec::new();
+        file.rewind()?;
+        file.read_to_end(&mut data)?;
+        if !data.windows(6).any(|window| window == b"MARKER") {
+            return Err(io::Error::from(ErrorKind::NotFound));
+        }
+        Ok(data)
+    }
+
+    #[test]
+    fn test_scan_windows() {
+        // the marker spans 2 segments and the boundary of the first 2 windows
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(b"0123456789abcMARxxxxKER456789abcdef")
+            .unwrap();
+        let vmcore = Vmcore {
+            format: "ELF core dump",
+            version: 0,
+            release: None,
+            memory: Memory::Segments(vec![(0, 16), (20, 15)]),
+        };
+        assert_eq!(vmcore.size(), 31);
+
+        let scanned = vmcore.scan_windows(&mut file, scan_marker, 16, 8).unwrap();
+        assert_eq!(scanned, b"89abcMARKER45678");
+        let scanned = vmcore.scan_windows(&mut file, scan_marker, 64, 8).unwrap();
+        assert_eq!(scanned, b"0123456789abcMARKER456789abcdef");
+        // not found if the overlap is shorter than the marker
+        assert!(vmcore.scan_windows(&mut file, scan_marker, 8, 1).is_err());
+    }
+
+    #[test]
+    fn test_decompress_page() {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&[0x55; 4096 * 2]).unwrap();
+        let data = encoder.finish().unwrap();
+        let page = decompress_page(&data, DUMP_DH_COMPRESSED_ZLIB, 8192).unwrap();
+        assert_eq!(page, [0x55; 8192]);
+        // the page inflated beyond its size
+        assert!(decompress_page(&data, DUMP_DH_COMPRESSED_ZLIB, 4096).is_err());
+    }
+}
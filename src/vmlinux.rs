@@ -0,0 +1,90 @@
+use argh::FromArgs;
+use std::{
+    fs::File,
+    io::{self, Read, Seek},
+};
+
+// the banner printed at the beginning of kernel log
+pub const LINUX_BANNER: &str = r"Linux version [0-9]";
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "extract-vmlinux",
+    description = "extract the decompressed vmlinux from a kernel image"
+)]
+pub struct ExtractVmlinuxArgs {
+    #[argh(positional, description = "kernel image")]
+    image: String,
+
+    #[argh(
+        option,
+        short = 'o',
+        description = "output file of vmlinux, default to standard output"
+    )]
+    output: Option<String>,
+}
+
+pub fn is_vmlinux(file: &mut File) -> bool {
+    let mut header = [0u8; 64];
+    if file.rewind().is_err() || file.read_exact(&mut header).is_err() {
+        return false;
+    }
+
+    header.starts_with(b"\x7fELF")
+        // arm64 Image
+        || &header[0x38..0x3c] == b"ARM\x64"
+        // RISC-V Image
+        || &header[0x38..0x3c] == b"RSC\x05"
+        // other raw kernel images
+        || crate::search_regex(file, LINUX_BANNER).is_ok()
+}
+
+pub fn run(args: ExtractVmlinuxArgs) {
+    let mut file = match File::open(&args.image) {
+        Ok(image) => image,
+        Err(err) => {
+            eprintln!("Failed to open file {}: {err}", &args.image);
+            return;
+        }
+    };
+
+    let mut vmlinux = match crate::extract_vmlinux(&mut file) {
+        Ok(vmlinux) => vmlinux,
+        Err(_) => {
+            eprintln!("{}: Cannot find vmlinux.", env!("CARGO_BIN_NAME"));
+            return;
+        }
+    };
+
+    let result = vmlinux.rewind().and_then(|_| match &args.output {
+        Some(output) => File::create(output).and_then(|mut dst| io::copy(&mut vmlinux, &mut dst)),
+        None => io::copy(&mut vmlinux, &mut io::stdout()),
+    });
+    if let Err(err) = result {
+        eprintln!("Failed to write vmlinux: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_is_vmlinux_riscv() {
+        // Documentation/riscv/boot-image-header.rst
+        let mut header = [0u8; 64];
+        header[0x30..0x38].copy_from_slice(b"RISCV\0\0\0");
+        header[0x38..0x3c].copy_from_slice(b"RSC\x05");
+
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&header).unwrap();
+        assert!(is_vmlinux(&mut file));
+
+        header[0x38..0x3c].fill(0);
+        file.rewind().unwrap();
+        file.write_all(&header).unwrap();
+        assert!(!is_vmlinux(&mut file));
+    }
+}
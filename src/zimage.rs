@@ -0,0 +1,99 @@
+use byteorder::{ByteOrder, LittleEndian};
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+};
+
+// the header of ARM zImage locates at offset 0x24:
+// magic number, start address and end address
+const ZIMAGE_HEADER_OFFSET: u64 = 0x24;
+const ZIMAGE_MAGIC: u32 = 0x016f2818;
+
+pub struct ZImage {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl ZImage {
+    pub fn size(&self) -> u64 {
+        (self.end - self.start) as u64
+    }
+}
+
+pub fn parse(file: &mut File) -> Result<ZImage, io::Error> {
+    let mut header = [0u8; 12];
+    file.seek(SeekFrom::Start(ZIMAGE_HEADER_OFFSET))?;
+    file.read_exact(&mut header)?;
+
+    // BE8 kernels swap the magic number and addresses when linking,
+    // so they can be read in little endian just like LE kernels,
+    // while the older big-endian ones store them in big endian.
+    let magic = LittleEndian::read_u32(&header[0..4]);
+    let (start, end) = if magic == ZIMAGE_MAGIC {
+        (
+            LittleEndian::read_u32(&header[4..8]),
+            LittleEndian::read_u32(&header[8..12]),
+        )
+    } else if magic == ZIMAGE_MAGIC.swap_bytes() {
+        (
+            LittleEndian::read_u32(&header[4..8]).swap_bytes(),
+            LittleEndian::read_u32(&header[8..12]).swap_bytes(),
+        )
+    } else {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    };
+
+    // the image can't be larger than the file, but it can be smaller
+    // if there is something appended, e.g. device tree blob
+    if end <= start || (end - start) as u64 > file.metadata()?.len() {
+        return Err(io::Error::from(ErrorKind::InvalidData));
+    }
+
+    Ok(ZImage { start, end })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn zimage_with_header(words: [u32; 4], big_endian: bool) -> File {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&[0u8; ZIMAGE_HEADER_OFFSET as usize])
+            .unwrap();
+        for word in words {
+            if big_endian {
+                file.write_all(&word.to_be_bytes()).unwrap();
+            } else {
+                file.write_all(&word.to_le_bytes()).unwrap();
+            }
+        }
+        file.write_all(&[0u8; 0x1000]).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_parse_zimage_le() {
+        let mut file = zimage_with_header([ZIMAGE_MAGIC, 0, 0x800, 0x04030201], false);
+        let zimage = parse(&mut file).unwrap();
+        assert_eq!(zimage.size(), 0x800);
+    }
+
+    #[test]
+    fn test_parse_zimage_be() {
+        let mut file = zimage_with_header([ZIMAGE_MAGIC, 0x100, 0x900, 0x04030201], true);
+        let zimage = parse(&mut file).unwrap();
+        assert_eq!(zimage.start, 0x100);
+        assert_eq!(zimage.size(), 0x800);
+    }
+
+    #[test]
+    fn test_parse_zimage_invalid() {
+        // end address beyond the end of file
+        let mut file = zimage_with_header([ZIMAGE_MAGIC, 0, 0x10000, 0x04030201], false);
+        assert!(parse(&mut file).is_err());
+
+        let mut file = zimage_with_header([0, 0, 0x800, 0x04030201], false);
+        assert!(parse(&mut file).is_err());
+    }
+}
@@ -1,4 +1,7 @@
 use assert_cmd::Command;
+use flate2::{write::GzEncoder, Compression, Crc};
+use std::{collections::BTreeMap, fs, io::Write};
+use tempfile::NamedTempFile;
 use time::Instant;
 
 const BIN_NAME: &str = env!("CARGO_BIN_EXE_ikconfig");
@@ -66,6 +69,2477 @@ fn test_extract_vmlinux_zstd() {
     test_extract_vmlinux(PATH_VMLINUX_ZSTD, "ZSTD");
 }
 
+fn extract_config(path: &str) -> String {
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg(path)
+        .output()
+        .unwrap();
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn extract_config_with_metadata(path: &std::path::Path) -> (String, String) {
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg(path)
+        .output()
+        .unwrap();
+
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+fn ikcfg_gzip(config: &str) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(b"IKCFG_ST".to_vec(), Compression::default());
+    encoder.write_all(config.as_bytes()).unwrap();
+    let mut data = encoder.finish().unwrap();
+    data.extend_from_slice(b"IKCFG_ED");
+    data
+}
+
+fn write_tempfile(data: &[u8]) -> NamedTempFile {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(data).unwrap();
+    file
+}
+
+#[test]
+fn test_extract_zimage() {
+    let kernel = fs::read(PATH_VMLINUX_GZIP).unwrap();
+
+    // zImage header at 0x24: magic number, start and end address
+    let mut zimage = vec![0u8; 0x24];
+    zimage.extend_from_slice(&0x016f2818u32.to_le_bytes());
+    zimage.extend_from_slice(&0u32.to_le_bytes());
+    zimage.extend_from_slice(&(0x30 + kernel.len() as u32).to_le_bytes());
+    zimage.extend_from_slice(&0x04030201u32.to_le_bytes());
+    zimage.extend_from_slice(&kernel);
+
+    // the appended data beyond the end address must be ignored
+    zimage.extend_from_slice(&0xd00dfeedu32.to_be_bytes());
+    zimage.extend_from_slice(&ikcfg_gzip("CONFIG_APPENDED=y\n"));
+
+    let file = write_tempfile(&zimage);
+    let configs = extract_config(file.path().to_str().unwrap());
+    assert!(configs.contains("CONFIG_IKCONFIG=y"));
+    assert!(!configs.contains("CONFIG_APPENDED=y"));
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn uimage(image_type: u8, comp: u8, data: &[u8]) -> Vec<u8> {
+    let mut crc = Crc::new();
+    crc.update(data);
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&0x27051956u32.to_be_bytes());
+    header.extend_from_slice(&0u32.to_be_bytes());
+    header.extend_from_slice(&1668720923u32.to_be_bytes());
+    header.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    header.extend_from_slice(&0x80008000u32.to_be_bytes());
+    header.extend_from_slice(&0x80008000u32.to_be_bytes());
+    header.extend_from_slice(&crc.sum().to_be_bytes());
+    // Linux, ARM
+    header.extend_from_slice(&[5, 2, image_type, comp]);
+    let mut name = b"Linux-6.1.0".to_vec();
+    name.resize(32, 0);
+    header.extend_from_slice(&name);
+
+    let mut crc = Crc::new();
+    crc.update(&header);
+    header[4..8].copy_from_slice(&crc.sum().to_be_bytes());
+
+    header.extend_from_slice(data);
+    header
+}
+
+#[test]
+fn test_extract_uimage() {
+    let kernel = fs::read(PATH_VMLINUX_GZIP).unwrap();
+    let file = write_tempfile(&uimage(2, 0, &kernel));
+
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert!(configs.contains("CONFIG_KERNEL_GZIP=y"));
+    assert!(metadata.contains("Image Name:   Linux-6.1.0"));
+    assert!(metadata.contains("ARM Linux Kernel Image (uncompressed)"));
+}
+
+#[test]
+fn test_extract_uimage_multi_gzip() {
+    // the first file is kernel and the second is ramdisk
+    let kernel = gzip(&ikcfg_gzip("CONFIG_UIMAGE=y\n"));
+    let ramdisk = gzip(&ikcfg_gzip("CONFIG_RAMDISK=y\n"));
+    let mut data = Vec::new();
+    data.extend_from_slice(&(kernel.len() as u32).to_be_bytes());
+    data.extend_from_slice(&(ramdisk.len() as u32).to_be_bytes());
+    data.extend_from_slice(&0u32.to_be_bytes());
+    data.extend_from_slice(&kernel);
+    data.resize((data.len() + 3) & !3, 0);
+    data.extend_from_slice(&ramdisk);
+
+    let file = write_tempfile(&uimage(4, 1, &data));
+    let configs = extract_config(file.path().to_str().unwrap());
+    assert_eq!(configs, "CONFIG_UIMAGE=y\n");
+}
+
+#[test]
+fn test_extract_uimage_bad_crc() {
+    let mut image = uimage(2, 1, &gzip(&ikcfg_gzip("CONFIG_UIMAGE=y\n")));
+    let last = image.len() - 1;
+    image[last] ^= 0xff;
+
+    let file = write_tempfile(&image);
+    let (configs, errors) = extract_config_with_metadata(file.path());
+    assert!(configs.is_empty());
+    assert!(errors.contains("uImage data checksum mismatch"));
+}
+
+#[derive(Default)]
+struct FdtBuilder {
+    structure: Vec<u8>,
+    strings: Vec<u8>,
+}
+
+impl FdtBuilder {
+    fn begin_node(&mut self, name: &str) -> &mut Self {
+        self.structure.extend_from_slice(&1u32.to_be_bytes());
+        self.structure.extend_from_slice(name.as_bytes());
+        self.structure.push(0);
+        self.structure.resize((self.structure.len() + 3) & !3, 0);
+        self
+    }
+
+    fn end_node(&mut self) -> &mut Self {
+        self.structure.extend_from_slice(&2u32.to_be_bytes());
+        self
+    }
+
+    fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+        self.structure.extend_from_slice(&3u32.to_be_bytes());
+        self.structure
+            .extend_from_slice(&(value.len() as u32).to_be_bytes());
+        self.structure
+            .extend_from_slice(&(self.strings.len() as u32).to_be_bytes());
+        self.structure.extend_from_slice(value);
+        self.structure.resize((self.structure.len() + 3) & !3, 0);
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        self
+    }
+
+    fn prop_str(&mut self, name: &str, value: &str) -> &mut Self {
+        self.prop(name, format!("{value}\0").as_bytes())
+    }
+
+    fn prop_u32(&mut self, name: &str, value: u32) -> &mut Self {
+        self.prop(name, &value.to_be_bytes())
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        self.structure.extend_from_slice(&9u32.to_be_bytes());
+
+        // header, memory reservation block, structure block and strings block
+        let off_mem_rsvmap = 40u32;
+        let off_dt_struct = off_mem_rsvmap + 16;
+        let off_dt_strings = off_dt_struct + self.structure.len() as u32;
+        let totalsize = off_dt_strings + self.strings.len() as u32;
+
+        let mut fdt = Vec::new();
+        for field in [
+            0xd00dfeed,
+            totalsize,
+            off_dt_struct,
+            off_dt_strings,
+            off_mem_rsvmap,
+            17,
+            16,
+            0,
+            self.strings.len() as u32,
+            self.structure.len() as u32,
+        ] {
+            fdt.extend_from_slice(&field.to_be_bytes());
+        }
+        fdt.extend_from_slice(&[0u8; 16]);
+        fdt.extend_from_slice(&self.structure);
+        fdt.extend_from_slice(&self.strings);
+        fdt
+    }
+}
+
+#[test]
+fn test_extract_fit() {
+    let external = ikcfg_gzip("CONFIG_FIT_EXTERNAL=y\n");
+    let mut fit = FdtBuilder::default()
+        .begin_node("")
+        .prop_str("description", "FIT image")
+        .begin_node("images")
+        .begin_node("kernel-1")
+        .prop_str("description", "Linux kernel")
+        .prop("data", &gzip(&ikcfg_gzip("CONFIG_FIT_GZIP=y\n")))
+        .prop_str("type", "kernel")
+        .prop_str("compression", "gzip")
+        .end_node()
+        .begin_node("kernel-2")
+        .prop_u32("data-offset", 0)
+        .prop_u32("data-size", external.len() as u32)
+        .prop_str("type", "kernel")
+        .prop_str("compression", "none")
+        .end_node()
+        .begin_node("fdt-1")
+        .prop("data", &ikcfg_gzip("CONFIG_FIT_FDT=y\n"))
+        .prop_str("type", "flat_dt")
+        .end_node()
+        .end_node()
+        .end_node()
+        .finish();
+    fit.resize((fit.len() + 3) & !3, 0);
+    fit.extend_from_slice(&external);
+
+    let file = write_tempfile(&fit);
+    let configs = extract_config(file.path().to_str().unwrap());
+    // each config is preceded by the kernel it's found in
+    assert!(configs.starts_with("# FIT kernel:   /images/kernel-1 (Linux kernel), "));
+    assert!(configs
+        .contains("compression: gzip\nCONFIG_FIT_GZIP=y\n# FIT kernel:   /images/kernel-2, "));
+    assert!(configs.ends_with("compression: none\nCONFIG_FIT_EXTERNAL=y\n"));
+}
+
+// Android 11, patch level 2021-03
+const ANDROID_OS_VERSION: u32 = ((11 << 14) << 11) | (21 << 4) | 3;
+
+#[test]
+fn test_extract_android_boot_v2() {
+    let kernel = gzip(&ikcfg_gzip("CONFIG_ANDROID_BOOT=y\n"));
+    let page_size = 2048;
+
+    let mut header = vec![0u8; 1660];
+    header[..8].copy_from_slice(b"ANDROID!");
+    header[8..12].copy_from_slice(&(kernel.len() as u32).to_le_bytes());
+    header[36..40].copy_from_slice(&(page_size as u32).to_le_bytes());
+    header[40..44].copy_from_slice(&2u32.to_le_bytes());
+    header[44..48].copy_from_slice(&ANDROID_OS_VERSION.to_le_bytes());
+    header[48..55].copy_from_slice(b"generic");
+    header[64..84].copy_from_slice(b"console=ttyS0,115200");
+
+    let mut image = header;
+    image.resize(page_size, 0);
+    image.extend_from_slice(&kernel);
+    image.resize(image.len().div_ceil(page_size) * page_size, 0);
+
+    let file = write_tempfile(&image);
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert_eq!(configs, "CONFIG_ANDROID_BOOT=y\n");
+    assert!(metadata.contains("Android boot image v2, page size 2048"));
+    assert!(metadata.contains("OS version:   11.0.0"));
+    assert!(metadata.contains("Patch level:  2021-03"));
+    assert!(metadata.contains("Command line: console=ttyS0,115200"));
+}
+
+#[test]
+fn test_extract_android_boot_v4() {
+    let kernel = fs::read(PATH_VMLINUX_LZ4).unwrap();
+
+    let mut header = vec![0u8; 1584];
+    header[..8].copy_from_slice(b"ANDROID!");
+    header[8..12].copy_from_slice(&(kernel.len() as u32).to_le_bytes());
+    header[16..20].copy_from_slice(&ANDROID_OS_VERSION.to_le_bytes());
+    header[20..24].copy_from_slice(&1584u32.to_le_bytes());
+    header[40..44].copy_from_slice(&4u32.to_le_bytes());
+    header[44..56].copy_from_slice(b"androidboot.");
+
+    let mut image = header;
+    image.resize(4096, 0);
+    image.extend_from_slice(&kernel);
+
+    let file = write_tempfile(&image);
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert!(configs.contains("CONFIG_KERNEL_LZ4=y"));
+    assert!(metadata.contains("Android boot image v4, page size 4096"));
+    assert!(metadata.contains("Command line: androidboot."));
+}
+
+#[test]
+fn test_extract_android_vendor_boot() {
+    // vendor ramdisk containing configs.ko
+    let ramdisk = gzip(&ikcfg_gzip("CONFIG_VENDOR_BOOT=m\n"));
+    let page_size = 4096;
+
+    let mut header = vec![0u8; 2112];
+    header[..8].copy_from_slice(b"VNDRBOOT");
+    header[8..12].copy_from_slice(&3u32.to_le_bytes());
+    header[12..16].copy_from_slice(&(page_size as u32).to_le_bytes());
+    header[24..28].copy_from_slice(&(ramdisk.len() as u32).to_le_bytes());
+    header[28..40].copy_from_slice(b"androidboot.");
+    header[2080..2086].copy_from_slice(b"vendor");
+    header[2096..2100].copy_from_slice(&2112u32.to_le_bytes());
+
+    let mut image = header;
+    image.resize(page_size, 0);
+    image.extend_from_slice(&ramdisk);
+
+    let file = write_tempfile(&image);
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert_eq!(configs, "CONFIG_VENDOR_BOOT=m\n");
+    assert!(metadata.contains("Android vendor_boot image v3, page size 4096"));
+    assert!(metadata.contains("Name:         vendor"));
+}
+
+fn vblock(body_load_address: u64, bootloader_address: u64, body: &[u8]) -> Vec<u8> {
+    let keyblock_size = 0x4b8u32;
+    let preamble_size = 0xfb48u32;
+
+    let mut keyblock = vec![0u8; keyblock_size as usize];
+    keyblock[..8].copy_from_slice(b"CHROMEOS");
+    keyblock[8..12].copy_from_slice(&2u32.to_le_bytes());
+    keyblock[12..16].copy_from_slice(&1u32.to_le_bytes());
+    keyblock[16..20].copy_from_slice(&keyblock_size.to_le_bytes());
+
+    let mut preamble = vec![0u8; preamble_size as usize];
+    preamble[0..4].copy_from_slice(&preamble_size.to_le_bytes());
+    preamble[32..36].copy_from_slice(&2u32.to_le_bytes());
+    preamble[36..40].copy_from_slice(&2u32.to_le_bytes());
+    preamble[40..44].copy_from_slice(&1u32.to_le_bytes());
+    preamble[48..56].copy_from_slice(&body_load_address.to_le_bytes());
+    preamble[56..64].copy_from_slice(&bootloader_address.to_le_bytes());
+    preamble[64..68].copy_from_slice(&4096u32.to_le_bytes());
+    preamble[88..92].copy_from_slice(&(body.len() as u32).to_le_bytes());
+
+    let mut image = keyblock;
+    image.extend_from_slice(&preamble);
+    image.extend_from_slice(body);
+    image
+}
+
+#[test]
+fn test_extract_chromeos_vblock() {
+    let kernel = fs::read(PATH_VMLINUX_XZ).unwrap();
+    let body_load_address = 0x100000u64;
+
+    // body: kernel, command line, boot params and bootloader
+    let mut body = kernel;
+    body.resize(body.len().div_ceil(4096) * 4096, 0);
+    let bootloader_address = body_load_address + body.len() as u64 + 8192;
+    let cmdline = b"console= loglevel=7 cros_secure";
+    body.extend_from_slice(cmdline);
+    body.resize(body.len() + 4096 * 3 - cmdline.len(), 0);
+
+    let file = write_tempfile(&vblock(body_load_address, bootloader_address, &body));
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert!(configs.contains("CONFIG_KERNEL_XZ=y"));
+    assert!(metadata.contains("keyblock v2.1, preamble v2.2"));
+    assert!(metadata.contains("Kernel version: 1"));
+    assert!(metadata.contains("Command line:   console= loglevel=7 cros_secure"));
+}
+
+#[test]
+fn test_extract_chromeos_vblock_bad_address() {
+    // the command line isn't located by the addresses overflowing
+    let body = gzip(&ikcfg_gzip("CONFIG_CHROMEOS=y\n"));
+    let file = write_tempfile(&vblock(u64::MAX, u64::MAX, &body));
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert_eq!(configs, "CONFIG_CHROMEOS=y\n");
+    assert!(metadata.contains("Command line:   \n"));
+}
+
+#[derive(Default)]
+struct ElfBuilder {
+    sections: Vec<(String, u32, Vec<u8>)>,
+    symbols: Vec<(String, usize, u64)>,
+}
+
+impl ElfBuilder {
+    const SHT_PROGBITS: u32 = 1;
+
+    // add a section and return its index
+    fn section(&mut self, name: &str, sh_type: u32, data: &[u8]) -> usize {
+        self.sections
+            .push((name.to_string(), sh_type, data.to_vec()));
+        self.sections.len()
+    }
+
+    fn symbol(&mut self, name: &str, shndx: usize, value: u64) -> &mut Self {
+        self.symbols.push((name.to_string(), shndx, value));
+        self
+    }
+
+    // ELF64 relocatable file in little endian, like a kernel module
+    fn build(&self) -> Vec<u8> {
+        let mut strtab = vec![0u8];
+        let mut symtab = vec![0u8; 24];
+        for (name, shndx, value) in &self.symbols {
+            symtab.extend_from_slice(&(strtab.len() as u32).to_le_bytes());
+            // STB_GLOBAL, STT_OBJECT
+            symtab.extend_from_slice(&[0x11, 0]);
+            symtab.extend_from_slice(&(*shndx as u16).to_le_bytes());
+            symtab.extend_from_slice(&value.to_le_bytes());
+            symtab.extend_from_slice(&0u64.to_le_bytes());
+            strtab.extend_from_slice(name.as_bytes());
+            strtab.push(0);
+        }
+
+        let mut sections = self.sections.clone();
+        let symtab_index = sections.len() + 1;
+        sections.push((".symtab".to_string(), 2, symtab));
+        sections.push((".strtab".to_string(), 3, strtab));
+        let mut shstrtab = vec![0u8];
+        let mut names = Vec::new();
+        for (name, _, _) in &sections {
+            names.push(shstrtab.len() as u32);
+            shstrtab.extend_from_slice(name.as_bytes());
+            shstrtab.push(0);
+        }
+        names.push(shstrtab.len() as u32);
+        shstrtab.extend_from_slice(b".shstrtab\0");
+        sections.push((".shstrtab".to_string(), 3, shstrtab));
+
+        let mut elf = vec![0u8; 64];
+        let mut headers = vec![0u8; 64];
+        for (index, (name, (_, sh_type, data))) in names.iter().zip(&sections).enumerate() {
+            elf.resize((elf.len() + 7) & !7, 0);
+            let mut header = [0u8; 64];
+            header[0..4].copy_from_slice(&name.to_le_bytes());
+            header[4..8].copy_from_slice(&sh_type.to_le_bytes());
+            header[24..32].copy_from_slice(&(elf.len() as u64).to_le_bytes());
+            header[32..40].copy_from_slice(&(data.len() as u64).to_le_bytes());
+            if index + 1 == symtab_index {
+                header[40..44].copy_from_slice(&(symtab_index as u32 + 1).to_le_bytes());
+                header[56..64].copy_from_slice(&24u64.to_le_bytes());
+            }
+            headers.extend_from_slice(&header);
+            elf.extend_from_slice(data);
+        }
+        elf.resize((elf.len() + 7) & !7, 0);
+        let shoff = elf.len() as u64;
+        elf.extend_from_slice(&headers);
+
+        elf[..16].copy_from_slice(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+        // ET_REL, EM_X86_64
+        elf[16..18].copy_from_slice(&1u16.to_le_bytes());
+        elf[18..20].copy_from_slice(&62u16.to_le_bytes());
+        elf[20..24].copy_from_slice(&1u32.to_le_bytes());
+        elf[40..48].copy_from_slice(&shoff.to_le_bytes());
+        elf[52..54].copy_from_slice(&64u16.to_le_bytes());
+        elf[58..60].copy_from_slice(&64u16.to_le_bytes());
+        elf[60..62].copy_from_slice(&(sections.len() as u16 + 1).to_le_bytes());
+        elf[62..64].copy_from_slice(&(sections.len() as u16).to_le_bytes());
+        elf
+    }
+}
+
+#[test]
+fn test_extract_elf_symbols() {
+    // the config data pointed by the symbols isn't the first one in .rodata
+    let mut rodata = ikcfg_gzip("CONFIG_DECOY=y\n");
+    let start = rodata.len() + "IKCFG_ST".len();
+    rodata.extend_from_slice(&ikcfg_gzip("CONFIG_SYMBOL=y\n"));
+    let end = rodata.len() - "IKCFG_ED".len();
+
+    let mut elf = ElfBuilder::default();
+    let shndx = elf.section(".rodata", ElfBuilder::SHT_PROGBITS, &rodata);
+    elf.symbol("kernel_config_data", shndx, start as u64)
+        .symbol("kernel_config_data_end", shndx, end as u64);
+
+    let file = write_tempfile(&elf.build());
+    let configs = extract_config(file.path().to_str().unwrap());
+    assert_eq!(configs, "CONFIG_SYMBOL=y\n");
+}
+
+fn configs_ko(config: &str) -> Vec<u8> {
+    let rodata = ikcfg_gzip(config);
+    let mut elf = ElfBuilder::default();
+    let shndx = elf.section(".rodata", ElfBuilder::SHT_PROGBITS, &rodata);
+    elf.symbol("kernel_config_data", shndx, "IKCFG_ST".len() as u64)
+        .symbol(
+            "kernel_config_data_end",
+            shndx,
+            (rodata.len() - "IKCFG_ED".len()) as u64,
+        );
+    elf.build()
+}
+
+#[test]
+fn test_extract_configs_ko_xz() {
+    let module = lzma::compress(&configs_ko("CONFIG_IKCONFIG=m\n"), 6).unwrap();
+    let file = write_tempfile(&module);
+    let configs = extract_config(file.path().to_str().unwrap());
+    assert_eq!(configs, "CONFIG_IKCONFIG=m\n");
+}
+
+#[test]
+fn test_extract_modules_dir() {
+    let modules_dir = tempfile::tempdir().unwrap();
+    let kernel_dir = modules_dir.path().join("kernel/kernel");
+    fs::create_dir_all(&kernel_dir).unwrap();
+    fs::write(modules_dir.path().join("modules.dep"), b"").unwrap();
+    fs::write(
+        kernel_dir.join("configs.ko.zst"),
+        zstd::encode_all(&configs_ko("CONFIG_IKCONFIG=m\n")[..], 3).unwrap(),
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--modules-dir")
+        .arg(modules_dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(output.stdout, b"CONFIG_IKCONFIG=m\n");
+    let metadata = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(metadata.contains("kernel/kernel/configs.ko.zst"));
+}
+
+fn fake_root(release: &str) -> tempfile::TempDir {
+    let root = tempfile::tempdir().unwrap();
+    fs::create_dir_all(root.path().join("proc/sys/kernel")).unwrap();
+    fs::create_dir_all(root.path().join("boot")).unwrap();
+    fs::create_dir_all(root.path().join("lib/modules").join(release)).unwrap();
+    fs::write(
+        root.path().join("proc/sys/kernel/osrelease"),
+        format!("{release}\n"),
+    )
+    .unwrap();
+    root
+}
+
+fn extract_running_config(root: &std::path::Path) -> (String, String) {
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--running")
+        .arg("--root")
+        .arg(root)
+        .output()
+        .unwrap();
+
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn test_extract_running_proc_config() {
+    let root = fake_root("6.1.0-rc5+");
+    fs::write(root.path().join("proc/config.gz"), gzip(b"CONFIG_PROC=y\n")).unwrap();
+    fs::write(
+        root.path().join("boot/config-6.1.0-rc5+"),
+        b"CONFIG_BOOT=y\n",
+    )
+    .unwrap();
+
+    let (configs, metadata) = extract_running_config(root.path());
+    assert_eq!(configs, "CONFIG_PROC=y\n");
+    assert!(metadata.contains("proc/config.gz"));
+}
+
+#[test]
+fn test_extract_running_boot_config() {
+    let root = fake_root("6.1.0-rc5+");
+    fs::write(
+        root.path().join("boot/config-6.1.0-rc5+"),
+        b"CONFIG_BOOT=y\n",
+    )
+    .unwrap();
+
+    let (configs, metadata) = extract_running_config(root.path());
+    assert_eq!(configs, "CONFIG_BOOT=y\n");
+    assert!(metadata.contains("boot/config-6.1.0-rc5+"));
+}
+
+#[test]
+fn test_extract_running_kernel_image() {
+    let root = fake_root("6.1.0-rc5+");
+    fs::copy(
+        PATH_VMLINUX_ZSTD,
+        root.path().join("lib/modules/6.1.0-rc5+/vmlinuz"),
+    )
+    .unwrap();
+
+    let (configs, metadata) = extract_running_config(root.path());
+    assert!(configs.contains("CONFIG_KERNEL_ZSTD=y"));
+    assert!(metadata.contains("lib/modules/6.1.0-rc5+/vmlinuz"));
+
+    // nothing available
+    fs::remove_file(root.path().join("lib/modules/6.1.0-rc5+/vmlinuz")).unwrap();
+    let (configs, errors) = extract_running_config(root.path());
+    assert!(configs.is_empty());
+    assert!(errors.contains("Cannot find the config of running kernel"));
+}
+
+#[test]
+fn test_extract_running_configs_ko() {
+    let root = fake_root("6.1.0-rc5+");
+    let kernel_dir = root.path().join("lib/modules/6.1.0-rc5+/kernel/kernel");
+    fs::create_dir_all(&kernel_dir).unwrap();
+    fs::write(
+        kernel_dir.join("configs.ko"),
+        configs_ko("CONFIG_IKCONFIG=m\n"),
+    )
+    .unwrap();
+
+    let (configs, metadata) = extract_running_config(root.path());
+    assert_eq!(configs, "CONFIG_IKCONFIG=m\n");
+    assert!(metadata.contains("kernel/kernel/configs.ko"));
+}
+
+#[test]
+fn test_extract_root_without_running() {
+    let root = fake_root("6.1.0-rc5+");
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--root")
+        .arg(root.path())
+        .arg(PATH_VMLINUX_GZIP)
+        .output()
+        .unwrap();
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Please specify --root only with --running"));
+}
+
+#[test]
+fn test_extract_vmlinux_command() {
+    let dir = tempfile::tempdir().unwrap();
+    let vmlinux = dir.path().join("vmlinux");
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("extract-vmlinux")
+        .arg(PATH_VMLINUX_XZ)
+        .arg("-o")
+        .arg(&vmlinux)
+        .assert()
+        .success();
+
+    let data = fs::read(&vmlinux).unwrap();
+    assert!(data.starts_with(b"\x7fELF"));
+    let configs = extract_config(vmlinux.to_str().unwrap());
+    assert!(configs.contains("CONFIG_KERNEL_XZ=y"));
+}
+
+#[test]
+fn test_extract_vmlinux_command_invalid() {
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("extract-vmlinux")
+        .arg("tests/data/config.gz")
+        .output()
+        .unwrap();
+    assert!(output.stdout.is_empty());
+    let errors = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(errors.contains("Cannot find vmlinux"));
+}
+
+fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    for (path, data) in files {
+        let mut header = [0u8; 512];
+        header[..path.len()].copy_from_slice(path.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+        header[136..148].copy_from_slice(b"00000000000\0");
+        header[156] = b'0';
+        header[257..265].copy_from_slice(b"ustar  \0");
+        // checksum is calculated with the checksum field filled by spaces
+        header[148..156].fill(b' ');
+        let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+
+        archive.extend_from_slice(&header);
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().div_ceil(512) * 512, 0);
+    }
+    archive.resize(archive.len() + 1024, 0);
+    archive
+}
+
+const KERNEL_H: &[u8] = b"#ifndef _LINUX_KERNEL_H\n#define _LINUX_KERNEL_H\n#endif\n";
+
+fn kheaders_data() -> Vec<u8> {
+    let archive = tar(&[
+        ("./include/linux/kernel.h", KERNEL_H),
+        ("./arch/x86/include/asm/page.h", b"#define PAGE_SHIFT 12\n"),
+    ]);
+    lzma::compress(&archive, 6).unwrap()
+}
+
+#[test]
+fn test_extract_headers_kheaders_ko() {
+    let data = kheaders_data();
+    let mut elf = ElfBuilder::default();
+    let shndx = elf.section(".rodata", ElfBuilder::SHT_PROGBITS, &data);
+    elf.symbol("kernel_headers_data", shndx, 0).symbol(
+        "kernel_headers_data_end",
+        shndx,
+        data.len() as u64,
+    );
+    let module = write_tempfile(&elf.build());
+
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("kheaders_data.tar.xz");
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("extract-headers")
+        .arg(module.path())
+        .arg("-o")
+        .arg(&output)
+        .assert()
+        .success();
+    assert_eq!(fs::read(&output).unwrap(), data);
+}
+
+#[test]
+fn test_extract_headers_unpack() {
+    // a raw kernel image without symbols, and with a bogus xz magic ahead
+    let mut image = b"Linux version 6.1.0 \xfd7zXZ\x00\x00\x04".to_vec();
+    image.resize(4096, 0);
+    image.extend_from_slice(&kheaders_data());
+    image.resize(image.len() + 4096, 0);
+    let image = write_tempfile(&image);
+
+    let dir = tempfile::tempdir().unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("extract-headers")
+        .arg(image.path())
+        .arg("-d")
+        .arg(dir.path())
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read(dir.path().join("include/linux/kernel.h")).unwrap(),
+        KERNEL_H
+    );
+    assert_eq!(
+        fs::read(dir.path().join("arch/x86/include/asm/page.h")).unwrap(),
+        b"#define PAGE_SHIFT 12\n"
+    );
+}
+
+#[test]
+fn test_extract_headers_unpack_symlink() {
+    let outside = tempfile::tempdir().unwrap();
+    let target = outside.path().to_str().unwrap();
+    let mut archive = tar(&[
+        ("./evil", b""),
+        ("./evil/passwd", b"root::0:0::/root:/bin/sh\n"),
+        ("./link", b""),
+        ("./link", b"overwritten\n"),
+        ("./include/linux/kernel.h", KERNEL_H),
+    ]);
+    // turn the entries of evil and the first link into symbolic links
+    // to the directory outside and a file in it
+    for (offset, link) in [(0, target.to_string()), (1536, format!("{target}/file"))] {
+        let header = &mut archive[offset..offset + 512];
+        header[156] = b'2';
+        header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        header[148..156].fill(b' ');
+        let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+    }
+    let mut image = b"Linux version 6.1.0 ".to_vec();
+    image.resize(4096, 0);
+    image.extend_from_slice(&lzma::compress(&archive, 6).unwrap());
+    let image = write_tempfile(&image);
+
+    let dir = tempfile::tempdir().unwrap();
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("extract-headers")
+        .arg(image.path())
+        .arg("-d")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    let errors = String::from_utf8(output.stderr).unwrap();
+    assert!(errors.contains("Skipped unsafe path ./evil/passwd"));
+    assert!(errors.contains("Skipped unsafe path ./link"));
+    assert!(!outside.path().join("passwd").exists());
+    assert!(!outside.path().join("file").exists());
+    assert_eq!(
+        fs::read(dir.path().join("include/linux/kernel.h")).unwrap(),
+        KERNEL_H
+    );
+}
+
+#[test]
+fn test_extract_headers_invalid() {
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("extract-headers")
+        .arg(PATH_VMLINUX_RAW)
+        .output()
+        .unwrap();
+    assert!(output.stdout.is_empty());
+    let errors = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(errors.contains("Cannot find kernel headers"));
+}
+
+#[test]
+fn test_extract_initramfs_list() {
+    // the default initramfs generated from usr/default_cpio_list
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("extract-initramfs")
+        .arg("--list")
+        .arg(PATH_VMLINUX_GZIP)
+        .output()
+        .unwrap();
+    let entries = std::str::from_utf8(&output.stdout).unwrap();
+    assert_eq!(
+        entries,
+        "drwxr-xr-x          0 dev\n\
+         crw-------          0 dev/console\n\
+         drwx------          0 root\n"
+    );
+    let errors = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(errors.contains("Found uncompressed initramfs"));
+}
+
+fn newc(files: &[(&str, u32, &[u8])]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let trailer = [("TRAILER!!!", 0, &b""[..])];
+    for (ino, (path, mode, data)) in files.iter().chain(&trailer).enumerate() {
+        archive.extend_from_slice(
+            format!(
+                "070701{ino:08x}{mode:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}",
+                0, 0, 1, 0, data.len(), 0, 0, 0, 0, path.len() + 1, 0
+            )
+            .as_bytes(),
+        );
+        archive.extend_from_slice(path.as_bytes());
+        archive.push(0);
+        archive.resize(archive.len().div_ceil(4) * 4, 0);
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().div_ceil(4) * 4, 0);
+    }
+    archive
+}
+
+#[test]
+fn test_extract_initramfs_elf_symbols() {
+    let cpio = newc(&[
+        ("init", 0o100755, b"#!/bin/sh\nexec /bin/sh\n"),
+        ("bin/sh", 0o120777, b"busybox"),
+    ]);
+    let data = gzip(&cpio);
+    let mut elf = ElfBuilder::default();
+    let ramfs = elf.section(".init.ramfs", ElfBuilder::SHT_PROGBITS, &data);
+    let info = elf.section(
+        ".init.ramfs.info",
+        ElfBuilder::SHT_PROGBITS,
+        &(data.len() as u64).to_le_bytes(),
+    );
+    elf.symbol("__initramfs_start", ramfs, 0)
+        .symbol("__initramfs_size", info, 0);
+    let vmlinux = write_tempfile(&elf.build());
+
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("initramfs.cpio");
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("extract-initramfs")
+        .arg(vmlinux.path())
+        .arg("-o")
+        .arg(&output)
+        .assert()
+        .success();
+    assert_eq!(fs::read(&output).unwrap(), cpio);
+
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("extract-initramfs")
+        .arg(vmlinux.path())
+        .arg("-l")
+        .output()
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&output.stdout).unwrap(),
+        "-rwxr-xr-x         23 init\n\
+         lrwxrwxrwx          7 bin/sh -> busybox\n"
+    );
+    let errors = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(errors.contains("Found gzip initramfs"));
+}
+
+fn dtb(model: &str, compatible: &[u8]) -> Vec<u8> {
+    FdtBuilder::default()
+        .begin_node("")
+        .prop_str("model", model)
+        .prop("compatible", compatible)
+        .prop_u32("#address-cells", 1)
+        .end_node()
+        .finish()
+}
+
+fn extract_dtb(path: &std::path::Path, dir: &std::path::Path) -> String {
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("extract-dtb")
+        .arg(path)
+        .arg("-d")
+        .arg(dir)
+        .output()
+        .unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_extract_dtb_appended() {
+    let kernel = gzip(b"Linux version 6.1.0 (builder@host)");
+    let dtb = dtb(
+        "Raspberry Pi 2 Model B",
+        b"raspberrypi,2-model-b\0brcm,bcm2836\0",
+    );
+
+    let mut zimage = vec![0u8; 0x24];
+    zimage.extend_from_slice(&0x016f2818u32.to_le_bytes());
+    zimage.extend_from_slice(&0u32.to_le_bytes());
+    zimage.extend_from_slice(&(0x30 + kernel.len() as u32).to_le_bytes());
+    zimage.extend_from_slice(&0x04030201u32.to_le_bytes());
+    zimage.extend_from_slice(&kernel);
+    let offset = zimage.len();
+    zimage.extend_from_slice(&dtb);
+    let file = write_tempfile(&zimage);
+
+    let dir = tempfile::tempdir().unwrap();
+    let listing = extract_dtb(file.path(), dir.path());
+    assert_eq!(
+        listing,
+        format!(
+            "dtb-0.dtb: {} bytes at 0x{offset:x} of image\n\
+             \x20 Model:      Raspberry Pi 2 Model B\n\
+             \x20 Compatible: raspberrypi,2-model-b brcm,bcm2836\n",
+            dtb.len()
+        )
+    );
+    assert_eq!(fs::read(dir.path().join("dtb-0.dtb")).unwrap(), dtb);
+}
+
+#[test]
+fn test_extract_dtb_builtin() {
+    let first = dtb("Board A", b"vendor,board-a\0");
+    let second = dtb("Board B", b"vendor,board-b\0");
+    let mut data = first.clone();
+    data.extend_from_slice(&second);
+
+    // a fake FDT magic number outside of the built-in device tree blobs
+    let mut elf = ElfBuilder::default();
+    elf.section(".rodata", ElfBuilder::SHT_PROGBITS, b"\xd0\x0d\xfe\xed");
+    let shndx = elf.section(".init.data", ElfBuilder::SHT_PROGBITS, &data);
+    elf.symbol("__dtb_start", shndx, 0)
+        .symbol("__dtb_end", shndx, data.len() as u64);
+    let vmlinux = write_tempfile(&elf.build());
+
+    let dir = tempfile::tempdir().unwrap();
+    let listing = extract_dtb(vmlinux.path(), dir.path());
+    assert!(listing.contains("Model:      Board A"));
+    assert!(listing.contains("Model:      Board B"));
+    assert_eq!(fs::read(dir.path().join("dtb-0.dtb")).unwrap(), first);
+    assert_eq!(fs::read(dir.path().join("dtb-1.dtb")).unwrap(), second);
+}
+
+#[test]
+fn test_extract_dtb_fit() {
+    let dtb = dtb("Board FIT", b"vendor,board-fit\0");
+    let fit = FdtBuilder::default()
+        .begin_node("")
+        .begin_node("images")
+        .begin_node("kernel-1")
+        .prop("data", &gzip(b"Linux version 6.1.0"))
+        .prop_str("type", "kernel")
+        .prop_str("compression", "gzip")
+        .end_node()
+        .begin_node("fdt-1")
+        .prop("data", &dtb)
+        .prop_str("type", "flat_dt")
+        .end_node()
+        .end_node()
+        .end_node()
+        .finish();
+    let file = write_tempfile(&fit);
+
+    let dir = tempfile::tempdir().unwrap();
+    let listing = extract_dtb(file.path(), dir.path());
+    assert!(listing.starts_with("dtb-0.dtb: "));
+    assert!(listing.contains("Compatible: vendor,board-fit\n"));
+    assert_eq!(fs::read(dir.path().join("dtb-0.dtb")).unwrap(), dtb);
+    assert!(!dir.path().join("dtb-1.dtb").exists());
+}
+
+// BTF with an int type, as pahole generates for vmlinux
+fn btf(big_endian: bool) -> Vec<u8> {
+    let u32_bytes = |value: u32| {
+        if big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        }
+    };
+    let strings = b"\0int\0";
+    let mut btf = if big_endian { b"\xeb\x9f" } else { b"\x9f\xeb" }.to_vec();
+    btf.extend_from_slice(&[1, 0]);
+    for value in [24, 0, 16, 16, strings.len() as u32] {
+        btf.extend_from_slice(&u32_bytes(value));
+    }
+    // BTF_KIND_INT named "int" of 4 bytes and 32 bits
+    for value in [1, 1 << 24, 4, 32] {
+        btf.extend_from_slice(&u32_bytes(value));
+    }
+    btf.extend_from_slice(strings);
+    btf
+}
+
+fn extract_btf(path: &std::path::Path) -> (Vec<u8>, String) {
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("extract-btf")
+        .arg(path)
+        .output()
+        .unwrap();
+    (output.stdout, String::from_utf8(output.stderr).unwrap())
+}
+
+#[test]
+fn test_extract_btf_elf() {
+    let data = btf(false);
+    let mut elf = ElfBuilder::default();
+    elf.section(".BTF", ElfBuilder::SHT_PROGBITS, &data);
+    let vmlinux = write_tempfile(&elf.build());
+
+    let (btf, log) = extract_btf(vmlinux.path());
+    assert_eq!(btf, data);
+    assert!(log.starts_with("Found little-endian BTF at offset 0x"));
+    assert!(log.ends_with(": 16 bytes of types, 5 bytes of strings\n"));
+}
+
+#[test]
+fn test_extract_btf_raw() {
+    // a gzip compressed raw kernel image, with a truncated BTF header ahead
+    let data = btf(true);
+    let mut kernel = b"Linux version 6.1.0 \xeb\x9f\x01\x00".to_vec();
+    kernel.resize(4096, 0);
+    let offset = kernel.len();
+    kernel.extend_from_slice(&data);
+    kernel.resize(8192, 0);
+    let image = write_tempfile(&gzip(&kernel));
+
+    let (btf, log) = extract_btf(image.path());
+    assert_eq!(btf, data);
+    assert!(log.starts_with(&format!("Found big-endian BTF at offset 0x{offset:x}")));
+
+    let image = write_tempfile(b"Linux version 6.1.0");
+    let (btf, log) = extract_btf(image.path());
+    assert!(btf.is_empty());
+    assert!(log.contains("Cannot find BTF"));
+}
+
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut der = vec![tag];
+    match content.len() {
+        len @ 0..=0x7f => der.push(len as u8),
+        len @ 0x80..=0xff => der.extend_from_slice(&[0x81, len as u8]),
+        len => der.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]),
+    }
+    der.extend_from_slice(content);
+    der
+}
+
+// a self-signed X.509 certificate with dummy key and signature
+fn x509(cn: &str, serial: &[u8]) -> Vec<u8> {
+    let attribute = [der(0x06, b"\x55\x04\x03"), der(0x0c, cn.as_bytes())].concat();
+    let name = der(0x30, &der(0x31, &der(0x30, &attribute)));
+    // sha256WithRSAEncryption
+    let algorithm = [
+        der(0x06, b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x0b"),
+        der(0x05, b""),
+    ]
+    .concat();
+    let algorithm = der(0x30, &algorithm);
+    let validity = [der(0x17, b"221118103100Z"), der(0x18, b"21221025103100Z")].concat();
+    let key = [
+        der(0x06, b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x01"),
+        der(0x05, b""),
+    ]
+    .concat();
+    let key = [der(0x30, &key), der(0x03, &[0; 271])].concat();
+    let tbs = [
+        der(0xa0, &der(0x02, b"\x02")),
+        der(0x02, serial),
+        algorithm.clone(),
+        name.clone(),
+        der(0x30, &validity),
+        name,
+        der(0x30, &key),
+    ]
+    .concat();
+    der(
+        0x30,
+        &[der(0x30, &tbs), algorithm, der(0x03, &[0; 257])].concat(),
+    )
+}
+
+fn extract_certs(path: &std::path::Path, dir: &std::path::Path) -> String {
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("extract-certs")
+        .arg(path)
+        .arg("-d")
+        .arg(dir)
+        .output()
+        .unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_extract_certs_elf() {
+    let first = x509("Build time autogenerated kernel key", b"\x12\xab");
+    let second = x509("Distro CA", b"\x00\x80");
+    // with the space reserved for an extra certificate in between
+    let list = [first.clone(), vec![0; 64], second.clone()].concat();
+    // the size variable bounds the list, rather than the end of the section
+    let trailing = x509("Trailing data", b"\x02");
+    let mut elf = ElfBuilder::default();
+    let shndx = elf.section(
+        ".init.rodata",
+        ElfBuilder::SHT_PROGBITS,
+        &[list.clone(), trailing].concat(),
+    );
+    elf.symbol("system_certificate_list", shndx, 0);
+    let shndx = elf.section(
+        ".rodata",
+        ElfBuilder::SHT_PROGBITS,
+        &(list.len() as u64).to_le_bytes(),
+    );
+    elf.symbol("system_certificate_list_size", shndx, 0);
+    let vmlinux = write_tempfile(&elf.build());
+
+    let dir = tempfile::tempdir().unwrap();
+    let listing = extract_certs(vmlinux.path(), dir.path());
+    let lines: Vec<&str> = listing.lines().collect();
+    assert!(lines[0].starts_with(&format!("cert-0.pem: {} bytes at 0x", first.len())));
+    assert_eq!(
+        &lines[1..6],
+        [
+            "  Subject:    CN=Build time autogenerated kernel key",
+            "  Issuer:     CN=Build time autogenerated kernel key",
+            "  Serial:     12:ab",
+            "  Not before: 2022-11-18 10:31:00 UTC",
+            "  Not after:  2122-10-25 10:31:00 UTC",
+        ]
+    );
+    assert!(lines[6].starts_with(&format!("cert-1.pem: {} bytes at 0x", second.len())));
+    assert_eq!(lines[7], "  Subject:    CN=Distro CA");
+    assert_eq!(lines[9], "  Serial:     80");
+    assert_eq!(listing.matches(".pem:").count(), 2);
+
+    let pem = fs::read_to_string(dir.path().join("cert-1.pem")).unwrap();
+    assert!(pem.starts_with("-----BEGIN CERTIFICATE-----\nMIIC"));
+    assert!(pem.ends_with("\n-----END CERTIFICATE-----\n"));
+    assert!(pem.lines().all(|line| line.len() <= 64));
+}
+
+#[test]
+fn test_extract_certs_init_data() {
+    // without the list symbols, only the init data sections are searched
+    let mut elf = ElfBuilder::default();
+    elf.section(
+        ".rodata",
+        ElfBuilder::SHT_PROGBITS,
+        &x509("Not a key", b"\x03"),
+    );
+    elf.section(
+        ".init.data",
+        ElfBuilder::SHT_PROGBITS,
+        &x509("Module signing key", b"\x04"),
+    );
+    let vmlinux = write_tempfile(&elf.build());
+
+    let dir = tempfile::tempdir().unwrap();
+    let listing = extract_certs(vmlinux.path(), dir.path());
+    assert_eq!(listing.matches(".pem:").count(), 1);
+    assert!(listing.contains("  Subject:    CN=Module signing key\n"));
+
+    // nor with the size variable cut short by the end of its section
+    let mut elf = ElfBuilder::default();
+    let shndx = elf.section(
+        ".init.rodata",
+        ElfBuilder::SHT_PROGBITS,
+        &x509("Module signing key", b"\x05"),
+    );
+    elf.symbol("system_certificate_list", shndx, 0);
+    let shndx = elf.section(".rodata", ElfBuilder::SHT_PROGBITS, &[0x10, 0, 0, 0]);
+    elf.symbol("system_certificate_list_size", shndx, 0);
+    let vmlinux = write_tempfile(&elf.build());
+
+    let dir = tempfile::tempdir().unwrap();
+    let listing = extract_certs(vmlinux.path(), dir.path());
+    assert_eq!(listing.matches(".pem:").count(), 1);
+    assert!(listing.contains("  Serial:     05\n"));
+}
+
+#[test]
+fn test_extract_certs_raw() {
+    // a raw kernel image with a bogus DER sequence ahead
+    let cert = x509("Secure Boot Signer", b"\x01");
+    let mut kernel = b"Linux version 6.1.0 \x30\x82\x00\x10\x30\x82".to_vec();
+    kernel.resize(4096, 0);
+    kernel.extend_from_slice(&cert);
+    kernel.extend_from_slice(&cert);
+    kernel.resize(8192, 0);
+    let image = write_tempfile(&gzip(&kernel));
+
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path().join("certs");
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("extract-certs")
+        .arg(image.path())
+        .arg("-l")
+        .arg("-d")
+        .arg(&dir)
+        .output()
+        .unwrap();
+    // the duplicated one is skipped
+    let listing = String::from_utf8(output.stdout).unwrap();
+    assert!(listing.starts_with(&format!("cert-0.pem: {} bytes at 0x1000\n", cert.len())));
+    assert_eq!(listing.matches(".pem:").count(), 1);
+    assert!(listing.contains("  Subject:    CN=Secure Boot Signer\n"));
+    assert!(!dir.exists());
+
+    assert!(extract_certs(image.path(), &dir).contains("CN=Secure Boot Signer"));
+    assert!(dir.join("cert-0.pem").exists());
+}
+
+#[test]
+fn test_extract_tarball() {
+    let kernel = gzip(&ikcfg_gzip("CONFIG_BZIMAGE=y\n"));
+    let module = lzma::compress(&configs_ko("CONFIG_MODULE=y\n"), 6).unwrap();
+    let mut archive = tar(&[
+        ("build/README", b"CONFIG_README=y\n"),
+        ("build/arch/x86/boot/bzImage", &kernel),
+        ("build/modules/kernel/configs.ko.xz", &module),
+    ]);
+    let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+    encoder.write_all(&archive).unwrap();
+
+    let file = write_tempfile(&encoder.finish().unwrap());
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert_eq!(
+        configs,
+        "# Archive member: build/arch/x86/boot/bzImage\nCONFIG_BZIMAGE=y\n\
+         # Archive member: build/modules/kernel/configs.ko.xz\nCONFIG_MODULE=y\n"
+    );
+    assert!(metadata.contains("Archive member: build/arch/x86/boot/bzImage\n"));
+    assert!(metadata.contains("Archive member: build/modules/kernel/configs.ko.xz\n"));
+    assert!(!metadata.contains("README"));
+
+    // the entry of the largest size in base-256 beyond the end of file
+    archive[124..136].fill(0xff);
+    let file = write_tempfile(&archive);
+    let (_, metadata) = extract_config_with_metadata(file.path());
+    assert!(!metadata.contains("Archive member"));
+    assert!(!metadata.contains("panicked"));
+}
+
+#[test]
+fn test_extract_cpio() {
+    let module = configs_ko("CONFIG_MODULE=y\n");
+    let archive = newc(&[
+        ("lib/modules/6.1.0", 0o40755, b""),
+        (
+            "lib/modules/6.1.0/kernel/kernel/configs.ko",
+            0o100644,
+            &module,
+        ),
+    ]);
+
+    let file = write_tempfile(&archive);
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert_eq!(configs, "CONFIG_MODULE=y\n");
+    assert!(metadata.contains("Archive member: lib/modules/6.1.0/kernel/kernel/configs.ko\n"));
+}
+
+fn zip(files: &[(&str, bool, &[u8])]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut directory = Vec::new();
+    for (path, deflated, data) in files {
+        let mut crc = Crc::new();
+        crc.update(data);
+        let (method, compressed) = if *deflated {
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data).unwrap();
+            (8u16, encoder.finish().unwrap())
+        } else {
+            (0u16, data.to_vec())
+        };
+
+        // the fields shared by local header and central directory
+        let mut fields = Vec::new();
+        fields.extend_from_slice(&20u16.to_le_bytes());
+        fields.extend_from_slice(&0u16.to_le_bytes());
+        fields.extend_from_slice(&method.to_le_bytes());
+        fields.extend_from_slice(&0u32.to_le_bytes());
+        fields.extend_from_slice(&crc.sum().to_le_bytes());
+        fields.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(path.len() as u16).to_le_bytes());
+        fields.extend_from_slice(&0u16.to_le_bytes());
+
+        directory.extend_from_slice(b"PK\x01\x02\x14\x03");
+        directory.extend_from_slice(&fields);
+        // comment length, disk number and internal attributes
+        directory.extend_from_slice(&[0; 6]);
+        directory.extend_from_slice(&(0o100644u32 << 16).to_le_bytes());
+        directory.extend_from_slice(&(archive.len() as u32).to_le_bytes());
+        directory.extend_from_slice(path.as_bytes());
+
+        archive.extend_from_slice(b"PK\x03\x04");
+        archive.extend_from_slice(&fields);
+        archive.extend_from_slice(path.as_bytes());
+        archive.extend_from_slice(&compressed);
+    }
+
+    let offset = archive.len() as u32;
+    archive.extend_from_slice(&directory);
+    archive.extend_from_slice(b"PK\x05\x06\0\0\0\0");
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    archive.extend_from_slice(&offset.to_le_bytes());
+    archive.extend_from_slice(&0u16.to_le_bytes());
+    archive
+}
+
+#[test]
+fn test_extract_zip() {
+    let stored = gzip(&ikcfg_gzip("CONFIG_STORED=y\n"));
+    let deflated = configs_ko("CONFIG_DEFLATED=y\n");
+    let mut archive = zip(&[
+        ("artifacts/Image.gz", false, &stored),
+        ("artifacts/modules/configs.ko", true, &deflated),
+        ("artifacts/build.log", true, b"CONFIG_LOG=y\n"),
+    ]);
+
+    let file = write_tempfile(&archive);
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert_eq!(
+        configs,
+        "# Archive member: artifacts/Image.gz\nCONFIG_STORED=y\n\
+         # Archive member: artifacts/modules/configs.ko\nCONFIG_DEFLATED=y\n"
+    );
+    assert!(metadata.contains("Archive member: artifacts/Image.gz\n"));
+    assert!(metadata.contains("Archive member: artifacts/modules/configs.ko\n"));
+
+    // the central directory beyond the end of file isn't read
+    let end = archive.len() - 22;
+    archive[end + 12..end + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+    let file = write_tempfile(&archive);
+    let (_, metadata) = extract_config_with_metadata(file.path());
+    assert!(!metadata.contains("Archive member"));
+}
+
+fn ar(members: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut archive = b"!<arch>\n".to_vec();
+    for (name, data) in members {
+        let header = format!(
+            "{name:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            0,
+            0,
+            0,
+            100644,
+            data.len()
+        );
+        archive.extend_from_slice(header.as_bytes());
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().div_ceil(2) * 2, b'\n');
+    }
+    archive
+}
+
+#[test]
+fn test_extract_deb() {
+    let control = b"Package: linux-image-6.1.0-1-amd64\nVersion: 6.1.4-1\nArchitecture: amd64\n";
+    let kernel = gzip(&ikcfg_gzip("CONFIG_DEB=y\n"));
+    let data = tar(&[
+        ("./boot/config-6.1.0-1-amd64", b"CONFIG_BOOT=y\n"),
+        ("./boot/vmlinuz-6.1.0-1-amd64", &kernel),
+    ]);
+    let deb = ar(&[
+        ("debian-binary", b"2.0\n"),
+        ("control.tar.gz", &gzip(&tar(&[("./control", control)]))),
+        ("data.tar.xz", &lzma::compress(&data, 6).unwrap()),
+    ]);
+
+    let file = write_tempfile(&deb);
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert_eq!(configs, "CONFIG_DEB=y\n");
+    assert!(metadata.contains("Debian package: linux-image-6.1.0-1-amd64 6.1.4-1\n"));
+    assert!(metadata.contains("Package file: ./boot/vmlinuz-6.1.0-1-amd64\n"));
+}
+
+fn rpm_header(tags: &[(u32, &str)]) -> Vec<u8> {
+    let mut index = Vec::new();
+    let mut data = Vec::new();
+    for (tag, value) in tags {
+        // RPM_STRING_TYPE
+        for field in [*tag, 6, data.len() as u32, 1] {
+            index.extend_from_slice(&field.to_be_bytes());
+        }
+        data.extend_from_slice(value.as_bytes());
+        data.push(0);
+    }
+    let mut header = b"\x8e\xad\xe8\x01\0\0\0\0".to_vec();
+    header.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    header.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    header.extend_from_slice(&index);
+    header.extend_from_slice(&data);
+    header
+}
+
+#[test]
+fn test_extract_rpm() {
+    let mut rpm = b"\xed\xab\xee\xdb\x03\x00".to_vec();
+    rpm.resize(96, 0);
+    // the signature header padded to 8 bytes
+    rpm.extend_from_slice(&rpm_header(&[(1007, "sha256")]));
+    rpm.resize(rpm.len().div_ceil(8) * 8, 0);
+    rpm.extend_from_slice(&rpm_header(&[
+        (1000, "kernel-core"),
+        (1001, "6.1.0"),
+        (1002, "1.fc37"),
+    ]));
+
+    let kernel = gzip(&ikcfg_gzip("CONFIG_RPM=y\n"));
+    let payload = newc(&[
+        ("./lib/modules/6.1.0-1.fc37.x86_64", 0o40755, b""),
+        (
+            "./lib/modules/6.1.0-1.fc37.x86_64/config",
+            0o100644,
+            b"CONFIG_RPM=y\n",
+        ),
+        (
+            "./lib/modules/6.1.0-1.fc37.x86_64/vmlinuz",
+            0o100755,
+            &kernel,
+        ),
+    ]);
+    rpm.extend_from_slice(&zstd::encode_all(&payload[..], 3).unwrap());
+
+    let file = write_tempfile(&rpm);
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert_eq!(configs, "CONFIG_RPM=y\n");
+    assert!(metadata.contains("RPM package: kernel-core-6.1.0-1.fc37\n"));
+    assert!(metadata.contains("Package file: ./lib/modules/6.1.0-1.fc37.x86_64/vmlinuz\n"));
+
+    // the index entries beyond the end of file aren't read
+    rpm[104..108].copy_from_slice(&u32::MAX.to_be_bytes());
+    let file = write_tempfile(&rpm);
+    let (_, metadata) = extract_config_with_metadata(file.path());
+    assert!(!metadata.contains("RPM package"));
+}
+
+#[test]
+fn test_extract_disk_gpt() {
+    // GPT disk image with FAT32 ESP and ext4 root partitions
+    let (configs, metadata) =
+        extract_config_with_metadata(std::path::Path::new("tests/data/disk-gpt.img.xz"));
+    assert!(configs.starts_with(
+        "# Disk file: /EFI/Linux/debian-6.1.0-1-amd64.efi in partition 1 (FAT32)\nCONFIG_UKI=y\n"
+    ));
+    assert!(configs.contains("CONFIG_EXT4=y\n"));
+    assert!(configs.contains("# Automatically generated file; DO NOT EDIT.\nCONFIG_PLAIN=y\n"));
+    assert!(metadata.contains("GPT partitioned disk image\n"));
+    assert!(metadata.contains("Partition 1:    FAT32 of 35651584 bytes (EFI System Partition)\n"));
+    assert!(metadata.contains("Partition 2:    ext4 of 8388608 bytes (root)\n"));
+    assert!(metadata
+        .contains("Disk file: /EFI/Linux/debian-6.1.0-1-amd64.efi in partition 1 (FAT32)\n"));
+    assert!(metadata.contains("Disk file: /boot/vmlinuz-6.1.0-1-amd64 in partition 2 (ext4)\n"));
+    assert!(metadata.contains("Disk file: /boot/config-6.1.0-1-amd64 in partition 2 (ext4)\n"));
+    // symbolic links are not followed
+    assert!(!metadata.contains("Disk file: /boot/vmlinuz in"));
+
+    // the ext4 partition with the root directory in an extent at the last
+    // logical block, or with the block count overflowing, is skipped
+    let disk = lzma::decompress(&fs::read("tests/data/disk-gpt.img.xz").unwrap()).unwrap();
+    let start = u64::from_le_bytes(disk[1184..1192].try_into().unwrap()) as usize * 512;
+    let block_size =
+        1024 << u32::from_le_bytes(disk[start + 1048..start + 1052].try_into().unwrap());
+    let descriptor = start + 2 * block_size;
+    let table = u32::from_le_bytes(disk[descriptor + 8..descriptor + 12].try_into().unwrap());
+    let root = start + table as usize * block_size + 256;
+    let mut extent = disk.clone();
+    extent[root + 52..root + 56].copy_from_slice(&u32::MAX.to_le_bytes());
+    let mut blocks = disk;
+    blocks[start + 1360..start + 1364].copy_from_slice(&(1u32 << 22).to_le_bytes());
+    for disk in [extent, blocks] {
+        let file = write_tempfile(&disk);
+        let (configs, metadata) = extract_config_with_metadata(file.path());
+        assert_eq!(configs, "CONFIG_UKI=y\n");
+        assert!(metadata
+            .contains("Disk file: /EFI/Linux/debian-6.1.0-1-amd64.efi in partition 1 (FAT32)\n"));
+        assert!(!metadata.contains("in partition 2 (ext4)"));
+    }
+}
+
+#[test]
+fn test_extract_disk_mbr() {
+    // MBR disk image with FAT12 boot and ext2 root partitions of 1K blocks
+    let (configs, metadata) =
+        extract_config_with_metadata(std::path::Path::new("tests/data/disk-mbr.img.xz"));
+    assert!(
+        configs.starts_with("# Disk file: /vmlinuz-5.10.0 in partition 1 (FAT12)\nCONFIG_FAT=y\n")
+    );
+    assert_eq!(configs.matches("CONFIG_EXT2=y\n").count(), 2);
+    assert!(metadata.contains("MBR partitioned disk image\n"));
+    assert!(metadata.contains("Disk file: /vmlinuz-5.10.0 in partition 1 (FAT12)\n"));
+    assert!(metadata.contains("Disk file: /boot/vmlinuz-5.10.0 in partition 2 (ext2)\n"));
+    assert!(metadata.contains("Disk file: /boot/config-5.10.0 in partition 2 (ext2)\n"));
+
+    // the FAT beyond the end of file isn't read, but the other partition is
+    let mut disk = lzma::decompress(&fs::read("tests/data/disk-mbr.img.xz").unwrap()).unwrap();
+    let boot = u32::from_le_bytes(disk[454..458].try_into().unwrap()) as usize * 512;
+    disk[boot + 22..boot + 24].copy_from_slice(&u16::MAX.to_le_bytes());
+    let file = write_tempfile(&disk);
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert!(!configs.contains("CONFIG_FAT=y\n"));
+    assert!(!metadata.contains("(FAT12)"));
+    assert!(metadata.contains("Disk file: /boot/vmlinuz-5.10.0 in partition 2 (ext2)\n"));
+}
+
+// the directories of the paths from the deepest to the root, with their
+// children of subdirectories or the indexes of files
+fn directories(paths: &[&str]) -> Vec<(String, BTreeMap<String, Option<usize>>)> {
+    let mut dirs: BTreeMap<String, BTreeMap<String, Option<usize>>> = BTreeMap::new();
+    dirs.insert(String::new(), BTreeMap::new());
+    for (i, path) in paths.iter().enumerate() {
+        let components: Vec<&str> = path.split('/').collect();
+        for (depth, name) in components.iter().enumerate() {
+            let parent = components[..depth].join("/");
+            let child = (depth + 1 == components.len()).then_some(i);
+            dirs.entry(parent)
+                .or_default()
+                .insert(name.to_string(), child);
+        }
+    }
+    let mut dirs: Vec<_> = dirs.into_iter().collect();
+    dirs.sort_by_key(|(dir, _)| {
+        std::cmp::Reverse(match dir.as_str() {
+            "" => 0,
+            _ => dir.matches('/').count() + 1,
+        })
+    });
+    dirs
+}
+
+fn child_path(dir: &str, name: &str) -> String {
+    match dir {
+        "" => name.to_string(),
+        _ => format!("{dir}/{name}"),
+    }
+}
+
+// squashfs image of 4KB blocks, with the tails of files in a fragment, and
+// the inodes and the directories in a metadata block each
+fn squashfs(compression: u16, files: &[(&str, &[u8])]) -> Vec<u8> {
+    const BLOCK_SIZE: usize = 4096;
+    let compress = |data: &[u8]| -> Vec<u8> {
+        match compression {
+            1 => {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            3 => {
+                let mut lzo = minilzo_rs::LZO::init().unwrap();
+                lzo.compress(data).unwrap()
+            }
+            4 => lzma::compress(data, 6).unwrap(),
+            5 => lz4_flex::block::compress(data),
+            _ => zstd::bulk::compress(data, 3).unwrap(),
+        }
+    };
+    // the compressed block if smaller, otherwise stored with the flag in size
+    let block = |data: &[u8], stored: u32| -> (Vec<u8>, u32) {
+        let compressed = compress(data);
+        if compressed.len() < data.len() {
+            let size = compressed.len() as u32;
+            (compressed, size)
+        } else {
+            (data.to_vec(), data.len() as u32 | stored)
+        }
+    };
+    let metadata = |image: &mut Vec<u8>, data: &[u8]| {
+        let (data, size) = block(data, 0x8000);
+        image.extend_from_slice(&(size as u16).to_le_bytes());
+        image.extend_from_slice(&data);
+    };
+    let inode_header = |inodes: &mut Vec<u8>, kind: u16, mode: u16, number: usize| {
+        for field in [kind, mode, 0, 0] {
+            inodes.extend_from_slice(&field.to_le_bytes());
+        }
+        inodes.extend_from_slice(&0u32.to_le_bytes());
+        inodes.extend_from_slice(&(number as u32).to_le_bytes());
+    };
+
+    let mut image = vec![0u8; 96];
+    let mut fragment = Vec::new();
+    let mut inodes = Vec::new();
+    // the inode numbers and the offsets in the inode table
+    let mut refs = Vec::new();
+    for (path, data) in files {
+        let blocks_start = image.len() as u32;
+        let tail = data.len() % BLOCK_SIZE;
+        let mut sizes = Vec::new();
+        for data in data[..data.len() - tail].chunks(BLOCK_SIZE) {
+            // the blocks of zeros are holes
+            if data.iter().all(|&b| b == 0) {
+                sizes.push(0);
+                continue;
+            }
+            let (data, size) = block(data, 1 << 24);
+            image.extend_from_slice(&data);
+            sizes.push(size);
+        }
+
+        refs.push((path.to_string(), refs.len() + 1, inodes.len() as u16));
+        inode_header(&mut inodes, 2, 0o644, refs.len());
+        let (index, offset) = match tail {
+            0 => (u32::MAX, 0),
+            _ => (0, fragment.len() as u32),
+        };
+        for field in [blocks_start, index, offset, data.len() as u32] {
+            inodes.extend_from_slice(&field.to_le_bytes());
+        }
+        for size in sizes {
+            inodes.extend_from_slice(&size.to_le_bytes());
+        }
+        fragment.extend_from_slice(&data[data.len() - tail..]);
+    }
+
+    let paths: Vec<&str> = files.iter().map(|(path, _)| *path).collect();
+    let mut directory_table = Vec::new();
+    for (dir, children) in directories(&paths) {
+        let mut listing = Vec::new();
+        for field in [children.len() as u32 - 1, 0, 0] {
+            listing.extend_from_slice(&field.to_le_bytes());
+        }
+        for (name, child) in &children {
+            let path = child_path(&dir, name);
+            let (_, number, offset) = refs.iter().find(|(child, _, _)| *child == path).unwrap();
+            listing.extend_from_slice(&offset.to_le_bytes());
+            listing.extend_from_slice(&(*number as i16).to_le_bytes());
+            let kind: u16 = if child.is_some() { 2 } else { 1 };
+            listing.extend_from_slice(&kind.to_le_bytes());
+            listing.extend_from_slice(&(name.len() as u16 - 1).to_le_bytes());
+            listing.extend_from_slice(name.as_bytes());
+        }
+
+        refs.push((dir, refs.len() + 1, inodes.len() as u16));
+        inode_header(&mut inodes, 1, 0o755, refs.len());
+        inodes.extend_from_slice(&0u32.to_le_bytes());
+        inodes.extend_from_slice(&2u32.to_le_bytes());
+        // the size includes 3 bytes for "." and ".."
+        inodes.extend_from_slice(&(listing.len() as u16 + 3).to_le_bytes());
+        inodes.extend_from_slice(&(directory_table.len() as u16).to_le_bytes());
+        inodes.extend_from_slice(&0u32.to_le_bytes());
+        directory_table.extend_from_slice(&listing);
+    }
+    assert!(inodes.len() <= 8192 && directory_table.len() <= 8192 && fragment.len() <= 4096);
+
+    let fragment_start = image.len() as u64;
+    let (data, fragment_size) = block(&fragment, 1 << 24);
+    image.extend_from_slice(&data);
+    let inode_table_start = image.len() as u64;
+    metadata(&mut image, &inodes);
+    let directory_table_start = image.len() as u64;
+    metadata(&mut image, &directory_table);
+    let fragment_entries = image.len() as u64;
+    let mut entry = fragment_start.to_le_bytes().to_vec();
+    entry.extend_from_slice(&fragment_size.to_le_bytes());
+    entry.extend_from_slice(&0u32.to_le_bytes());
+    metadata(&mut image, &entry);
+    let fragment_table = image.len() as u64;
+    image.extend_from_slice(&fragment_entries.to_le_bytes());
+    let id_entries = image.len() as u64;
+    metadata(&mut image, &0u32.to_le_bytes());
+    let id_table = image.len() as u64;
+    image.extend_from_slice(&id_entries.to_le_bytes());
+    let bytes_used = image.len() as u64;
+    image.resize(image.len().next_multiple_of(BLOCK_SIZE), 0);
+
+    let mut superblock = b"hsqs".to_vec();
+    for field in [refs.len() as u32, 0, BLOCK_SIZE as u32, 1] {
+        superblock.extend_from_slice(&field.to_le_bytes());
+    }
+    // the compression, the block log, the flags, the id count and the version
+    for field in [compression, 12, 0, 1, 4, 0] {
+        superblock.extend_from_slice(&field.to_le_bytes());
+    }
+    let (_, _, root) = refs.last().unwrap();
+    for field in [
+        *root as u64,
+        bytes_used,
+        id_table,
+        u64::MAX,
+        inode_table_start,
+        directory_table_start,
+        fragment_table,
+        u64::MAX,
+    ] {
+        superblock.extend_from_slice(&field.to_le_bytes());
+    }
+    image[..96].copy_from_slice(&superblock);
+    image
+}
+
+#[test]
+fn test_extract_squashfs() {
+    // the kernel with a hole, full blocks and the tail in the fragment
+    let kernel = [
+        vec![0u8; 4096],
+        b"Linux version 6.1.0 ".repeat(500),
+        ikcfg_gzip("CONFIG_SQUASHFS=y\n"),
+    ]
+    .concat();
+    let module = configs_ko("CONFIG_IKCONFIG=m\n");
+    // gzip, lzo, xz, lz4 and zstd compressions
+    for compression in [1, 3, 4, 5, 6] {
+        let image = squashfs(
+            compression,
+            &[
+                ("boot/vmlinux-6.1.0", &kernel),
+                ("etc/banner", b"OpenWrt\n"),
+                ("lib/modules/6.1.0/kernel/kernel/configs.ko", &module),
+            ],
+        );
+
+        let file = write_tempfile(&image);
+        let (configs, metadata) = extract_config_with_metadata(file.path());
+        assert_eq!(
+            configs,
+            "# Disk file: /boot/vmlinux-6.1.0 (squashfs)\nCONFIG_SQUASHFS=y\n\
+             # Disk file: /lib/modules/6.1.0/kernel/kernel/configs.ko (squashfs)\nCONFIG_IKCONFIG=m\n"
+        );
+        assert!(metadata.contains("Read-only filesystem image\nFilesystem:     squashfs of"));
+        assert!(metadata.contains("Disk file: /boot/vmlinux-6.1.0 (squashfs)\n"));
+        assert!(metadata
+            .contains("Disk file: /lib/modules/6.1.0/kernel/kernel/configs.ko (squashfs)\n"));
+        assert!(!metadata.contains("/etc/banner"));
+    }
+}
+
+// MicroLZMA taken from the only LZMA2 chunk of an xz stream, with the first
+// byte of the range coder replaced by the inverted properties
+fn microlzma(data: &[u8]) -> Vec<u8> {
+    let xz = lzma::compress(data, 6).unwrap();
+    let chunk = &xz[12 + (xz[12] as usize + 1) * 4..];
+    assert!(chunk[0] >= 0xe0);
+    assert_eq!(
+        u16::from_be_bytes([chunk[1], chunk[2]]) as usize + 1,
+        data.len()
+    );
+    let len = u16::from_be_bytes([chunk[3], chunk[4]]) as usize + 1;
+    let (props, packed) = (chunk[5], &chunk[6..6 + len]);
+    assert_eq!(packed[0], 0);
+    [&[!props], &packed[1..]].concat()
+}
+
+#[derive(Clone, Copy)]
+enum ErofsLayout {
+    Inline,
+    Chunked,
+    // the algorithm, and whether the indexes are compacted and the physical
+    // clusters are big
+    Compressed(u8, bool, bool),
+}
+
+// the compacted indexes of logical clusters in types, clusterofs or delta[0],
+// and the blocks of heads, in 6 ones of 4 bytes up to 32-byte alignment,
+// followed by packs of 16 in 2 bytes, and the rest in packs of 2 in 4 bytes
+fn erofs_compacted(lclusters: &[(u16, u16, u32)], big_pcluster: bool) -> Vec<u8> {
+    let initial = lclusters.len().min(6);
+    let compacted_2b = (lclusters.len() - initial) / 16 * 16;
+    let mut indexes = Vec::new();
+    let mut start = 0;
+    for (count, size, vcnt) in [
+        (initial, 4, 2),
+        (compacted_2b, 2, 16),
+        (lclusters.len() - initial - compacted_2b, 4, 2),
+    ] {
+        for pack in lclusters[start..start + count].chunks(vcnt) {
+            let mut bits = vec![0u8; size * vcnt];
+            let encodebits = (bits.len() - 4) * 8 / vcnt;
+            for (i, &(kind, lo, _)) in pack.iter().enumerate() {
+                let pos = encodebits * i;
+                let value = ((kind as u32) << 12 | lo as u32) << (pos % 8);
+                for (byte, value) in bits[pos / 8..].iter_mut().zip(value.to_le_bytes()) {
+                    *byte |= value;
+                }
+            }
+            // the blocks of heads are counted from the one at the end of pack
+            let head = pack.iter().find(|(kind, _, _)| *kind != 2);
+            let base = match (head, pack[0]) {
+                (None, _) => 0,
+                (Some(&(_, _, blkaddr)), (2, lo, _)) if big_pcluster && lo & 0x800 != 0 => {
+                    blkaddr - (lo & 0x7ff) as u32
+                }
+                (Some(&(_, _, blkaddr)), _) if big_pcluster => blkaddr,
+                (Some(&(_, _, blkaddr)), _) => blkaddr - 1,
+            };
+            let len = bits.len();
+            bits[len - 4..].copy_from_slice(&base.to_le_bytes());
+            indexes.extend_from_slice(&bits);
+        }
+        start += count;
+    }
+    indexes
+}
+
+// EROFS image of 4KB blocks with the data blocks followed by the inodes, where
+// the directories are inline and the compressed extents span 2 blocks
+fn erofs(files: &[(&str, ErofsLayout, &[u8])]) -> Vec<u8> {
+    const BLOCK_SIZE: usize = 4096;
+    let mut blocks = Vec::new();
+    let mut append = |data: &[u8]| {
+        let blkaddr = 1 + blocks.len() / BLOCK_SIZE;
+        blocks.extend_from_slice(data);
+        blocks.resize(blocks.len().next_multiple_of(BLOCK_SIZE), 0);
+        blkaddr as u32
+    };
+
+    // the layout, the mode, the size, the union and the data after inode
+    let mut inodes: Vec<(u16, u16, usize, u32, Vec<u8>)> = Vec::new();
+    for (_, layout, data) in files {
+        let (layout, info, inline) = match *layout {
+            ErofsLayout::Inline => {
+                let tail = (data.len() - 1) % BLOCK_SIZE + 1;
+                let blkaddr = append(&data[..data.len() - tail]);
+                (2, blkaddr, data[data.len() - tail..].to_vec())
+            }
+            ErofsLayout::Chunked => {
+                let mut map = Vec::new();
+                for data in data.chunks(BLOCK_SIZE) {
+                    let blkaddr = match data.iter().all(|&b| b == 0) {
+                        true => u32::MAX,
+                        false => append(data),
+                    };
+                    map.extend_from_slice(&blkaddr.to_le_bytes());
+                }
+                (4, 0, map)
+            }
+            ErofsLayout::Compressed(algorithm, compacted, big_pcluster) => {
+                let mut lclusters = Vec::new();
+                let mut compressed_blocks = 0;
+                for extent in data.chunks(BLOCK_SIZE * 2) {
+                    let compressed = match algorithm {
+                        0 => lz4_flex::block::compress(extent),
+                        1 => microlzma(extent),
+                        2 => {
+                            let mut encoder = flate2::write::DeflateEncoder::new(
+                                Vec::new(),
+                                Compression::default(),
+                            );
+                            encoder.write_all(extent).unwrap();
+                            encoder.finish().unwrap()
+                        }
+                        _ => zstd::bulk::compress(extent, 3).unwrap(),
+                    };
+                    // the compressed data is padded with zeros ahead to blocks
+                    let (kind, pcluster) = match compressed.len() < extent.len() {
+                        true => {
+                            let len = compressed.len().next_multiple_of(BLOCK_SIZE);
+                            let padding = vec![0u8; len - compressed.len()];
+                            (1, [padding, compressed].concat())
+                        }
+                        false => (0, extent.to_vec()),
+                    };
+                    let count = pcluster.len().div_ceil(BLOCK_SIZE) as u16;
+                    assert!(big_pcluster || count == 1);
+                    compressed_blocks += count as u32;
+                    lclusters.push((kind, 0, append(&pcluster)));
+                    if extent.len() > BLOCK_SIZE {
+                        let delta = if big_pcluster { 0x800 | count } else { 1 };
+                        lclusters.push((2, delta, 0));
+                    }
+                }
+
+                // the map header with the advise and the algorithm
+                let advise = compacted as u16 | (big_pcluster as u16) << 1;
+                let mut map = vec![0u8; 4];
+                map.extend_from_slice(&advise.to_le_bytes());
+                map.extend_from_slice(&[algorithm, 0]);
+                if compacted {
+                    map.extend_from_slice(&erofs_compacted(&lclusters, big_pcluster));
+                } else {
+                    for (kind, lo, blkaddr) in lclusters {
+                        map.extend_from_slice(&kind.to_le_bytes());
+                        match kind {
+                            // the non-head has delta[0] to its head and delta[1] to the next
+                            2 => {
+                                for field in [0, lo, 1] {
+                                    map.extend_from_slice(&field.to_le_bytes());
+                                }
+                            }
+                            _ => {
+                                map.extend_from_slice(&lo.to_le_bytes());
+                                map.extend_from_slice(&blkaddr.to_le_bytes());
+                            }
+                        }
+                    }
+                }
+                (if compacted { 3 } else { 1 }, compressed_blocks, map)
+            }
+        };
+        inodes.push((layout, 0o100644, data.len(), info, inline));
+    }
+
+    // the directories of inline dirents, which are filled after the nids known
+    let paths: Vec<&str> = files.iter().map(|(path, _, _)| *path).collect();
+    let dirs = directories(&paths);
+    for (_, children) in &dirs {
+        let names: usize = children.keys().map(|name| name.len()).sum();
+        let size = (children.len() + 2) * 12 + names + 3;
+        inodes.push((2, 0o40755, size, 0, vec![0u8; size]));
+    }
+    let mut nids = Vec::new();
+    let mut nid = 0;
+    for (_, _, _, _, inline) in &inodes {
+        nids.push(nid);
+        nid += (32 + inline.len()).div_ceil(32) as u64;
+    }
+    let nid = |path: &str| match paths.iter().position(|file| *file == path) {
+        Some(i) => nids[i],
+        None => nids[files.len() + dirs.iter().position(|(dir, _)| dir == path).unwrap()],
+    };
+
+    for (i, (dir, children)) in dirs.iter().enumerate() {
+        let parent = dir.rsplit_once('/').map_or("", |(parent, _)| parent);
+        let mut entries = vec![(".", nid(dir), 2u8), ("..", nid(parent), 2)];
+        for (name, child) in children {
+            let file_type = if child.is_some() { 1 } else { 2 };
+            entries.push((name, nid(&child_path(dir, name)), file_type));
+        }
+        let mut dirents = Vec::new();
+        let mut names = Vec::new();
+        for (name, nid, file_type) in &entries {
+            dirents.extend_from_slice(&nid.to_le_bytes());
+            let nameoff = entries.len() * 12 + names.len();
+            dirents.extend_from_slice(&(nameoff as u16).to_le_bytes());
+            dirents.extend_from_slice(&[*file_type, 0]);
+            names.extend_from_slice(name.as_bytes());
+        }
+        inodes[files.len() + i].4 = [dirents, names].concat();
+    }
+
+    let meta_blkaddr = 1 + blocks.len() / BLOCK_SIZE;
+    let mut image = vec![0u8; BLOCK_SIZE];
+    image.extend_from_slice(&blocks);
+    for (ino, (layout, mode, size, info, inline)) in inodes.iter().enumerate() {
+        image.extend_from_slice(&(layout << 1).to_le_bytes());
+        for field in [0, *mode, 1] {
+            image.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [*size as u32, 0, *info, ino as u32 + 1, 0, 0] {
+            image.extend_from_slice(&field.to_le_bytes());
+        }
+        image.extend_from_slice(inline);
+        image.resize(image.len().next_multiple_of(32), 0);
+    }
+    image.resize(image.len().next_multiple_of(BLOCK_SIZE), 0);
+
+    let root = nid("") as u16;
+    let block_count = (image.len() / BLOCK_SIZE) as u32;
+    let superblock = &mut image[1024..1152];
+    superblock[0..4].copy_from_slice(&0xe0f5e1e2u32.to_le_bytes());
+    superblock[12] = 12;
+    superblock[14..16].copy_from_slice(&root.to_le_bytes());
+    superblock[16..24].copy_from_slice(&(inodes.len() as u64).to_le_bytes());
+    superblock[36..40].copy_from_slice(&block_count.to_le_bytes());
+    superblock[40..44].copy_from_slice(&(meta_blkaddr as u32).to_le_bytes());
+    // the compressed data is padded with zeros ahead
+    superblock[80..84].copy_from_slice(&1u32.to_le_bytes());
+    image
+}
+
+#[test]
+fn test_extract_erofs() {
+    let kernel = [
+        b"Linux version 6.1.0 ".repeat(300),
+        ikcfg_gzip("CONFIG_EROFS_FS=y\n"),
+    ]
+    .concat();
+    // the module with a hole in chunks
+    let module = [vec![0u8; 4096], configs_ko("CONFIG_IKCONFIG=m\n")].concat();
+    let image = erofs(&[
+        ("boot/vmlinuz-6.1.0", ErofsLayout::Inline, &kernel),
+        ("etc/os-release", ErofsLayout::Inline, b"ID=android\n"),
+        (
+            "lib/modules/6.1.0/kernel/kernel/configs.ko",
+            ErofsLayout::Chunked,
+            &module,
+        ),
+    ]);
+
+    let file = write_tempfile(&image);
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert_eq!(
+        configs,
+        "# Disk file: /boot/vmlinuz-6.1.0 (EROFS)\nCONFIG_EROFS_FS=y\n\
+         # Disk file: /lib/modules/6.1.0/kernel/kernel/configs.ko (EROFS)\nCONFIG_IKCONFIG=m\n"
+    );
+    assert!(metadata.contains("Read-only filesystem image\nFilesystem:     EROFS of"));
+    assert!(metadata.contains("Disk file: /boot/vmlinuz-6.1.0 (EROFS)\n"));
+    assert!(metadata.contains("Disk file: /lib/modules/6.1.0/kernel/kernel/configs.ko (EROFS)\n"));
+}
+
+#[test]
+fn test_extract_erofs_huge_inode() {
+    let mut image = erofs(&[("etc/os-release", ErofsLayout::Inline, b"ID=android\n")]);
+    // the root directory in an extended chunk-based inode of the maximum size
+    let root = u16::from_le_bytes([image[1038], image[1039]]) as usize;
+    let meta_blkaddr = u32::from_le_bytes(image[1064..1068].try_into().unwrap()) as usize;
+    let inode = &mut image[meta_blkaddr * 4096 + root * 32..][..64];
+    inode[0..2].copy_from_slice(&(4u16 << 1 | 1).to_le_bytes());
+    inode[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+    inode[16..20].copy_from_slice(&0u32.to_le_bytes());
+
+    let file = write_tempfile(&image);
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert_eq!(configs, "");
+    assert!(metadata.contains("Cannot find kernel config."));
+}
+
+#[test]
+fn test_extract_erofs_compressed() {
+    // the kernel of logical clusters enough for the compacted indexes of
+    // 2 bytes, with the config across extents
+    let filler: Vec<u8> = (0..6000u32)
+        .flat_map(|i| format!("{:08x}\n", i % 64).into_bytes())
+        .collect();
+    let kernel = [
+        &filler[..],
+        &ikcfg_gzip("CONFIG_EROFS_FS_ZIP=y\n"),
+        &filler[..],
+    ]
+    .concat();
+    // the incompressible data stored in big physical clusters
+    let mut state = 0x2545f491u32;
+    let random: Vec<u8> = (0..10000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    let big_kernel = [&random[..], &kernel[..]].concat();
+
+    // lz4, lzma, deflate and zstd in full and compacted indexes
+    for (layout, kernel) in [
+        (ErofsLayout::Compressed(0, false, false), &kernel),
+        (ErofsLayout::Compressed(0, true, false), &kernel),
+        (ErofsLayout::Compressed(1, true, false), &kernel),
+        (ErofsLayout::Compressed(2, true, false), &kernel),
+        (ErofsLayout::Compressed(0, false, true), &big_kernel),
+        (ErofsLayout::Compressed(3, true, true), &big_kernel),
+    ] {
+        let image = erofs(&[("vendor/boot/vmlinux", layout, kernel)]);
+        let file = write_tempfile(&image);
+        let (configs, metadata) = extract_config_with_metadata(file.path());
+        assert_eq!(configs, "CONFIG_EROFS_FS_ZIP=y\n");
+        assert!(metadata.contains("Disk file: /vendor/boot/vmlinux (EROFS)\n"));
+    }
+}
+
+// the config and the memory of 8 pages, with the config spanning from page 3 to 5
+fn vmcore_memory(page_size: usize) -> (String, Vec<u8>) {
+    let config: String = (0..1000u32)
+        .map(|i| format!("CONFIG_VMCORE_{:08x}={i}\n", i.wrapping_mul(2654435761)))
+        .collect();
+    let mut memory = vec![0xaau8; page_size * 3 + page_size / 2];
+    memory.extend_from_slice(&ikcfg_gzip(&config));
+    assert!((page_size * 5..page_size * 6).contains(&memory.len()));
+    memory.resize(page_size * 8, 0x55);
+    (config, memory)
+}
+
+#[test]
+fn test_extract_vmcore_elf() {
+    let (config, memory) = vmcore_memory(4096);
+    let mut note = Vec::new();
+    let desc = b"OSRELEASE=6.1.0\nPAGESIZE=4096\n";
+    note.extend_from_slice(&11u32.to_le_bytes());
+    note.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+    note.extend_from_slice(&0u32.to_le_bytes());
+    note.extend_from_slice(b"VMCOREINFO\0\0");
+    note.extend_from_slice(desc);
+
+    // the memory is split into 2 segments with a gap in the file
+    let segments = [
+        (0x1000u64, &note[..]),
+        (0x2000, &memory[..0x4800]),
+        (0x7000, &memory[0x4800..]),
+    ];
+    let mut core = b"\x7fELF\x02\x01\x01".to_vec();
+    core.resize(16, 0);
+    core.extend_from_slice(&4u16.to_le_bytes());
+    core.extend_from_slice(&62u16.to_le_bytes());
+    core.extend_from_slice(&1u32.to_le_bytes());
+    core.extend_from_slice(&0u64.to_le_bytes());
+    core.extend_from_slice(&64u64.to_le_bytes());
+    core.extend_from_slice(&0u64.to_le_bytes());
+    core.extend_from_slice(&0u32.to_le_bytes());
+    core.extend_from_slice(&64u16.to_le_bytes());
+    core.extend_from_slice(&56u16.to_le_bytes());
+    core.extend_from_slice(&(segments.len() as u16).to_le_bytes());
+    core.extend_from_slice(&[0; 6]);
+    let mut paddr = 0x1000000u64;
+    for (index, (offset, data)) in segments.iter().enumerate() {
+        // PT_NOTE and then PT_LOAD
+        let p_type: u32 = if index == 0 { 4 } else { 1 };
+        core.extend_from_slice(&p_type.to_le_bytes());
+        core.extend_from_slice(&7u32.to_le_bytes());
+        core.extend_from_slice(&offset.to_le_bytes());
+        core.extend_from_slice(&(0xffffffff80000000 + paddr).to_le_bytes());
+        core.extend_from_slice(&paddr.to_le_bytes());
+        core.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        core.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        core.extend_from_slice(&0u64.to_le_bytes());
+        paddr += data.len() as u64;
+    }
+    for (offset, data) in segments {
+        core.resize(offset as usize, 0);
+        core.extend_from_slice(data);
+    }
+
+    let file = write_tempfile(&core);
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert_eq!(configs, config);
+    assert!(metadata.contains("ELF core dump, 2 PT_LOAD segments of 32768 bytes"));
+    assert!(metadata.contains("Release:        6.1.0"));
+}
+
+#[test]
+fn test_extract_vmcore_kdump() {
+    const BLOCK_SIZE: usize = 4096;
+    let (config, memory) = vmcore_memory(BLOCK_SIZE);
+    // page 1 and 6 are excluded, the others are compressed differently
+    let dumped = [0, 2, 3, 4, 5, 7];
+    let mut lzo = minilzo_rs::LZO::init().unwrap();
+
+    let mut header = b"KDUMP   ".to_vec();
+    header.extend_from_slice(&6u32.to_le_bytes());
+    for field in [
+        "Linux",
+        "crash",
+        "6.1.0-vmcore",
+        "#1 SMP",
+        "x86_64",
+        "(none)",
+    ] {
+        let mut field = field.as_bytes().to_vec();
+        field.resize(65, 0);
+        header.extend_from_slice(&field);
+    }
+    header.resize(424, 0);
+    // status, block_size, sub_hdr_size, bitmap_blocks, max_mapnr
+    for value in [0, BLOCK_SIZE as u32, 1, 2, 8] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    header.resize(BLOCK_SIZE * 2, 0);
+
+    let mut bitmaps = vec![0u8; BLOCK_SIZE * 2];
+    bitmaps[0] = 0xff;
+    bitmaps[BLOCK_SIZE] = dumped.iter().fold(0, |bits, pfn| bits | (1 << pfn));
+
+    let mut descs = Vec::new();
+    let mut pages = Vec::new();
+    let mut offset = (header.len() + bitmaps.len() + dumped.len() * 24) as u64;
+    for (index, pfn) in dumped.into_iter().enumerate() {
+        let page = &memory[pfn * BLOCK_SIZE..(pfn + 1) * BLOCK_SIZE];
+        // raw, zlib, lzo and zstd
+        let (flags, data) = match index % 4 {
+            0 => (0u32, page.to_vec()),
+            1 => {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(page).unwrap();
+                (0x1, encoder.finish().unwrap())
+            }
+            2 => (0x2, lzo.compress(page).unwrap()),
+            _ => (0x20, zstd::bulk::compress(page, 3).unwrap()),
+        };
+        descs.extend_from_slice(&offset.to_le_bytes());
+        descs.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        descs.extend_from_slice(&flags.to_le_bytes());
+        descs.extend_from_slice(&0u64.to_le_bytes());
+        offset += data.len() as u64;
+        pages.extend_from_slice(&data);
+    }
+
+    let mut dump = header;
+    dump.extend_from_slice(&bitmaps);
+    dump.extend_from_slice(&descs);
+    dump.extend_from_slice(&pages);
+    let file = write_tempfile(&dump);
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert_eq!(configs, config);
+    assert!(metadata.contains("kdump compressed dump v6, 6 pages of 4096 bytes"));
+    assert!(metadata.contains("Release:        6.1.0-vmcore"));
+
+    // the bitmaps beyond the end of file aren't read
+    dump[436..440].copy_from_slice(&u32::MAX.to_le_bytes());
+    let file = write_tempfile(&dump);
+    let (configs, metadata) = extract_config_with_metadata(file.path());
+    assert_eq!(configs, "");
+    assert!(!metadata.contains("kdump compressed dump"));
+    assert!(metadata.contains("Cannot find kernel config."));
+}
+
+fn modinfo(path: &std::path::Path, kernel: Option<&std::path::Path>) -> String {
+    let mut cmd = Command::cargo_bin(BIN_NAME).unwrap();
+    cmd.arg("modinfo").arg(path);
+    if let Some(kernel) = kernel {
+        cmd.arg("-k").arg(kernel);
+    }
+    String::from_utf8(cmd.output().unwrap().stdout).unwrap()
+}
+
+#[test]
+fn test_modinfo() {
+    let module = fs::read("tests/data/module.ko").unwrap();
+    let compressed = write_tempfile(&lzma::compress(&module, 6).unwrap());
+    for path in [
+        std::path::Path::new("tests/data/module.ko"),
+        compressed.path(),
+    ] {
+        let output = modinfo(path, None);
+        assert!(output.contains("\nlicense:        GPL\n"));
+        assert!(output.contains("\nsrcversion:     8E1A6D4C2B5F3A9E7D0C1B2\n"));
+        assert!(output.contains("\ndepends:        \n"));
+        assert!(output.contains("\nvermagic:       6.1.0 SMP preempt mod_unload \n"));
+        assert!(output.contains("\nsig_id:         PKCS#7\n"));
+        assert!(output
+            .contains("\nsigner:         O=ikconfig, CN=Build time autogenerated kernel key\n"));
+        assert!(output.contains(
+            "\nsig_key:        0B:D3:43:24:35:83:CF:88:4B:B7:B8:3C:BB:53:F8:3D:0B:3A:18:F8\n"
+        ));
+        assert!(output.contains("\nsig_hashalgo:   sha256\n"));
+        assert!(!output.contains("sig_verify"));
+    }
+}
+
+#[test]
+fn test_modinfo_verify() {
+    let mut kernel = b"Linux version 6.1.0 ".to_vec();
+    kernel.resize(4096, 0);
+    kernel.extend_from_slice(&fs::read("tests/data/signing_key.der").unwrap());
+    kernel.resize(8192, 0);
+    let kernel = write_tempfile(&gzip(&kernel));
+    let module = std::path::Path::new("tests/data/module.ko");
+    assert!(modinfo(module, Some(kernel.path()))
+        .ends_with("sig_verify:     OK by O=ikconfig, CN=Build time autogenerated kernel key\n"));
+
+    let mut tampered = fs::read(module).unwrap();
+    tampered[0x100] ^= 1;
+    let tampered = write_tempfile(&tampered);
+    assert!(modinfo(tampered.path(), Some(kernel.path()))
+        .ends_with("sig_verify:     FAILED, bad signature\n"));
+
+    let mut other = b"Linux version 6.1.0 ".to_vec();
+    other.resize(8192, 0);
+    let other = write_tempfile(&gzip(&other));
+    assert!(modinfo(module, Some(other.path()))
+        .ends_with("sig_verify:     FAILED, no certificate of the signer\n"));
+}
+
+fn info(path: &std::path::Path) -> String {
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("info")
+        .arg(path)
+        .output()
+        .unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_info() {
+    let info = info(std::path::Path::new(PATH_VMLINUX_ZSTD));
+    assert!(info.starts_with("Format:       bzImage\nCompression:  zstd at offset 0x"));
+    assert!(info.contains("IKCFG_ST:     0x"));
+    assert!(info.contains("Config CRC32: 0x"));
+    assert!(info.contains("Version:      6.1.0-rc5+\n"));
+    assert!(info.contains("Architecture: x86\n"));
+    assert!(info.contains("Compiler:     gcc (GCC) 12.2.0\n"));
+    assert!(info.contains("Built by:     yestyle@Yarch\n"));
+    assert!(info.contains("Build date:   Fri Nov 18 "));
+    assert!(info.contains("Banner:       Linux version 6.1.0-rc5+ (yestyle@Yarch)"));
+}
+
+#[test]
+fn test_info_uimage() {
+    let config = "CONFIG_UIMAGE_INFO=y\n";
+    let mut kernel = b"Linux version 6.6.0 (builder@host) (gcc 13.2.0) #1 SMP\0".to_vec();
+    let start = kernel.len();
+    kernel.extend_from_slice(&ikcfg_gzip(config));
+    let file = write_tempfile(&uimage(2, 1, &gzip(&kernel)));
+
+    let info = info(file.path());
+    let mut crc = Crc::new();
+    crc.update(config.as_bytes());
+    assert!(info.starts_with(&format!(
+        "Format:       uImage\n\
+         Compression:  none\n\
+         IKCFG_ST:     0x{start:x}\n\
+         IKCFG_ED:     0x{:x}\n\
+         Config size:  {} bytes\n\
+         Config CRC32: 0x{:08x}\n\
+         Version:      6.6.0\n",
+        kernel.len() - "IKCFG_ED".len(),
+        config.len(),
+        crc.sum()
+    )));
+}
+
+#[test]
+fn test_info_truncated() {
+    let kernel = fs::read(PATH_VMLINUX_GZIP).unwrap();
+    let file = write_tempfile(&uimage(2, 0, &kernel[..100000]));
+
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("info")
+        .arg(file.path())
+        .output()
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&output.stdout).unwrap(),
+        "Format:       uImage > bzImage\n\
+         Compression:  gzip at offset 0x3ebf\n\
+         Error:        corrupt deflate stream\n"
+    );
+    assert!(std::str::from_utf8(&output.stderr)
+        .unwrap()
+        .ends_with("Cannot find vmlinux.\n"));
+}
+
+#[test]
+fn test_verbose() {
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-v")
+        .arg(PATH_VMLINUX_XZ)
+        .output()
+        .unwrap();
+    let logs = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(logs.contains("DEBUG] Trying xz decompression at 0x"));
+    assert!(logs.contains("DEBUG] Found 'IKCFG_ST\\x1f\\x8b\\x08' at 0x"));
+    assert!(!logs.contains("TRACE]"));
+    assert!(std::str::from_utf8(&output.stdout)
+        .unwrap()
+        .contains("CONFIG_KERNEL_XZ=y"));
+
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("RUST_LOG", "ikconfig=trace")
+        .arg("tests/data/config.gz")
+        .output()
+        .unwrap();
+    let logs = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(logs.contains("DEBUG] Decompressed the whole file of gzip"));
+    assert!(logs.contains("TRACE] Not found 'IKCFG_ST"));
+
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env_remove("RUST_LOG")
+        .arg(PATH_VMLINUX_XZ)
+        .output()
+        .unwrap();
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_kallsyms_system_map() {
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("kallsyms")
+        .arg(PATH_VMLINUX_ZSTD)
+        .output()
+        .unwrap();
+    let map = std::str::from_utf8(&output.stdout).unwrap();
+    assert_eq!(map.lines().count(), 22214);
+    assert!(map.starts_with("ffffffff81000000 T startup_64\n"));
+    assert!(map.contains("\nffffffff81000000 T _text\n"));
+    assert!(map.ends_with(" T _einittext\n"));
+    assert_eq!(output.stderr, b"Found 22214 symbols\n");
+}
+
+#[test]
+fn test_kallsyms_elf() {
+    // a raw image without ELF header is wrapped into a new ELF file
+    let mut raw = fs::read(PATH_VMLINUX_RAW).unwrap();
+    raw[..4].fill(0);
+    let file = write_tempfile(&raw);
+
+    for path in [PATH_VMLINUX_RAW, file.path().to_str().unwrap()] {
+        let output = Command::cargo_bin(BIN_NAME)
+            .unwrap()
+            .arg("kallsyms")
+            .arg("--elf")
+            .arg(path)
+            .output()
+            .unwrap();
+        let elf = output.stdout;
+        assert!(elf.starts_with(b"\x7fELF\x02\x01"));
+        // the section header string table is the last section
+        let shoff = u64::from_le_bytes(elf[0x28..0x30].try_into().unwrap()) as usize;
+        let shnum = u16::from_le_bytes(elf[0x3c..0x3e].try_into().unwrap()) as usize;
+        let shstrndx = u16::from_le_bytes(elf[0x3e..0x40].try_into().unwrap()) as usize;
+        assert_eq!(shoff + shnum * 64, elf.len());
+        assert_eq!(shstrndx, shnum - 1);
+        let names = String::from_utf8_lossy(&elf);
+        assert!(names.contains(".symtab\0.strtab\0.shstrtab\0"));
+        assert!(names.contains("\0startup_64\0"));
+    }
+}
+
+#[test]
+fn test_infer() {
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("infer")
+        .arg(PATH_VMLINUX_ZSTD)
+        .output()
+        .unwrap();
+    let inferred = std::str::from_utf8(&output.stdout).unwrap();
+    assert!(inferred.starts_with("#\n# INFERRED by ikconfig"));
+    assert!(inferred.contains("\n# high: symbol smp_call_function\nCONFIG_SMP=y\n"));
+    assert!(inferred.contains("\n# CONFIG_EXT4_FS is not set\n"));
+
+    // all the options inferred agree with the original config
+    let config = fs::read_to_string("tests/data/config").unwrap();
+    for line in inferred.lines() {
+        if let Some(option) = line.strip_suffix("=y") {
+            assert!(config.contains(&format!("\n{option}=y\n")), "{option}");
+        } else if let Some(option) = line
+            .strip_prefix("# ")
+            .and_then(|line| line.strip_suffix(" is not set"))
+        {
+            assert!(!config.contains(&format!("\n{option}=y\n")), "{option}");
+        }
+    }
+}
+
 fn compare_to_shell_script(path: &str) {
     println!("Extracting {}", path);
     let instant = Instant::now();